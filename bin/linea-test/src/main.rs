@@ -4,7 +4,9 @@ use std::path::PathBuf;
 
 use clap::Parser;
 use clients::Eth;
-use linea_verifier::{block_trace_to_pob, BlockTrace, LineaBatchVerifier, PobContext};
+use linea_verifier::{
+    block_trace_to_pob, BlockTrace, LineaBatchVerifier, LocalWitnessGenerator, PobContext,
+};
 use prover_types::SuccinctPobList;
 
 #[derive(Debug, Parser)]
@@ -17,6 +19,12 @@ struct Opt {
     besu: String,
     #[clap(long, default_value = "60")]
     timeout_secs: u64,
+    /// Compare the interning codecs on the generated PoB.
+    #[clap(long)]
+    bench_codecs: bool,
+    /// Build the trace from the local state at this path instead of Shomei.
+    #[clap(long)]
+    local_state: Option<PathBuf>,
 
     #[clap(default_value = "0")]
     block: u64,
@@ -46,6 +54,11 @@ async fn main() {
     let pob = block_trace_to_pob(block_trace.clone()).unwrap();
     let spob = SuccinctPobList::compress(&[pob.clone()]);
     log::info!("context size: {}", serde_json::to_vec(&spob).unwrap().len());
+    if opt.bench_codecs {
+        for bench in SuccinctPobList::bench_codecs(&[pob.clone()]).unwrap() {
+            log::info!("{}", bench);
+        }
+    }
     
     let ctx = PobContext::new(pob).unwrap();
     // let ctx = BlockTraceContext::new(block_trace);
@@ -58,6 +71,15 @@ async fn fetch_or_gen_block_trace(
     eth: &mut Option<Eth>,
     block_number: u64,
 ) -> BlockTrace {
+    if eth.is_none() {
+        *eth = Some(Eth::dial(&opt.besu, None).unwrap());
+    }
+    let eth = eth.as_ref().unwrap();
+    if let Some(path) = &opt.local_state {
+        let local = LocalWitnessGenerator::open(path).unwrap();
+        return local.build(eth, block_number).await.unwrap();
+    }
+
     let dir = PathBuf::new().join("testdata").join("downloaded");
     let _ = std::fs::create_dir(&dir);
 
@@ -67,10 +89,6 @@ async fn fetch_or_gen_block_trace(
         timeout_secs: Some(opt.timeout_secs),
     };
     let client = linea_shomei::Client::new(cfg).unwrap();
-    if eth.is_none() {
-        *eth = Some(Eth::dial(&opt.besu, None).unwrap());
-    }
-    let eth = eth.as_ref().unwrap();
 
     let fp = dir.join(format!("linea-mainnet-{}.json", block_number));
     match std::fs::try_exists(&fp).unwrap() {
@@ -0,0 +1,432 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+use alloy::consensus::{Transaction, TxEnvelope};
+use alloy::primitives::{keccak256, Bytes, B256};
+use alloy::rlp::Header;
+use clap::{Parser, Subcommand};
+use linea_verifier::{PobContext, StateDump};
+use prover_types::{
+    pob_list_hash, tx_envelope, verify_block_headers, Pob, StringInterningReader, SuccinctPobList,
+};
+use serde_json::Value;
+
+const L1_MESSAGE_TX_TYPE: u8 = 0x7e;
+
+#[derive(Debug, Parser)]
+struct Opt {
+    #[clap(subcommand)]
+    cmd: Command,
+}
+
+/// Every command accepts a `SuccinctPobList`, a `Vec<Pob>` or a single `Pob`,
+/// either as JSON or in the wire encoding.
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print the per-block stats and recompute the hashes.
+    Stats { file: PathBuf },
+    /// Decode the transactions.
+    Txs {
+        file: PathBuf,
+        /// Only decode the transactions of this block.
+        #[clap(long)]
+        block: Option<u64>,
+    },
+    /// Show the blocks, header fields, nodes and codes that differ.
+    Diff { a: PathBuf, b: PathBuf },
+    /// Print the Linea accounts and storage slots a block touches, as they
+    /// are before the block.
+    State {
+        file: PathBuf,
+        /// Only dump the state of this block.
+        #[clap(long)]
+        block: Option<u64>,
+        /// Print the dumps as JSON, keyed by block number.
+        #[clap(long)]
+        json: bool,
+    },
+}
+
+fn main() {
+    prover_types::init_log();
+
+    let opt = Opt::parse();
+    let result = match opt.cmd {
+        Command::Stats { file } => load(&file).map(|file| stats(&file)),
+        Command::Txs { file, block } => load(&file).map(|file| txs(&file, block)),
+        Command::Diff { a, b } => load(&a).and_then(|a| Ok(diff(&a, &load(&b)?))),
+        Command::State { file, block, json } => {
+            load(&file).and_then(|file| state(file, block, json))
+        }
+    };
+    if let Err(err) = result {
+        log::error!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+struct PobFile {
+    pob_list: Vec<Pob>,
+    /// The list hash carried by a `SuccinctPobList`.
+    list_hash: Option<B256>,
+}
+
+fn load(path: &PathBuf) -> Result<PobFile, String> {
+    let data = std::fs::read(path).map_err(|err| format!("read {:?}: {}", path, err))?;
+    let wire_err = |err| format!("decode {:?}: {:?}", path, err);
+    if data.starts_with(b"SPL") {
+        let list = SuccinctPobList::from_wire(&data).map_err(wire_err)?;
+        return unintern(list).map_err(|err| format!("decode {:?}: {}", path, err));
+    }
+    if data.starts_with(b"POB") {
+        let pob = Pob::<Bytes>::from_wire(&data).map_err(wire_err)?;
+        return Ok(PobFile {
+            pob_list: vec![pob],
+            list_hash: None,
+        });
+    }
+
+    if let Ok(list) = serde_json::from_slice::<SuccinctPobList>(&data) {
+        return unintern(list).map_err(|err| format!("decode {:?}: {}", path, err));
+    }
+    let pob_list = match serde_json::from_slice::<Vec<Pob>>(&data) {
+        Ok(pob_list) => pob_list,
+        Err(_) => vec![serde_json::from_slice::<Pob>(&data)
+            .map_err(|err| format!("decode {:?}: {}", path, err))?],
+    };
+    Ok(PobFile {
+        pob_list,
+        list_hash: None,
+    })
+}
+
+/// Unlike `SuccinctPobList::verify_unwrap`, a hash mismatch is not an error
+/// here: the received PoB hashes are kept so they can be checked against the
+/// content.
+fn unintern(list: SuccinctPobList) -> Result<PobFile, String> {
+    let reader =
+        StringInterningReader::try_new(&list.interning).map_err(|err| format!("{:?}", err))?;
+    let mut pob_list = Vec::with_capacity(list.pob.len());
+    for pob in list.pob {
+        pob_list.push(Pob {
+            data: pob
+                .data
+                .try_unintern(&reader)
+                .map_err(|err| format!("{:?}", err))?,
+            block: pob.block,
+            hash: pob.hash,
+        });
+    }
+    Ok(PobFile {
+        pob_list,
+        list_hash: Some(list.hash),
+    })
+}
+
+fn categories(pob: &Pob) -> Vec<(&'static str, &Vec<Bytes>)> {
+    let mut out = vec![
+        ("mpt_nodes", &pob.data.mpt_nodes),
+        ("codes", &pob.data.codes),
+    ];
+    if let Some(linea) = pob.data.linea() {
+        out.push(("traces", &linea.traces));
+        out.push(("proofs", &linea.proofs));
+    }
+    out
+}
+
+fn total_len(items: &[Bytes]) -> usize {
+    items.iter().map(|item| item.len()).sum()
+}
+
+fn check(expect: B256, got: B256) -> String {
+    if expect == got {
+        "ok".into()
+    } else {
+        format!("mismatch, expect {:?}, got {:?}", expect, got)
+    }
+}
+
+fn stats(file: &PobFile) {
+    let mut totals = BTreeMap::new();
+    for pob in &file.pob_list {
+        println!(
+            "block #{} {:?} chain={} chain_id={} version={}",
+            pob.block.number,
+            pob.block.block_hash.unwrap_or_default(),
+            pob.data.task_type().name(),
+            pob.data.chain_id,
+            pob.data.version,
+        );
+        let mut sizes = vec![("txs", &pob.block.transactions)];
+        sizes.extend(categories(pob));
+        for (name, items) in sizes {
+            let (count, len) = totals.entry(name).or_insert((0, 0));
+            *count += items.len();
+            *len += total_len(items);
+            println!(
+                "  {:<10} {:>8} {:>12} bytes",
+                name,
+                items.len(),
+                total_len(items)
+            );
+        }
+        println!("  pob_hash:  {}", check(pob.hash, pob.pob_hash()));
+        match pob.block.verify() {
+            Ok(()) => println!("  header:    ok"),
+            Err(err) => println!("  header:    {:?}", err),
+        }
+    }
+
+    println!("total: {} blocks", file.pob_list.len());
+    for (name, (count, len)) in totals {
+        println!("  {:<10} {:>8} {:>12} bytes", name, count, len);
+    }
+    if let Some(list_hash) = file.list_hash {
+        println!(
+            "  list_hash: {}",
+            check(list_hash, pob_list_hash(&file.pob_list))
+        );
+    }
+    match verify_block_headers(&file.pob_list) {
+        Ok(()) => println!("  headers:   ok"),
+        Err(err) => println!("  headers:   {:?}", err),
+    }
+}
+
+fn decode_tx(tx: &[u8]) -> Result<TxEnvelope, String> {
+    let envelope = tx_envelope(tx).ok_or("invalid rlp header")?;
+    // decode_exact expects the network encoding, typed transactions are
+    // wrapped in an RLP string.
+    let mut network = Vec::with_capacity(envelope.len() + 9);
+    if envelope[0] < 0xc0 {
+        Header {
+            list: false,
+            payload_length: envelope.len(),
+        }
+        .encode(&mut network);
+    }
+    network.extend_from_slice(envelope);
+    alloy::rlp::decode_exact(&network).map_err(|err| err.to_string())
+}
+
+fn txs(file: &PobFile, block: Option<u64>) {
+    for pob in &file.pob_list {
+        let number = pob.block.number.to::<u64>();
+        if block.map_or(false, |block| block != number) {
+            continue;
+        }
+        println!("block #{} txs={}", number, pob.block.transactions.len());
+        for (idx, tx) in pob.block.transactions.iter().enumerate() {
+            let Some(envelope) = tx_envelope(tx) else {
+                println!("  [{}] invalid rlp header", idx);
+                continue;
+            };
+            let hash = keccak256(envelope);
+            if envelope[0] == L1_MESSAGE_TX_TYPE {
+                println!(
+                    "  [{}] {:?} l1 message, {} bytes",
+                    idx,
+                    hash,
+                    envelope.len()
+                );
+                continue;
+            }
+            let tx = match decode_tx(tx) {
+                Ok(tx) => tx,
+                Err(err) => {
+                    println!("  [{}] {:?} invalid: {}", idx, hash, err);
+                    continue;
+                }
+            };
+            let from = match tx.recover_signer() {
+                Ok(from) => format!("{:?}", from),
+                Err(err) => format!("<{}>", err),
+            };
+            println!(
+                "  [{}] {:?} type={} from={} to={:?} nonce={} value={} gas_limit={} input={} bytes",
+                idx,
+                hash,
+                tx.tx_type() as u8,
+                from,
+                tx.to(),
+                tx.nonce(),
+                tx.value(),
+                tx.gas_limit(),
+                tx.input().len(),
+            );
+        }
+    }
+}
+
+/// Prints the leaves that differ between two JSON values, `prefix` is the
+/// path of `a` and `b`.
+fn diff_json(prefix: &str, a: &Value, b: &Value) {
+    if let (Value::Object(a), Value::Object(b)) = (a, b) {
+        let keys = a.keys().chain(b.keys()).collect::<BTreeSet<_>>();
+        for key in keys {
+            let path = format!("{}.{}", prefix, key);
+            match (a.get(key), b.get(key)) {
+                (Some(a), Some(b)) => diff_json(&path, a, b),
+                (a, b) => println!("  {}: {:?} -> {:?}", path, a, b),
+            }
+        }
+        return;
+    }
+    if a != b {
+        println!("  {}: {} -> {}", prefix, a, b);
+    }
+}
+
+fn diff_items(name: &str, a: &[Bytes], b: &[Bytes]) {
+    let index = |items: &[Bytes]| {
+        items
+            .iter()
+            .map(|item| (keccak256(item), item.len()))
+            .collect::<BTreeMap<_, _>>()
+    };
+    let (a, b) = (index(a), index(b));
+    let removed = a
+        .iter()
+        .filter(|(hash, _)| !b.contains_key(*hash))
+        .collect::<Vec<_>>();
+    let added = b
+        .iter()
+        .filter(|(hash, _)| !a.contains_key(*hash))
+        .collect::<Vec<_>>();
+    if removed.is_empty() && added.is_empty() {
+        return;
+    }
+    println!(
+        "  {}: -{} +{} ({} shared)",
+        name,
+        removed.len(),
+        added.len(),
+        a.len() - removed.len()
+    );
+    for (hash, len) in removed {
+        println!("    - {:?} {} bytes", hash, len);
+    }
+    for (hash, len) in added {
+        println!("    + {:?} {} bytes", hash, len);
+    }
+}
+
+fn diff_pob(a: &Pob, b: &Pob) {
+    let header = |pob: &Pob| {
+        let mut block = pob.block.clone();
+        block.transactions.clear();
+        serde_json::to_value(&block).unwrap()
+    };
+    diff_json("block", &header(a), &header(b));
+
+    let (txs_a, txs_b) = (&a.block.transactions, &b.block.transactions);
+    if txs_a != txs_b {
+        println!("  block.transactions: {} -> {}", txs_a.len(), txs_b.len());
+        for idx in 0..txs_a.len().max(txs_b.len()) {
+            let hash = |tx: Option<&Bytes>| tx.map(|tx| keccak256(tx_envelope(tx).unwrap_or(tx)));
+            let (tx_a, tx_b) = (hash(txs_a.get(idx)), hash(txs_b.get(idx)));
+            if tx_a != tx_b {
+                println!("    [{}] {:?} -> {:?}", idx, tx_a, tx_b);
+            }
+        }
+    }
+
+    // the item lists are compared by content below
+    let data = |pob: &Pob| {
+        let mut data = pob.data.clone();
+        for items in data.item_lists_mut() {
+            items.clear();
+        }
+        serde_json::to_value(&data).unwrap()
+    };
+    diff_json("data", &data(a), &data(b));
+
+    let (items_a, items_b) = (categories(a), categories(b));
+    let names = items_a
+        .iter()
+        .chain(&items_b)
+        .map(|(name, _)| *name)
+        .collect::<BTreeSet<_>>();
+    for name in names {
+        let find = |items: &[(&str, &Vec<Bytes>)]| {
+            items
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, items)| items.to_vec())
+                .unwrap_or_default()
+        };
+        diff_items(name, &find(&items_a), &find(&items_b));
+    }
+}
+
+fn diff(a: &PobFile, b: &PobFile) {
+    let by_number = |file: &PobFile| {
+        file.pob_list
+            .iter()
+            .map(|pob| (pob.block.number.to::<u64>(), pob))
+            .collect::<BTreeMap<_, _>>()
+    };
+    let (a, b) = (by_number(a), by_number(b));
+    let numbers = a.keys().chain(b.keys()).collect::<BTreeSet<_>>();
+    let mut same = 0;
+    for number in numbers {
+        match (a.get(number), b.get(number)) {
+            (Some(a), Some(b)) => {
+                if a.pob_hash() == b.pob_hash() {
+                    same += 1;
+                    continue;
+                }
+                println!("block #{}: {:?} -> {:?}", number, a.hash, b.hash);
+                diff_pob(a, b);
+            }
+            (Some(_), None) => println!("block #{}: only in a", number),
+            (None, Some(_)) => println!("block #{}: only in b", number),
+            (None, None) => unreachable!(),
+        }
+    }
+    println!("{} identical blocks", same);
+}
+
+fn print_dump(dump: &StateDump) {
+    println!("  root: {:?}", dump.root);
+    for (addr, acc) in &dump.accounts {
+        let Some(account) = &acc.account else {
+            println!("  {:?} missing", addr);
+            continue;
+        };
+        println!(
+            "  {:?} nonce={} balance={} root={:?} code_hash={:?} code_size={}",
+            addr,
+            account.nonce,
+            account.balance,
+            account.root,
+            account.keccak_code_hash,
+            account.code_size,
+        );
+        for (slot, value) in &acc.storage {
+            println!("    {:#x} = {:#x}", slot, value);
+        }
+    }
+}
+
+fn state(file: PobFile, block: Option<u64>, json: bool) -> Result<(), String> {
+    let mut dumps = BTreeMap::new();
+    for pob in file.pob_list {
+        let number = pob.block.number.to::<u64>();
+        if block.map_or(false, |block| block != number) {
+            continue;
+        }
+        let ctx = PobContext::new(pob).map_err(|err| format!("block #{}: {:?}", number, err))?;
+        let dump = ctx.dump_touched();
+        if !json {
+            println!("block #{} accounts={}", number, dump.accounts.len());
+            print_dump(&dump);
+        }
+        dumps.insert(number, dump);
+    }
+    if json {
+        println!("{}", serde_json::to_string_pretty(&dumps).unwrap());
+    }
+    Ok(())
+}
@@ -3,6 +3,7 @@
 use std::path::PathBuf;
 
 use clap::Parser;
+use prover_types::{Pob, SuccinctPobList};
 use scroll_executor::BlockTrace;
 use scroll_verifier::{
     block_trace_to_pob, BatchTask, Finalize, HardforkConfig, PobContext, ScrollBatchVerifier, ScrollExecutionNode,
@@ -12,6 +13,9 @@ use scroll_verifier::{
 struct Opt {
     #[clap(long, default_value = "")]
     download_from: String,
+    /// Compare the interning codecs on the PoBs of each batch.
+    #[clap(long)]
+    bench_codecs: bool,
     txs: Vec<PathBuf>,
 }
 
@@ -118,6 +122,17 @@ async fn main() {
             .flatten()
             .collect::<Vec<_>>();
 
+        if opt.bench_codecs {
+            // the verifier is built on the PoB types of `base`
+            let pob_list = chunks
+                .iter()
+                .map(|ctx| Pob::from(ctx.pob.clone()))
+                .collect::<Vec<_>>();
+            for bench in SuccinctPobList::bench_codecs(&pob_list).unwrap() {
+                log::info!("{}", bench);
+            }
+        }
+
         let first_block = chunks.first().unwrap();
 
         let fork = HardforkConfig::default_from_chain_id(first_block.pob.data.chain_id);
@@ -30,6 +30,8 @@ base::stack_error! {
     stack: {
         CommitAccount(addr: Address, acc: ZkStateAccount),
         CommitStorage(addr: Address, key: U256, value: U256),
+        CommitStorageBatch(addr: Address),
+        CommitAccounts(),
         CommitTx(number: u64, tx_hash: B256),
     }
 }
@@ -55,6 +57,8 @@ pub trait Context {
     fn state_root(&self) -> B256;
     fn withdrawal_root(&self) -> B256;
     fn block_hash(&self) -> B256;
+    /// Commitment to the witness data this block is executed with.
+    fn state_hash(&self) -> B256;
     fn verify_execution_result(&self, idx: usize, result: Self::ExecutionResult);
     fn commit_changes(&self, db: CacheDB<Self::DB>) -> Result<Self::CommitState, ExecutionError>;
 
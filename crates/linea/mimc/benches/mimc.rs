@@ -0,0 +1,57 @@
+//! `sum` over the message sizes hashed by the trie, and `sum` one by one
+//! against `sum_batch` over the nodes of a block.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use linea_mimc::{keccak_hash, sum, sum_batch, BLOCK_SIZE};
+
+/// `len` bytes, every block a field element.
+fn message(seed: u64, len: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(len);
+    let mut rnd = keccak_hash(&seed.to_be_bytes());
+    while out.len() < len {
+        let mut block = rnd;
+        // keep the block below the modulus
+        block[0] &= 0x0f;
+        out.extend_from_slice(&block);
+        rnd = keccak_hash(&rnd);
+    }
+    out.truncate(len);
+    out
+}
+
+fn bench_sum(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sum");
+    // a key, a branch node, an account, a 24KB contract code
+    for len in [BLOCK_SIZE, 2 * BLOCK_SIZE, 7 * BLOCK_SIZE, 24576 * 2] {
+        let msg = message(len as u64, len);
+        group.throughput(Throughput::Bytes(len as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(len), &msg, |b, msg| {
+            b.iter(|| sum(black_box(msg)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_batch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("batch");
+    for count in [4, 64, 1024] {
+        let msgs = (0..count)
+            .map(|n| message(n, 2 * BLOCK_SIZE))
+            .collect::<Vec<_>>();
+        group.throughput(Throughput::Elements(count));
+        group.bench_with_input(BenchmarkId::new("sum", count), &msgs, |b, msgs| {
+            b.iter(|| {
+                msgs.iter()
+                    .map(|msg| sum(black_box(msg)).unwrap())
+                    .collect::<Vec<_>>()
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("sum_batch", count), &msgs, |b, msgs| {
+            b.iter(|| sum_batch(black_box(msgs)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_sum, bench_batch);
+criterion_main!(benches);
@@ -1,28 +1,78 @@
-use lazy_static::lazy_static;
 use tiny_keccak::{Hasher, Keccak};
 
 use crate::{fr::BYTES, Fr};
 
 pub(crate) const MIMC_NB_ROUNDS: usize = 62;
-const SEED: &str = "seed";
 pub const BLOCK_SIZE: usize = BYTES;
 
-lazy_static! {
-    pub(crate) static ref MIMC_CONSTANTS: [Fr; MIMC_NB_ROUNDS] = init_constants();
-}
-
-fn init_constants() -> [Fr; MIMC_NB_ROUNDS] {
-    let mut mimc = [Fr::default(); MIMC_NB_ROUNDS];
-
-    let mut rnd = keccak_hash(SEED.as_bytes());
-    rnd = keccak_hash(&rnd);
-    for item in mimc.iter_mut() {
-        *item = Fr::from_be(&rnd).unwrap();
-        rnd = keccak_hash(&rnd);
-    }
-
-    mimc
-}
+/// The round constants of gnark-crypto: `keccak(keccak("seed"))` and its
+/// successive keccak hashes reduced into `Fr`, kept in Montgomery form so the
+/// rounds don't wait on a lazy initialization.
+#[rustfmt::skip]
+pub(crate) const MIMC_CONSTANTS: [Fr; MIMC_NB_ROUNDS] = [
+    Fr::from_mont([0x70966abe7daa2084, 0xdde6c47a5b297aa6, 0xaafbfcf12a1e6572, 0x0a88d5a64a29b698]),
+    Fr::from_mont([0x93376c8c6c20b948, 0xbd92745538c768e3, 0x0a092028f8f9f69f, 0x0441c101474bc9b9]),
+    Fr::from_mont([0x0ccdd526fb77eebc, 0x67604689707cbdf1, 0xb9bb02879520e74c, 0x0eba6c33b6a724f1]),
+    Fr::from_mont([0x5ea2e3e01a5ef30d, 0x68c46fe71066a9da, 0xd763d256f834671d, 0x0ac19064453b4315]),
+    Fr::from_mont([0x3403626b32498845, 0x2d78bc5c3de758f1, 0xbc5b4c2f5749dcf9, 0x00e76c1afa4bf295]),
+    Fr::from_mont([0xa23b4deb307fefa0, 0x385351ca98d0004c, 0x8a8ad858b01c1b7f, 0x0bb9f338efe7c98d]),
+    Fr::from_mont([0xc68f33a15054248f, 0x0657f27762f35f7d, 0x002d6fdda0267bd1, 0x0237e3f106595c52]),
+    Fr::from_mont([0xfb3b593ccf368b87, 0xee32323c2c77db89, 0xe5544723b4ce1755, 0x00b193c41e661c47]),
+    Fr::from_mont([0xeddf16c2ce45ddd9, 0x7a41ec01ca1e11be, 0x1b48f0866fa2fd36, 0x0385f2765bea9043]),
+    Fr::from_mont([0xaf65d527d0a582bd, 0xc5083c1001f095c7, 0xe74c5c1eafa2b834, 0x0c18b1f0d89da887]),
+    Fr::from_mont([0x95817e3a57cbd737, 0x9f20e7969d5ba8b1, 0xb0f79354bd81ed39, 0x0b620def38bbd331]),
+    Fr::from_mont([0x895f6788ea38923e, 0x95b8f291ce889f1b, 0x54ff752a6816a46a, 0x0c95abd4f7bc2f2e]),
+    Fr::from_mont([0x21130c801291bb03, 0x1218cfc4550c02a9, 0xba2574b8283d41ec, 0x0a5b9a2de6ad3a73]),
+    Fr::from_mont([0x00b816fc6d27b4f2, 0x5a6184a0bdcd56c2, 0xc2e2f4d88949399a, 0x0e1ae530ba6e0a58]),
+    Fr::from_mont([0x225bbd8cad4da6de, 0x8c28aa1bfa62a556, 0xbdd77daa93406d8d, 0x0c592553fbf8878f]),
+    Fr::from_mont([0xd145cb51017a5faf, 0xeb7182e39bec37be, 0xa4d775138ad847cd, 0x058500a270770991]),
+    Fr::from_mont([0xa79389d3c5320e32, 0x0efcd1a2beff8667, 0x892599c53c1d7cf7, 0x055727acfef50692]),
+    Fr::from_mont([0x37f726a295664039, 0xa24f2438bb7f74b5, 0x67929400752a0766, 0x120ea9da33c1c066]),
+    Fr::from_mont([0x5855969e9fdbae2b, 0xec1befab107d427c, 0x19158ef2a8be1dbe, 0x001603f71ea2d934]),
+    Fr::from_mont([0xbe4561bab79e889c, 0x4fcfab712ac21c7b, 0x83b76ff23b93e803, 0x05df9a2936e98d68]),
+    Fr::from_mont([0xbc4800afddeeeba6, 0x1688aa845c6b1aad, 0x6eda72dfab36827d, 0x0cdacd63bffa4453]),
+    Fr::from_mont([0x97c10d86cc64ae77, 0xfa6c567fbe64e584, 0x76b2c9f3f8d83445, 0x0b5755c9369f9b93]),
+    Fr::from_mont([0xcd492b89e22ddec9, 0xe18102389c3c2d8e, 0xca3632c400618a7a, 0x07c89ac8b97e18b9]),
+    Fr::from_mont([0x77b00ac9c99a521c, 0xf75aeb6dd6984760, 0x5de5207b346135e6, 0x00e487a2d770fa3e]),
+    Fr::from_mont([0x7a60776c521348c7, 0xe0a67d94ad11b5fb, 0x8fcb7abe627c00e9, 0x117ac23ab7aec70f]),
+    Fr::from_mont([0xc1717a68110c7455, 0x70f04c9fdd54e688, 0x8a6806e9d7e81af6, 0x117e6fe901207038]),
+    Fr::from_mont([0x696ec83d528a74e8, 0x094f03c703e0c546, 0xd5914b000f963532, 0x03e29ed7dd19bedb]),
+    Fr::from_mont([0x99171d60fa84506c, 0x18aec7e150207855, 0xb0f0c58acec10c80, 0x076ae13663e69153]),
+    Fr::from_mont([0x757a45d590786363, 0x1eb18157ca1c1597, 0x5a2c7a3d43eaea33, 0x11cadd559712ef6c]),
+    Fr::from_mont([0x538f2a426827e3c5, 0xe61f58b0d292c9d2, 0x9f39c67099266706, 0x0992749a64f5fd1d]),
+    Fr::from_mont([0x00229c7b2da70702, 0xbe90bee9fb3a2062, 0xd718a8bf2b326653, 0x0d22f420a3392d2b]),
+    Fr::from_mont([0x237e51c71814bdfa, 0xc5a499f31b7edd2f, 0xb0443f57467e3b89, 0x0052d24c432d5ede]),
+    Fr::from_mont([0x03dfd239384e8897, 0x856f15881a6195c5, 0x94ce4aa59da851cd, 0x111e78de85a8f498]),
+    Fr::from_mont([0x0ddf63ff9dc747ae, 0x059146788d150f4e, 0x3f043d6a15ac2cef, 0x051aed5da013dcde]),
+    Fr::from_mont([0x2d978d26741bbe23, 0x65ac9c40e136b5ec, 0x8f836c3384cacba9, 0x1052a26b9b5f52c2]),
+    Fr::from_mont([0xff7577ada734a435, 0xcf376d6e5f14a352, 0xa8d9a60a5064f2a3, 0x00221c0018c5e44f]),
+    Fr::from_mont([0x194570f6a53e4c30, 0x41eecb2b9323051e, 0x7a6ffeb12d35cfa5, 0x05021c5db48dedeb]),
+    Fr::from_mont([0xe3ac9511987d3345, 0x43f90f4f87769e70, 0x1691207b600d1da6, 0x09f94a323cbebfee]),
+    Fr::from_mont([0xb8f40a12733c14f2, 0xb093835e875e5341, 0x034aa3eca11049cd, 0x0394ba92baf16b89]),
+    Fr::from_mont([0x9cc8ece9727b0b76, 0x5879553ab06cced2, 0x9495067534dda993, 0x0aac4432a46aab77]),
+    Fr::from_mont([0xdc984ee930341c94, 0xf8ac9e252bd7f69d, 0x9bf76c46cedd6612, 0x116774ec699df091]),
+    Fr::from_mont([0xaeb6be958f15d166, 0xd56d7b9329a06989, 0xcc136ca9af3d1277, 0x0b02e5bda6ebef9b]),
+    Fr::from_mont([0xab34f49f1e30a00e, 0x191ea9918979c06e, 0x5084b7a8c3143950, 0x07edd6aa7564afd0]),
+    Fr::from_mont([0x03cce89af2e3cb4e, 0x6d132b3605370762, 0x61c8c825a336aebd, 0x05b5e1243b178d09]),
+    Fr::from_mont([0xab8f973331906a15, 0x6b3e443213936619, 0x595056b0e2367220, 0x02f59cab8bc526f6]),
+    Fr::from_mont([0x439924ac39584a80, 0x9dfa8ba45765a89f, 0xb14339bc2d6d8d2b, 0x05b43d5668b8377a]),
+    Fr::from_mont([0xef63bc9fec79bcc6, 0x7e472186f1116ad5, 0xedf7fa0d47ff29d2, 0x004854e6241cdfcb]),
+    Fr::from_mont([0xc62448199e0f6a42, 0x0e66bf0c4704e55c, 0x6cd584d6130f9d00, 0x033b647827486409]),
+    Fr::from_mont([0x1666264d516a5de6, 0xbcc310d8a3c6d6be, 0xc393eaf11b106356, 0x06f499e295b69072]),
+    Fr::from_mont([0xc81b2067a21f172f, 0xddc14b34375fa65b, 0xd727f1e4ac9a4770, 0x044cdb1875825933]),
+    Fr::from_mont([0x55d4b17e773393f1, 0x03cdee4604f6cfbb, 0xb8dffa87f30b7d89, 0x1215afafe7a27dbc]),
+    Fr::from_mont([0x4cf447d891e73868, 0x136c7a2f3d1184bb, 0xa4b36f78c6f3e95c, 0x041aab0bd9ab5f75]),
+    Fr::from_mont([0x1b574dfb8233a2cc, 0x1477fddbdbaba480, 0xded4c134666fcacc, 0x0de3f6ae1508736b]),
+    Fr::from_mont([0xb249e61402d20ffe, 0xa4b5100cae76a048, 0x56a8fe8d1a478b41, 0x08b3daafa3a9a94b]),
+    Fr::from_mont([0x911b1fb468d2f734, 0xf3a86e6e229e4d28, 0x3c8719eefe40b2ef, 0x0633834dab741212]),
+    Fr::from_mont([0x549cff7003f16643, 0x45ffeb8a725e433b, 0x4c057834f20c7b83, 0x0d5e16fdb63eadc9]),
+    Fr::from_mont([0x0d5a9acb91bb62da, 0x5b801a6744fff2f8, 0x1e40e908cda70031, 0x0cff37000ffbad92]),
+    Fr::from_mont([0x93bb7bc3157f4082, 0x4773b0617b77b0af, 0x9f018e977725d7d5, 0x068d312fa9ff089d]),
+    Fr::from_mont([0x8ef214f77607b461, 0x8514276d2822fe48, 0x23f3bca76581625a, 0x0e26b370196ee317]),
+    Fr::from_mont([0x6119fedb681ff2cd, 0x2c6fd93ff6e6c2e7, 0x3c708bf90306a0d2, 0x01245cf90659bb3e]),
+    Fr::from_mont([0x1c7e3c19778ab662, 0xd12bc40b5091e282, 0x7e188d8157688adf, 0x0fec12f047a07078]),
+    Fr::from_mont([0xa9bfb5d40a759be9, 0x45191b5f50dce6c0, 0xa0d4a60482568f8c, 0x002b8810533806e0]),
+];
 
 pub fn keccak_hash(data: &[u8]) -> [u8; 32] {
     let mut keccak = Keccak::v256();
@@ -31,3 +81,28 @@ pub fn keccak_hash(data: &[u8]) -> [u8; 32] {
     keccak.finalize(&mut out);
     out
 }
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    const SEED: &str = "seed";
+
+    pub(crate) fn init_constants() -> [Fr; MIMC_NB_ROUNDS] {
+        let mut mimc = [Fr::default(); MIMC_NB_ROUNDS];
+
+        let mut rnd = keccak_hash(SEED.as_bytes());
+        rnd = keccak_hash(&rnd);
+        for item in mimc.iter_mut() {
+            *item = Fr::from_be_reduced(&rnd).unwrap();
+            rnd = keccak_hash(&rnd);
+        }
+
+        mimc
+    }
+
+    #[test]
+    fn test_precomputed_constants() {
+        assert_eq!(MIMC_CONSTANTS, init_constants());
+    }
+}
@@ -22,9 +22,24 @@ use ff::PrimeField;
 use lazy_static::lazy_static;
 
 impl Fr {
+    /// Takes `repr` as the Montgomery form of the element, without any check.
+    pub(crate) const fn from_mont(repr: [u64; 4]) -> Self {
+        Fr(FrRepr(repr))
+    }
+
+    /// Decodes a canonical element, the encodings of the modulus and above
+    /// are rejected like gnark-crypto's `fr.BigEndian.Element` does.
     pub fn from_be(data: &[u8]) -> Result<Self, String> {
         let mut repr = FrRepr::default();
-        repr.read_be(data).unwrap();
+        repr.read_be(data).map_err(|err| format!("{:?}", err))?;
+        Fr::from_repr(repr).map_err(|err| format!("{:?}", err))
+    }
+
+    /// Decodes any 32 bytes, reduced modulo the field order like
+    /// gnark-crypto's `SetBytes`.
+    pub fn from_be_reduced(data: &[u8]) -> Result<Self, String> {
+        let mut repr = FrRepr::default();
+        repr.read_be(data).map_err(|err| format!("{:?}", err))?;
         let mut tmpfr = Fr(repr);
         tmpfr.mul_assign(&R_SQUARE);
 
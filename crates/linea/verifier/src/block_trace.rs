@@ -10,15 +10,56 @@ use alloy::{
     rlp::{encode_list, Encodable},
     rpc::types::{eth::Block, BlockTransactionsKind, TransactionReceipt},
 };
+use async_trait::async_trait;
 use base::eth::{Keypair, PrimitivesConvert, Eth, EthError};
 use linea_executor::{CommitState, Context, ExecutionError, SpecId, TxEnv};
 use linea_revm::db::CacheDB;
 use linea_shomei::{Client, MerkleAccountProof};
-use linea_zktrie::{MemStore, Trace};
-use prover_types::{Pob, PobBlock, PobData};
+use linea_zktrie::{MemStore, PrefixDB, PrefixDatabase, Trace, TraceLog};
+use prover_types::{ChainData, LineaData, Pob, PobBlock, PobData, POB_DATA_VERSION};
 use serde::{Deserialize, Serialize};
 
-use crate::{build_mem_db, ContextDB, DBError};
+use crate::{build_mem_db, ContextDB, DBError, StateWitness};
+
+/// Where the blocks and receipts are fetched from, the execution node or a
+/// stand-in serving recorded responses.
+#[async_trait]
+pub trait BlockSource: Sync {
+    async fn chain_id(&self) -> Result<u64, BlockTraceError>;
+
+    async fn block(&self, number: u64) -> Result<Block, BlockTraceError>;
+
+    async fn receipts(&self, number: u64) -> Result<Vec<TransactionReceipt>, BlockTraceError>;
+}
+
+#[async_trait]
+impl BlockSource for Eth {
+    async fn chain_id(&self) -> Result<u64, BlockTraceError> {
+        Ok(self.provider().get_chain_id().await.map_err(EthError::Rpc)?)
+    }
+
+    async fn block(&self, number: u64) -> Result<Block, BlockTraceError> {
+        let block_id = BlockId::Number(number.into());
+        let block = self
+            .provider()
+            .get_block(block_id, BlockTransactionsKind::Full)
+            .await
+            .map_err(EthError::Rpc)
+            .map_err(BlockTraceError::FetchBlock(&number))?
+            .ok_or(BlockTraceError::BlockNotFound(number))?;
+        Ok(block)
+    }
+
+    async fn receipts(&self, number: u64) -> Result<Vec<TransactionReceipt>, BlockTraceError> {
+        let receipts = self
+            .provider()
+            .get_block_receipts(number.into())
+            .await
+            .map_err(EthError::Rpc)?
+            .ok_or(BlockTraceError::BlockReceiptsNotFound(number))?;
+        Ok(receipts)
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct BlockTrace {
@@ -41,24 +82,9 @@ impl BlockTrace {
         shomei: &Client,
         block_number: u64,
     ) -> Result<BlockTrace, BlockTraceError> {
-        let chain_id = eth.provider().get_chain_id().await.map_err(EthError::Rpc)?;
-
-        let block_id = BlockId::Number(block_number.into());
-
-        let block = eth
-            .provider()
-            .get_block(block_id, BlockTransactionsKind::Full)
-            .await
-            .map_err(EthError::Rpc)
-            .map_err(BlockTraceError::FetchBlock(&block_number))?
-            .ok_or(BlockTraceError::BlockNotFound(block_number))?;
-
-        let receipts = eth
-            .provider()
-            .get_block_receipts(block_number.into())
-            .await
-            .map_err(EthError::Rpc)?
-            .ok_or(BlockTraceError::BlockReceiptsNotFound(block_number))?;
+        let chain_id = eth.chain_id().await?;
+        let block = eth.block(block_number).await?;
+        let receipts = eth.receipts(block_number).await?;
 
         let prestate_block_number = block_number - 1;
         let prestate_block_id = BlockId::Number(prestate_block_number.into());
@@ -121,17 +147,48 @@ impl BlockTrace {
     }
 }
 
-pub struct BlockTraceContext {
+/// The traces and the witness captured while a block is executed on top of
+/// a locally maintained state.
+#[derive(Default)]
+pub(crate) struct WitnessRecorder {
+    pub(crate) traces: TraceLog,
+    pub(crate) witness: Mutex<Option<StateWitness>>,
+}
+
+pub struct BlockTraceContext<D: PrefixDatabase = PrefixDB> {
     bc: BlockTrace,
-    db: Arc<Mutex<MemStore>>,
+    db: D,
+    recorder: Option<Arc<WitnessRecorder>>,
+    // the commitment of the PoB converted from `bc`
+    state_hash: B256,
 }
 
 impl BlockTraceContext {
-    pub fn new(bc: BlockTrace) -> Self {
-        let db = bc.clone().build_db().unwrap();
-        let db = Arc::new(Mutex::new(db));
+    pub fn new(bc: BlockTrace) -> Result<Self, BlockTraceError> {
+        let db = bc.clone().build_db()?;
+        let db = PrefixDB::new(Address::default(), Arc::new(Mutex::new(db)));
+        Self::with_db(bc, db)
+    }
+}
 
-        Self { bc, db }
+impl<D: PrefixDatabase> BlockTraceContext<D> {
+    /// `db` holds the state at `bc.zk_parent_state_root_hash`.
+    pub fn with_db(bc: BlockTrace, db: D) -> Result<Self, BlockTraceError> {
+        let number = bc.block.header.number.unwrap_or_default();
+        let state_hash = block_trace_to_pob(bc.clone())
+            .ok_or(BlockTraceError::ConvertToPobFailed(number))?
+            .state_commitment();
+        Ok(Self {
+            bc,
+            db,
+            recorder: None,
+            state_hash,
+        })
+    }
+
+    pub(crate) fn with_recorder(mut self, recorder: Arc<WitnessRecorder>) -> Self {
+        self.recorder = Some(recorder);
+        self
     }
 
     pub fn seal_header(&self) -> B256 {
@@ -168,17 +225,21 @@ impl BlockTraceContext {
     }
 }
 
-impl Context for BlockTraceContext {
+impl<D: PrefixDatabase> Context for BlockTraceContext<D> {
     type ExecutionResult = linea_revm::primitives::ExecutionResult;
     type CommitState = linea_executor::CommitState;
-    type DB = ContextDB;
+    type DB = ContextDB<D>;
 
     fn db(&self) -> Self::DB {
-        ContextDB::new(
+        let db = ContextDB::from_db(
             self.bc.zk_parent_state_root_hash,
-            self.db.clone(),
+            self.db.new_prefix(Address::default()),
             self.bc.block_hashes.clone(),
-        )
+        );
+        match &self.recorder {
+            Some(recorder) => db.with_trace_log(recorder.traces.clone()),
+            None => db,
+        }
     }
 
     fn spec_id(&self) -> SpecId {
@@ -201,6 +262,10 @@ impl Context for BlockTraceContext {
         self.bc.block.header.hash.unwrap()
     }
 
+    fn state_hash(&self) -> B256 {
+        self.state_hash
+    }
+
     fn coinbase(&self) -> Address {
         let extra_data = self.bc.block.header.extra_data.as_ref();
         let mut sig_array = [0_u8; 65];
@@ -292,6 +357,10 @@ impl Context for BlockTraceContext {
     }
 
     fn commit_changes(&self, mut db: CacheDB<Self::DB>) -> Result<CommitState, ExecutionError> {
+        if let Some(recorder) = &self.recorder {
+            let witness = db.db.state_witness(&db.accounts)?;
+            *recorder.witness.lock().unwrap() = Some(witness);
+        }
         db.db.commit_changes(self.old_state_root(), &db.accounts)
     }
 }
@@ -303,13 +372,20 @@ base::stack_error! {
     error: {
         BlockNotFound(u64),
         BlockReceiptsNotFound(u64),
+        LocalStateNotInitialized,
+        LocalStateHeadMismatch { head: u64, block: u64 },
+        ConvertToPobFailed(u64),
     },
     wrap: {
         Eth(EthError),
+        DB(DBError),
         Zktrie(linea_zktrie::Error),
+        Execution(ExecutionError),
     },
     stack: {
         BuildMemStore(),
+        ExecuteBlock(block_number: u64),
+        CommitLocalState(block_number: u64),
         FetchBlock(block_number: u64),
         FetchBlockProof(block_number: u64),
         FetchAccountProof(block_number: u64),
@@ -352,31 +428,28 @@ pub fn block_trace_to_pob(trace: BlockTrace) -> Option<Pob<Bytes>> {
         gas_used: header.gas_used.to(),
     };
 
-    let mut linea_traces = vec![];
+    let mut traces = vec![];
     for item in trace.traces {
-        linea_traces.push(serde_json::to_vec(&item).unwrap().into());
+        traces.push(serde_json::to_vec(&item).unwrap().into());
     }
-    let mut linea_proofs = vec![];
+    let mut proofs = vec![];
     for item in trace.state_proof {
-        linea_proofs.push(serde_json::to_vec(&item).unwrap().into());
+        proofs.push(serde_json::to_vec(&item).unwrap().into());
     }
 
     let data = PobData {
+        version: POB_DATA_VERSION,
         chain_id: trace.chain_id,
         coinbase: None,
         prev_state_root: trace.zk_parent_state_root_hash,
         block_hashes: trace.block_hashes,
         mpt_nodes: vec![],
         codes: trace.codes,
-
-        // scroll
-        start_l1_queue_index: 0,
-        withdrawal_root: B256::default(),
-
-        // linea
-        linea_traces,
-        linea_proofs,
-        linea_zkroot: trace.zk_end_state_root_hash,
+        chain: ChainData::Linea(LineaData {
+            traces,
+            proofs,
+            zkroot: trace.zk_end_state_root_hash,
+        }),
     };
     Some(Pob::new(block, data))
 }
@@ -1,7 +1,10 @@
 use std::{
     collections::{BTreeMap, HashMap},
     convert::Infallible,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
 };
 
 use alloy::primitives::{Address, Bytes, U256};
@@ -13,7 +16,7 @@ use linea_revm::{db::DbAccount, DatabaseRef};
 use linea_shomei::MerkleAccountProof;
 use linea_zktrie::{
     mimc_safe, mimc_safe_code_hash, mimc_safe_encode, parse_prefix, trie_hash, Database, MemStore,
-    PrefixDB, Trace, ZkTrie,
+    PrefixDB, PrefixDatabase, Trace, TraceLog, WriteBatch, ZkTrie, EMPTY_TRIE_NODE_HASH,
 };
 use prover_types::B256;
 
@@ -93,17 +96,33 @@ pub(crate) fn get_hkey(key: &[u8]) -> Result<B256, linea_zktrie::Error> {
     })
 }
 
-pub struct ContextDB {
-    zktrie: ZkTrie<PrefixDB>,
-    pub db: PrefixDB,
+/// The proofs and codes of the accounts and storage slots a block loaded,
+/// what Shomei's `linea_getProof` and `eth_getCode` return for them.
+#[derive(Debug, Clone, Default)]
+pub struct StateWitness {
+    pub state_proof: Vec<MerkleAccountProof>,
+    pub codes: Vec<Bytes>,
+}
+
+pub struct ContextDB<D: PrefixDatabase = PrefixDB> {
+    zktrie: ZkTrie<D>,
+    pub db: D,
     pub block_hashes: BTreeMap<u64, B256>,
 
     cache: Arc<Mutex<BTreeMap<(Address, U256), U256>>>,
+    trace_log: Option<TraceLog>,
+    commit_threads: usize,
 }
 
 impl ContextDB {
     pub fn new(root: B256, db: Arc<Mutex<MemStore>>, block_hashes: BTreeMap<u64, B256>) -> Self {
-        let db = PrefixDB::new(Address::default(), db);
+        Self::from_db(root, PrefixDB::new(Address::default(), db), block_hashes)
+    }
+}
+
+impl<D: PrefixDatabase> ContextDB<D> {
+    /// `db` is the prefix of the world state trie.
+    pub fn from_db(root: B256, db: D, block_hashes: BTreeMap<u64, B256>) -> Self {
         let zktrie = ZkTrie::new(root);
         let cache = Arc::new(Mutex::new(BTreeMap::new()));
         Self {
@@ -111,15 +130,97 @@ impl ContextDB {
             zktrie,
             cache,
             block_hashes,
+            trace_log: None,
+            commit_threads: 1,
+        }
+    }
+
+    /// Commits the storage tries of up to `threads` accounts in parallel,
+    /// the state root doesn't depend on it.
+    pub fn with_commit_threads(mut self, threads: usize) -> Self {
+        self.commit_threads = threads.max(1);
+        self
+    }
+
+    /// Records the traces of the world state and storage tries into `log`,
+    /// in the format of `rollup_getZkEVMStateMerkleProofV0`.
+    pub fn with_trace_log(mut self, log: TraceLog) -> Self {
+        let root = *self.zktrie.top_root_hash();
+        self.trace_log = Some(log);
+        self.zktrie = self.trie(root, None);
+        self
+    }
+
+    fn trie(&self, root: B256, account: Option<&Address>) -> ZkTrie<D> {
+        let trie = ZkTrie::new(root);
+        match &self.trace_log {
+            Some(log) => {
+                let location = account.map(|addr| addr.to_vec().into());
+                trie.with_trace_log(location.unwrap_or_default(), log.clone())
+            }
+            None => trie,
         }
     }
 
+    /// The root of the world state the `ContextDB` reads from.
+    pub fn root(&self) -> B256 {
+        *self.zktrie.top_root_hash()
+    }
+
     pub fn get_acc(&self, addr: &Address) -> Option<ZkStateAccount> {
         let hkey = get_hkey(addr.as_ref()).unwrap();
         let result = self.zktrie.read(&self.db, hkey, addr.as_ref()).unwrap()?;
         ZkStateAccount::decode(&result)
     }
 
+    /// Proves the accounts and storage slots loaded by the execution against
+    /// the root the `ContextDB` was created at, it has to be called before
+    /// the changes are committed. Nothing is recorded in the trace log.
+    pub fn state_witness(
+        &self,
+        accounts: &HashMap<Address, DbAccount>,
+    ) -> Result<StateWitness, linea_zktrie::Error> {
+        let zktrie = ZkTrie::<D>::new(*self.zktrie.top_root_hash());
+        let mut addrs = accounts.keys().collect::<Vec<_>>();
+        addrs.sort();
+
+        let mut witness = StateWitness::default();
+        let mut codes = BTreeMap::new();
+        for addr in addrs {
+            let hkey = get_hkey(addr.as_ref())?;
+            let account_proof = zktrie.prove(&self.db, hkey, addr.as_ref())?;
+            let acc = zktrie
+                .read(&self.db, hkey, addr.as_ref())?
+                .and_then(|val| ZkStateAccount::decode(&val));
+            let storage_root = match &acc {
+                Some(acc) => {
+                    if let Some(code) = self.db.get_code(&acc.keccak_code_hash) {
+                        codes.insert(acc.keccak_code_hash, code.as_ref().clone());
+                    }
+                    acc.root
+                }
+                None => *EMPTY_TRIE_NODE_HASH,
+            };
+
+            let db = self.db.new_prefix(*addr);
+            let storage = ZkTrie::<D>::new(storage_root);
+            let mut slots = accounts[addr].storage.keys().collect::<Vec<_>>();
+            slots.sort();
+            let mut storage_proofs = Vec::with_capacity(slots.len());
+            for slot in slots {
+                let key_bytes = slot.to_be_bytes::<32>();
+                let hkey = get_hkey(&key_bytes)?;
+                storage_proofs.push(storage.prove(&db, hkey, &key_bytes)?);
+            }
+            witness.state_proof.push(MerkleAccountProof {
+                account_proof,
+                storage_proofs,
+            });
+        }
+        witness.codes = codes.into_values().collect();
+        Ok(witness)
+    }
+
     pub fn sort_storage(
         &self,
         address: &Address,
@@ -150,13 +251,15 @@ impl ContextDB {
         accounts: &HashMap<Address, DbAccount>,
     ) -> Result<CommitState, ExecutionError> {
         let cdb = self;
-        let mut zktrie = ZkTrie::<PrefixDB>::new(old_root);
+        let mut zktrie = cdb.trie(old_root, None);
         let mut db_accounts = accounts
             .iter()
             .map(|(addr, acc)| (get_hkey(addr.as_ref()).unwrap(), addr, acc))
             .collect::<Vec<_>>();
         db_accounts.sort_by(|(a, _, _), (b, _, _)| a.cmp(b));
 
+        let mut updated = Vec::with_capacity(db_accounts.len());
+        let mut storages = Vec::new();
         for (acc_hkey, addr, db_acc) in db_accounts {
             let Some(info): Option<AccountInfo> = db_acc.info() else {
                 continue;
@@ -176,51 +279,150 @@ impl ContextDB {
                 );
                 acc.keccak_code_hash = info.code_hash;
                 acc.code_size = info.code.as_ref().map(|n| n.len()).unwrap_or_default().to();
+                // keep the deployed code for the blocks executed on top of
+                // a persisted state
+                if let Some(code) = info.code.as_ref().filter(|code| !code.is_empty()) {
+                    cdb.db
+                        .set_code(info.code_hash, Arc::new(code.original_bytes()));
+                }
             }
 
             if !db_acc.storage.is_empty() {
-                let storage_root_before = acc.root;
-                let mut db = cdb.db.new_prefix(*addr);
-                let mut storage_tire = ZkTrie::<PrefixDB>::new(storage_root_before);
-                for (hkey, key, value) in cdb.sort_storage(addr, &db_acc.storage) {
-                    let key_bytes = key.to_be_bytes::<32>();
-
-                    if !value.is_zero() {
-                        let value_bytes = value.to_be_bytes::<32>();
-                        let hval = trie_hash(&mimc_safe_encode(&value_bytes)).unwrap();
-                        storage_tire
-                            .put(&mut db, hkey, &key_bytes, hval, value_bytes.to_vec())
-                            .map_err(ExecutionError::CommitStorage(addr, &key, &value))?;
-                    } else {
-                        storage_tire
-                            .remove(&mut db, hkey, &key_bytes)
-                            .map_err(ExecutionError::CommitStorage(addr, &key, &value))?;
-                    }
-                }
-                acc.root = *storage_tire.top_root_hash();
+                storages.push(StorageCommit {
+                    index: updated.len(),
+                    addr: *addr,
+                    db: cdb.db.new_prefix(*addr),
+                    root: acc.root,
+                    slots: cdb.sort_storage(addr, &db_acc.storage),
+                    log: cdb.trace_log.as_ref().map(|_| TraceLog::default()),
+                });
+            }
+            updated.push((acc_hkey, addr, acc));
+        }
+
+        // the storage tries are independent, the roots are computed before
+        // the account trie is updated
+        let mut storage_traces: Vec<Vec<Trace>> = vec![Vec::new(); updated.len()];
+        for (storage, result) in commit_storages(storages, cdb.commit_threads) {
+            updated[storage.index].2.root = result?;
+            if let Some(storage_log) = storage.log {
+                storage_traces[storage.index] = std::mem::take(&mut *storage_log.lock().unwrap());
             }
+        }
 
+        let mut account_batch = WriteBatch::new();
+        for ((acc_hkey, addr, acc), traces) in updated.into_iter().zip(storage_traces) {
             // log::info!("addr: {:?} => {:?}", addr, acc);
 
-            let val = acc.encode_mimc_safe();
-            if val.len() == 0 {
-                zktrie
-                    .remove(&mut cdb.db, acc_hkey, addr.as_ref())
-                    .map_err(ExecutionError::CommitAccount(&addr, &acc))?;
+            // the leaf keeps the plain encoding `ZkStateAccount::decode`
+            // reads, the hash is over the mimc safe one
+            let safe = acc.encode_mimc_safe();
+            if safe.len() == 0 {
+                account_batch.remove(acc_hkey, addr.as_ref());
             } else {
-                let hval = trie_hash(&val).unwrap();
+                let hval = trie_hash(&safe).unwrap();
+                account_batch.put(acc_hkey, addr.as_ref(), hval, acc.encode());
+            }
+            // the traces of a storage trie come right before the one of its
+            // account, as in a commit one account at a time. The traced
+            // writes aren't deferred, so nothing is lost by applying them
+            // one by one
+            if let Some(log) = &cdb.trace_log {
+                log.lock().unwrap().extend(traces);
                 zktrie
-                    .put(&mut cdb.db, acc_hkey, addr.as_ref(), hval, val)
-                    .map_err(ExecutionError::CommitAccount(&addr, &acc))?;
+                    .apply_batch(&mut cdb.db, std::mem::take(&mut account_batch))
+                    .map_err(ExecutionError::CommitAccounts())?;
             }
         }
+        zktrie
+            .apply_batch(&mut cdb.db, account_batch)
+            .map_err(ExecutionError::CommitAccounts())?;
         Ok(CommitState {
             new_state_root: *zktrie.top_root_hash(),
         })
     }
 }
 
-impl DatabaseRef for ContextDB {
+/// The writes into the storage trie of one account.
+struct StorageCommit<D> {
+    // the position of the account in the updated accounts
+    index: usize,
+    addr: Address,
+    db: D,
+    root: B256,
+    slots: Vec<(B256, U256, U256)>,
+    log: Option<TraceLog>,
+}
+
+impl<D: PrefixDatabase> StorageCommit<D> {
+    fn commit(&mut self) -> Result<B256, ExecutionError> {
+        let mut trie = ZkTrie::new(self.root);
+        if let Some(log) = &self.log {
+            trie = trie.with_trace_log(self.addr.to_vec().into(), log.clone());
+        }
+        let mut batch = WriteBatch::new();
+        for (hkey, key, value) in &self.slots {
+            let key_bytes = key.to_be_bytes::<32>();
+
+            if !value.is_zero() {
+                let value_bytes = value.to_be_bytes::<32>();
+                let hval = trie_hash(&mimc_safe_encode(&value_bytes)).unwrap();
+                batch.put(*hkey, &key_bytes, hval, value_bytes.to_vec());
+            } else {
+                batch.remove(*hkey, &key_bytes);
+            }
+        }
+        trie.apply_batch(&mut self.db, batch)
+            .map_err(ExecutionError::CommitStorageBatch(&self.addr))?;
+        Ok(*trie.top_root_hash())
+    }
+}
+
+/// Commits the storage tries on up to `threads` threads. The results are
+/// returned in the order of `storages`, whatever thread computed them.
+fn commit_storages<D: PrefixDatabase>(
+    storages: Vec<StorageCommit<D>>,
+    threads: usize,
+) -> Vec<(StorageCommit<D>, Result<B256, ExecutionError>)> {
+    let threads = threads.min(storages.len());
+    if threads <= 1 {
+        return storages
+            .into_iter()
+            .map(|mut storage| {
+                let result = storage.commit();
+                (storage, result)
+            })
+            .collect();
+    }
+
+    let queue = storages
+        .into_iter()
+        .map(|storage| Mutex::new((storage, None)))
+        .collect::<Vec<_>>();
+    let next = AtomicUsize::new(0);
+    std::thread::scope(|s| {
+        for _ in 0..threads {
+            s.spawn(|| loop {
+                let idx = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = queue.get(idx) else {
+                    break;
+                };
+                let mut item = item.lock().unwrap();
+                let result = item.0.commit();
+                item.1 = Some(result);
+            });
+        }
+    });
+    queue
+        .into_iter()
+        .map(|item| {
+            let (storage, result) = item.into_inner().unwrap();
+            (storage, result.unwrap())
+        })
+        .collect()
+}
+
+impl<D: PrefixDatabase> DatabaseRef for ContextDB<D> {
     type Error = Infallible;
     fn basic_ref(&self, addr: Address) -> Result<Option<AccountInfo>, Self::Error> {
         let Some(acc) = self.get_acc(&addr) else {
@@ -256,7 +458,7 @@ impl DatabaseRef for ContextDB {
             return Ok(U256::default());
         };
         let db = self.db.new_prefix(address);
-        let zktrie = ZkTrie::<PrefixDB>::new(acc.root);
+        let zktrie = self.trie(acc.root, Some(&address));
         let index_bytes = index.to_be_bytes::<32>();
         let hkey = get_hkey(&index_bytes).unwrap();
         let result = zktrie.read(&db, hkey, &index_bytes).unwrap();
@@ -271,7 +473,9 @@ base::stack_error! {
     #[derive(Debug)]
     name: DBError,
     stack_name: DBErrorStack,
-    error: {},
+    error: {
+        NotLineaPob { chain_id: u64 },
+    },
     wrap: {
         ZkTrie(linea_zktrie::Error),
         Json(serde_json::Error),
@@ -287,3 +491,166 @@ base::stack_error! {
         DecodeProofs(data: Bytes),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use linea_zktrie::{diff_traces, EMPTY_TRIE_NODE_HASH};
+
+    use super::*;
+
+    fn put_slot(trie: &mut ZkTrie<PrefixDB>, db: &mut PrefixDB, slot: u64, value: u64) {
+        let key_bytes = U256::from(slot).to_be_bytes::<32>();
+        let value_bytes = U256::from(value).to_be_bytes::<32>();
+        let hkey = get_hkey(&key_bytes).unwrap();
+        let hval = trie_hash(&mimc_safe_encode(&value_bytes)).unwrap();
+        trie.put(db, hkey, &key_bytes, hval, value_bytes.to_vec())
+            .unwrap();
+    }
+
+    #[test]
+    fn test_commit_storages_in_parallel() {
+        let mut results = Vec::new();
+        for threads in [1, 4] {
+            let store = Arc::new(Mutex::new(MemStore::new_full()));
+            let db = PrefixDB::new(Address::default(), store);
+            let log = TraceLog::default();
+            let mut cdb = ContextDB::from_db(*EMPTY_TRIE_NODE_HASH, db, BTreeMap::new())
+                .with_commit_threads(threads)
+                .with_trace_log(log.clone());
+            let mut root = *EMPTY_TRIE_NODE_HASH;
+            for round in 1..=2u64 {
+                let mut accounts = HashMap::new();
+                for n in 1..=8u8 {
+                    let info = AccountInfo {
+                        nonce: round,
+                        balance: U256::from(n),
+                        ..Default::default()
+                    };
+                    let mut acc: DbAccount = info.into();
+                    for slot in 0..n as u64 {
+                        let value = U256::from((slot + round) % 3);
+                        acc.storage.insert(U256::from(slot), value);
+                    }
+                    accounts.insert(Address::with_last_byte(n), acc);
+                }
+                root = cdb.commit_changes(root, &accounts).unwrap().new_state_root;
+            }
+            let traces = std::mem::take(&mut *log.lock().unwrap());
+            results.push((root, traces));
+        }
+        assert_eq!(results[0].0, results[1].0);
+        assert_eq!(results[0].1.len(), results[1].1.len());
+        assert!(diff_traces(&results[0].1, &results[1].1).is_none());
+
+        // the storage traces of an account come right before its own
+        let traces = &results[0].1;
+        for (n, trace) in traces.iter().enumerate() {
+            if trace.location().is_empty() {
+                continue;
+            }
+            let next = &traces[n + 1];
+            assert!(
+                next.location() == trace.location()
+                    || (next.location().is_empty() && next.key() == trace.location())
+            );
+        }
+    }
+
+    #[test]
+    fn test_commit_keeps_the_code_fields() {
+        let store = Arc::new(Mutex::new(MemStore::new_full()));
+        let code = Bytecode::new_raw(Bytes::from_static(&[0x60, 0x00, 0x60, 0x00, 0xf3]));
+        let code_hash = alloy::primitives::keccak256(code.original_bytes());
+        let addr = Address::with_last_byte(1);
+
+        let mut root = *EMPTY_TRIE_NODE_HASH;
+        for round in 1..=2u64 {
+            // every block is executed on a new db at the parent root, the
+            // code is only loaded by the block deploying it
+            let db = PrefixDB::new(Address::default(), store.clone());
+            let mut cdb = ContextDB::from_db(root, db, BTreeMap::new());
+            let info = AccountInfo {
+                nonce: 1,
+                balance: U256::from(round),
+                code_hash,
+                code: (round == 1).then(|| code.clone()),
+            };
+            let accounts = HashMap::from([(addr, info.into())]);
+            root = cdb.commit_changes(root, &accounts).unwrap().new_state_root;
+        }
+
+        let db = PrefixDB::new(Address::default(), store);
+        let cdb = ContextDB::from_db(root, db, BTreeMap::new());
+        let acc = cdb.get_acc(&addr).unwrap();
+        assert_eq!(acc.balance, U256::from(2));
+        assert_eq!(acc.keccak_code_hash, code_hash);
+        assert_eq!(acc.code_size, U256::from(code.len()));
+        assert_eq!(acc.mimc_code_hash, mimc_safe_code_hash(code.bytes_slice()));
+    }
+
+    #[test]
+    fn test_build_mem_db_from_zktrie_proofs() {
+        base::init_log_test();
+        let store = Arc::new(Mutex::new(MemStore::new_full()));
+        let mut db = PrefixDB::new(Address::default(), store);
+        let mut trie = ZkTrie::<PrefixDB>::empty(&mut db).unwrap();
+
+        let owner = Address::with_last_byte(1);
+        let mut storage_db = db.new_prefix(owner);
+        let mut storage = ZkTrie::<PrefixDB>::new(*EMPTY_TRIE_NODE_HASH);
+        for slot in 1..=3 {
+            put_slot(&mut storage, &mut storage_db, slot, slot * 10);
+        }
+
+        let mut accounts = Vec::new();
+        for n in 1..=3u8 {
+            let addr = Address::with_last_byte(n);
+            let mut acc = ZkStateAccount {
+                nonce: n.into(),
+                balance: U256::from(n),
+                ..Default::default()
+            };
+            if addr == owner {
+                acc.root = *storage.top_root_hash();
+            }
+            let hval = trie_hash(&acc.encode_mimc_safe()).unwrap();
+            let hkey = get_hkey(addr.as_ref()).unwrap();
+            trie.put(&mut db, hkey, addr.as_ref(), hval, acc.encode())
+                .unwrap();
+            accounts.push((addr, acc));
+        }
+
+        let missing = Address::with_last_byte(9);
+        let mut state_proof = Vec::new();
+        for addr in [owner, Address::with_last_byte(2), missing] {
+            let hkey = get_hkey(addr.as_ref()).unwrap();
+            let account_proof = trie.prove(&db, hkey, addr.as_ref()).unwrap();
+            let mut storage_proofs = Vec::new();
+            if addr == owner {
+                for slot in [2u64, 9] {
+                    let key_bytes = U256::from(slot).to_be_bytes::<32>();
+                    let hkey = get_hkey(&key_bytes).unwrap();
+                    storage_proofs.push(storage.prove(&storage_db, hkey, &key_bytes).unwrap());
+                }
+            }
+            state_proof.push(MerkleAccountProof {
+                account_proof,
+                storage_proofs,
+            });
+        }
+        assert!(state_proof[0].account_proof.inclusion.is_some());
+        assert!(state_proof[2].account_proof.non_inclusion.is_some());
+
+        let mem_db = build_mem_db(&[], vec![], &state_proof).unwrap();
+        let cdb = ContextDB::new(
+            *trie.top_root_hash(),
+            Arc::new(Mutex::new(mem_db)),
+            BTreeMap::new(),
+        );
+        assert_eq!(cdb.get_acc(&accounts[0].0), Some(accounts[0].1.clone()));
+        assert_eq!(cdb.get_acc(&accounts[1].0), Some(accounts[1].1.clone()));
+        assert_eq!(cdb.get_acc(&missing), None);
+        assert_eq!(cdb.storage_ref(owner, U256::from(2)), Ok(U256::from(20)));
+        assert_eq!(cdb.storage_ref(owner, U256::from(9)), Ok(U256::ZERO));
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use alloy::primitives::{Address, U256};
+use linea_executor::ZkStateAccount;
+use linea_revm::DatabaseRef;
+use linea_zktrie::{PrefixDatabase, TrieLeaf, ZkTrie, EMPTY_TRIE_NODE_HASH};
+use prover_types::B256;
+use serde::{Deserialize, Serialize};
+
+use crate::ContextDB;
+
+/// The accounts and storage slots of a state, sorted so two dumps can be
+/// compared line by line.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StateDump {
+    pub root: B256,
+    pub accounts: BTreeMap<Address, AccountDump>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AccountDump {
+    /// `None` if the account doesn't exist.
+    pub account: Option<ZkStateAccount>,
+    pub storage: BTreeMap<U256, U256>,
+}
+
+impl<D: PrefixDatabase> ContextDB<D> {
+    /// Reads the accounts and slots of `keys` at the root of the
+    /// `ContextDB`, they have to be covered by the store.
+    pub fn dump(&self, keys: &BTreeMap<Address, BTreeSet<U256>>) -> StateDump {
+        let mut accounts = BTreeMap::new();
+        for (addr, slots) in keys {
+            let account = self.get_acc(addr);
+            let mut storage = BTreeMap::new();
+            if account.is_some() {
+                for slot in slots {
+                    let value = self.storage_ref(*addr, *slot).unwrap();
+                    storage.insert(*slot, value);
+                }
+            }
+            accounts.insert(*addr, AccountDump { account, storage });
+        }
+        StateDump {
+            root: self.root(),
+            accounts,
+        }
+    }
+
+    /// The leaves of the world state with the accounts decoded, `None` if
+    /// the store doesn't have the value. The sentinels are skipped, so are
+    /// the subtrees missing from a witness.
+    pub fn account_leaves(
+        &self,
+    ) -> Result<Vec<(TrieLeaf, Option<ZkStateAccount>)>, linea_zktrie::Error> {
+        let trie = ZkTrie::<D>::new(self.root());
+        let mut out = Vec::new();
+        for leaf in trie.leaves(&self.db)? {
+            let leaf = leaf?;
+            if leaf.is_sentinel() {
+                continue;
+            }
+            let account = leaf
+                .value
+                .as_ref()
+                .and_then(|value| ZkStateAccount::decode(value));
+            out.push((leaf, account));
+        }
+        Ok(out)
+    }
+
+    /// The leaves of the storage trie of `addr` with the values decoded.
+    pub fn storage_leaves(
+        &self,
+        addr: &Address,
+    ) -> Result<Vec<(TrieLeaf, Option<U256>)>, linea_zktrie::Error> {
+        let root = match self.get_acc(addr) {
+            Some(acc) => acc.root,
+            None => *EMPTY_TRIE_NODE_HASH,
+        };
+        let db = self.db.new_prefix(*addr);
+        let trie = ZkTrie::<D>::new(root);
+        let mut out = Vec::new();
+        for leaf in trie.leaves(&db)? {
+            let leaf = leaf?;
+            if leaf.is_sentinel() {
+                continue;
+            }
+            let value = leaf.value.as_ref().map(|value| U256::from_be_slice(value));
+            out.push((leaf, value));
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use linea_executor::AccountInfo;
+    use linea_revm::db::DbAccount;
+    use linea_zktrie::{MemStore, PrefixDB};
+
+    use super::*;
+
+    #[test]
+    fn test_dump_committed_state() {
+        let store = Arc::new(Mutex::new(MemStore::new_full()));
+        let db = PrefixDB::new(Address::default(), store);
+        let state = db.new_prefix(Address::default());
+        let mut cdb = ContextDB::from_db(*EMPTY_TRIE_NODE_HASH, state, BTreeMap::new());
+
+        let mut accounts = HashMap::new();
+        let mut keys = BTreeMap::new();
+        for n in 1..=3u8 {
+            let info = AccountInfo {
+                nonce: n.into(),
+                balance: U256::from(n),
+                ..Default::default()
+            };
+            let mut acc: DbAccount = info.into();
+            for slot in 0..n as u64 {
+                acc.storage.insert(U256::from(slot), U256::from(slot + 10));
+            }
+            let addr = Address::with_last_byte(n);
+            keys.insert(addr, acc.storage.keys().copied().collect::<BTreeSet<_>>());
+            accounts.insert(addr, acc);
+        }
+        let missing = Address::with_last_byte(9);
+        keys.insert(missing, BTreeSet::from([U256::from(1)]));
+        let root = cdb
+            .commit_changes(*EMPTY_TRIE_NODE_HASH, &accounts)
+            .unwrap()
+            .new_state_root;
+
+        let cdb = ContextDB::from_db(root, db, BTreeMap::new());
+        let dump = cdb.dump(&keys);
+        assert_eq!(dump.root, root);
+        assert_eq!(dump.accounts[&missing], AccountDump::default());
+        let owner = Address::with_last_byte(3);
+        let acc = dump.accounts[&owner].account.clone().unwrap();
+        assert_eq!(acc.nonce, 3);
+        assert_eq!(acc.balance, U256::from(3));
+        assert_eq!(dump.accounts[&owner].storage.len(), 3);
+        assert_eq!(
+            dump.accounts[&owner].storage[&U256::from(2)],
+            U256::from(12)
+        );
+
+        let json = serde_json::to_vec(&dump).unwrap();
+        assert_eq!(serde_json::from_slice::<StateDump>(&json).unwrap(), dump);
+
+        let leaves = cdb.account_leaves().unwrap();
+        assert_eq!(leaves.len(), 3);
+        assert!(leaves
+            .iter()
+            .any(|(_, account)| account.as_ref() == Some(&acc)));
+        let slots = cdb.storage_leaves(&owner).unwrap();
+        assert_eq!(slots.len(), 3);
+        assert!(slots
+            .iter()
+            .any(|(_, value)| *value == Some(U256::from(11))));
+        assert!(cdb.storage_leaves(&missing).unwrap().is_empty());
+    }
+}
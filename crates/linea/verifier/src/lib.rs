@@ -8,4 +8,12 @@ mod pob_context;
 pub use pob_context::*;
 
 mod db;
-pub use db::*;
\ No newline at end of file
+pub use db::*;
+
+mod dump;
+pub use dump::*;
+
+#[cfg(feature = "local-state")]
+mod witness;
+#[cfg(feature = "local-state")]
+pub use witness::*;
@@ -1,22 +1,27 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::sync::{Arc, Mutex};
 
 use alloy::{
     consensus::{Transaction, TxEnvelope},
     primitives::{keccak256, Address, Bytes, U256},
-    rlp::{encode_list, Encodable},
 };
 use base::eth::Keypair;
 use linea_executor::{Context, ExecutionError, SpecId, TxEnv};
-use linea_revm::db::CacheDB;
-use linea_zktrie::MemStore;
+use linea_revm::db::{CacheDB, DbAccount};
+use linea_shomei::MerkleAccountProof;
+use linea_zktrie::{diff_traces, MemStore, Trace, TraceLog};
 use prover_types::{Pob, PobBlock, B256};
 
-use crate::{build_mem_db, ContextDB, DBError};
+use crate::{build_mem_db, ContextDB, DBError, StateDump};
 
 pub struct PobContext {
     pub pob: Pob<Bytes>,
     db: Arc<Mutex<MemStore>>,
     txs: Vec<TxEnvelope>,
+    zkroot: B256,
+    traces: Vec<Trace>,
+    proofs: Vec<MerkleAccountProof>,
+    commit_threads: usize,
 }
 
 impl PobContext {
@@ -26,57 +31,107 @@ impl PobContext {
             let tx: TxEnvelope = alloy::rlp::decode_exact(tx).unwrap();
             txs.push(tx)
         }
-        let mut linea_traces = vec![];
-        for item in &pob.data.linea_traces {
+        let linea = pob.data.linea().ok_or(DBError::NotLineaPob {
+            chain_id: pob.data.chain_id,
+        })?;
+        let mut linea_traces: Vec<Trace> = vec![];
+        for item in &linea.traces {
             linea_traces.push(serde_json::from_slice(&item).map_err(DBError::DecodeTrace(&item))?);
         }
-        let mut linea_proofs = vec![];
-        for item in &pob.data.linea_proofs {
+        let mut linea_proofs: Vec<MerkleAccountProof> = vec![];
+        for item in &linea.proofs {
             linea_proofs.push(serde_json::from_slice(&item).map_err(DBError::DecodeProofs(&item))?);
         }
+        let zkroot = linea.zkroot;
         let db = Arc::new(Mutex::new(build_mem_db(
             &linea_traces,
             pob.data.codes.clone(),
             &linea_proofs,
         )?));
-        Ok(Self { pob, txs, db })
+        Ok(Self {
+            pob,
+            txs,
+            db,
+            zkroot,
+            traces: linea_traces,
+            proofs: linea_proofs,
+            commit_threads: 1,
+        })
+    }
+
+    /// The number of threads committing the storage tries of the block.
+    pub fn with_commit_threads(mut self, threads: usize) -> Self {
+        self.commit_threads = threads;
+        self
+    }
+
+    /// The accounts and storage slots the traces and proofs of the block
+    /// cover, the accounts read or written with their slots.
+    pub fn touched(&self) -> BTreeMap<Address, BTreeSet<U256>> {
+        let mut touched = BTreeMap::<_, BTreeSet<_>>::new();
+        let mut touch = |location: &[u8], key: &[u8]| {
+            if location.is_empty() {
+                touched.entry(Address::from_slice(key)).or_default();
+            } else {
+                let slots = touched.entry(Address::from_slice(location)).or_default();
+                slots.insert(U256::from_be_slice(key));
+            }
+        };
+        for trace in &self.traces {
+            touch(trace.location(), trace.key());
+        }
+        for proof in &self.proofs {
+            let addr = &proof.account_proof.key;
+            touch(&[], addr);
+            for slot in &proof.storage_proofs {
+                touch(addr, &slot.key);
+            }
+        }
+        touched
+    }
+
+    /// The touched accounts and slots before the block is executed.
+    pub fn dump_touched(&self) -> StateDump {
+        self.db().dump(&self.touched())
     }
 
     fn blk(&self) -> &PobBlock {
         &self.pob.block
     }
 
+    /// Commits the changes again with the traces recorded, and logs the
+    /// first write that differs from the Shomei traces of the block.
+    fn log_trace_diff(&self, accounts: &HashMap<Address, DbAccount>) {
+        let trace_log = TraceLog::default();
+        let mut db = self.db().with_trace_log(trace_log.clone());
+        if let Err(err) = db.commit_changes(self.old_state_root(), accounts) {
+            log::warn!("[block #{}] trace the commit: {:?}", self.number(), err);
+            return;
+        }
+        let local = trace_log.lock().unwrap();
+        match diff_traces(&local, &self.traces) {
+            Some(diff) => log::warn!(
+                "[block #{}] state diverged at write #{}: local={:?}, remote={:?}",
+                self.number(),
+                diff.index,
+                diff.local,
+                diff.remote
+            ),
+            None => log::warn!(
+                "[block #{}] state root mismatch, but the writes match the traces",
+                self.number()
+            ),
+        }
+    }
+
     pub fn seal_header(&self) -> B256 {
         let header = self.blk();
 
         // Remove the last 65 bytes of extra_data
         let extra_data = Bytes::copy_from_slice(&header.extra_data[..header.extra_data.len() - 65]);
-        // May need to handle the case where base_fee_per_gas is nil
 
         // https://github.com/ethereum/go-ethereum/blob/81fd1b3cf9c4c4c9f0e06f8bdcbaa8b29c81b052/consensus/clique/clique.go#L763
-        let mut buf = alloy::rlp::bytes::BytesMut::new();
-        let enc: [&dyn Encodable; 16] = [
-            &header.parent_hash,
-            &header.uncles_hash,
-            &header.miner,
-            &header.state_root,
-            &header.transactions_root,
-            &header.receipts_root,
-            &header.logs_bloom,
-            &header.difficulty,
-            &header.number,
-            &header.gas_limit,
-            &header.gas_used,
-            &header.timestamp,
-            &extra_data,
-            &header.mix_hash,
-            &header.nonce,
-            &header.base_fee_per_gas.as_ref().unwrap(),
-        ];
-
-        encode_list::<_, dyn Encodable>(&enc, &mut buf);
-
-        keccak256(&buf)
+        keccak256(header.header_rlp(&extra_data))
     }
 }
 
@@ -87,6 +142,7 @@ impl Context for PobContext {
 
     fn db(&self) -> Self::DB {
         ContextDB::new(self.old_state_root(), self.db.clone(), self.pob.data.block_hashes.clone())
+            .with_commit_threads(self.commit_threads)
     }
 
     fn spec_id(&self) -> SpecId {
@@ -101,6 +157,10 @@ impl Context for PobContext {
         self.blk().block_hash.unwrap_or_default()
     }
 
+    fn state_hash(&self) -> B256 {
+        self.pob.state_commitment()
+    }
+
     fn chain_id(&self) -> u64 {
         self.pob.data.chain_id
     }
@@ -135,7 +195,7 @@ impl Context for PobContext {
     }
 
     fn state_root(&self) -> B256 {
-        self.pob.data.linea_zkroot
+        self.zkroot
     }
 
     fn timestamp(&self) -> U256 {
@@ -178,6 +238,10 @@ impl Context for PobContext {
         mut db: CacheDB<Self::DB>,
     ) -> Result<Self::CommitState, ExecutionError> {
         let old_root = self.old_state_root();
-        db.db.commit_changes(old_root, &db.accounts)
+        let state = db.db.commit_changes(old_root, &db.accounts)?;
+        if state.new_state_root != self.zkroot {
+            self.log_trace_diff(&db.accounts);
+        }
+        Ok(state)
     }
 }
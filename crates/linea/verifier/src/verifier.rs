@@ -5,7 +5,9 @@ use base::{thread::parallel, trace::Alive};
 use linea_executor::{CommitState, Context, ExecutionError, LineaEvmExecutor};
 use linea_revm::primitives::ExecutionResult;
 use linea_shomei::ShomeiConfig;
-use prover_types::{Pob, Poe, ProveTaskParams, B256};
+use prover_types::{
+    verify_block_headers, HeaderError, ParamsError, Pob, Poe, ProveTaskParams, TaskType, B256,
+};
 
 use crate::{block_trace_to_pob, BlockTrace, BlockTraceError, DBError, PobContext};
 
@@ -14,6 +16,9 @@ pub struct LineaBatchVerifier {
     alive: Alive,
     el: Option<Eth>,
     shomei: Option<linea_shomei::Client>,
+    commit_threads: usize,
+    #[cfg(feature = "local-state")]
+    local: Option<crate::LocalWitnessGenerator>,
 }
 
 impl LineaBatchVerifier {
@@ -35,15 +40,35 @@ impl LineaBatchVerifier {
             None => None,
         };
         let alive = Alive::new();
-        Ok(Self { alive, el, shomei })
+        Ok(Self {
+            alive,
+            el,
+            shomei,
+            commit_threads: 1,
+            #[cfg(feature = "local-state")]
+            local: None,
+        })
+    }
+
+    /// Commits the storage tries of each block on up to `threads` threads.
+    pub fn with_commit_threads(mut self, threads: usize) -> Self {
+        self.commit_threads = threads;
+        self
+    }
+
+    /// Generates the contexts from a locally maintained state instead of
+    /// Shomei, only the execution node is required.
+    #[cfg(feature = "local-state")]
+    pub fn with_local_state(mut self, local: crate::LocalWitnessGenerator) -> Self {
+        self.local = Some(local);
+        self
     }
 
     pub fn cache_key(
         &self,
         params: &ProveTaskParams,
     ) -> Result<(u64, u64, u64, B256), ValidateError> {
-        let start = params.start.unwrap();
-        let end = params.end.unwrap();
+        let (start, end) = params.block_range()?;
         let batch_id = end;
         Ok((batch_id, start, end, params.pob_hash))
     }
@@ -58,6 +83,15 @@ impl LineaBatchVerifier {
             .el
             .clone()
             .ok_or(ValidateError::ExecutionNodeIsRequired)?;
+        #[cfg(feature = "local-state")]
+        if let Some(local) = &self.local {
+            let mut result = Vec::with_capacity(blocks.len());
+            for block_trace in local.build_range(&el, start_block, end_block).await? {
+                let pob = block_trace_to_pob(block_trace).ok_or(ValidateError::ConvertToPobFailed)?;
+                result.push(pob);
+            }
+            return Ok(result);
+        }
         let shomei = self.shomei.clone().ok_or(ValidateError::ShomeiIsRequired)?;
 
         let ctx = (el, shomei);
@@ -77,11 +111,22 @@ impl LineaBatchVerifier {
     pub async fn prove(
         &self,
         pob_list: &[Pob],
-        _params: ProveTaskParams,
+        params: ProveTaskParams,
     ) -> Result<Poe, ValidateError> {
+        let range = params.block_range()?;
+        if let Some(pob) = pob_list.iter().find(|pob| pob.data.task_type() != TaskType::Linea) {
+            return Err(ValidateError::UnexpectedChain {
+                number: pob.block.number.to(),
+            });
+        }
+        params.check_pob_list(pob_list, range, |pob| {
+            pob.data.linea().map(|linea| linea.zkroot).unwrap_or_default()
+        })?;
+        verify_block_headers(pob_list)?;
+
         let mut ctx_list = Vec::with_capacity(pob_list.len());
         for pob in pob_list {
-            ctx_list.push(PobContext::new(pob.clone())?);
+            ctx_list.push(PobContext::new(pob.clone())?.with_commit_threads(self.commit_threads));
         }
         Self::verify(ctx_list).await
     }
@@ -104,6 +149,7 @@ impl LineaBatchVerifier {
                 });
             }
             let mut poe = Poe::default();
+            poe.state_hash = ctx.state_hash();
             poe.prev_state_root = ctx.old_state_root();
             poe.new_state_root = result.new_state_root;
             Ok::<_, ValidateError>(poe)
@@ -124,8 +170,11 @@ base::stack_error! {
         ExecutionNodeIsRequired,
         ShomeiIsRequired,
         ConvertToPobFailed,
+        UnexpectedChain { number: u64 },
     },
     wrap: {
+        Params(ParamsError),
+        Header(HeaderError),
         BlockTrace(BlockTraceError),
         Execution(ExecutionError),
         DB(DBError),
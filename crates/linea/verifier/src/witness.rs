@@ -0,0 +1,284 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use alloy::primitives::{Address, B256};
+use linea_executor::{Context, LineaEvmExecutor};
+use linea_zktrie::{DiskPrefixDB, DiskStore, StateHead, Trace};
+
+use crate::{BlockSource, BlockTrace, BlockTraceContext, BlockTraceError, WitnessRecorder};
+
+/// The `zk_state_manager_version` of the traces built locally.
+pub const LOCAL_STATE_MANAGER_VERSION: &str = "local";
+
+/// Number of the latest blocks whose traces are kept for `build_range`.
+pub const DEFAULT_KEPT_TRACES: usize = 256;
+
+/// Maintains the Linea world state in a `DiskStore` and builds the
+/// `BlockTrace` of the blocks executed on top of it, in place of Shomei.
+///
+/// The store has to be seeded with the state and the codes of the block
+/// before the first one built, the blocks are then applied in order. The
+/// traces of the latest blocks are kept in memory, so a range can be built
+/// again, e.g. when a batch is retried.
+#[derive(Clone)]
+pub struct LocalWitnessGenerator {
+    store: Arc<Mutex<DiskStore>>,
+    // the pending writes of the store belong to one block at a time
+    exec: Arc<Mutex<()>>,
+    traces: Arc<Mutex<BTreeMap<u64, BlockTrace>>>,
+    kept_traces: usize,
+}
+
+impl LocalWitnessGenerator {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, BlockTraceError> {
+        let store = DiskStore::open(path)?;
+        Ok(Self::new(Arc::new(Mutex::new(store))))
+    }
+
+    pub fn new(store: Arc<Mutex<DiskStore>>) -> Self {
+        Self {
+            store,
+            exec: Arc::new(Mutex::new(())),
+            traces: Arc::new(Mutex::new(BTreeMap::new())),
+            kept_traces: DEFAULT_KEPT_TRACES,
+        }
+    }
+
+    pub fn with_kept_traces(mut self, kept_traces: usize) -> Self {
+        self.kept_traces = kept_traces;
+        self
+    }
+
+    pub fn store(&self) -> &Arc<Mutex<DiskStore>> {
+        &self.store
+    }
+
+    pub fn head(&self) -> Result<Option<StateHead>, BlockTraceError> {
+        Ok(self.store.lock().unwrap().head()?)
+    }
+
+    /// Applies the blocks after the head up to `end_block`, and returns the
+    /// traces of the ones from `start_block`. The blocks already applied
+    /// are served from the kept traces, it fails once they are evicted or
+    /// were built before a restart.
+    pub async fn build_range<S: BlockSource>(
+        &self,
+        source: &S,
+        start_block: u64,
+        end_block: u64,
+    ) -> Result<Vec<BlockTrace>, BlockTraceError> {
+        let head = self
+            .head()?
+            .ok_or(BlockTraceError::LocalStateNotInitialized)?;
+        let mut out = Vec::new();
+        for block_number in start_block.min(head.number + 1)..=end_block {
+            let kept = self.traces.lock().unwrap().get(&block_number).cloned();
+            let trace = match kept {
+                Some(trace) => trace,
+                None if block_number > head.number => self.build(source, block_number).await?,
+                None => {
+                    return Err(BlockTraceError::LocalStateHeadMismatch {
+                        head: head.number,
+                        block: block_number,
+                    })
+                }
+            };
+            if block_number >= start_block {
+                out.push(trace);
+            }
+        }
+        Ok(out)
+    }
+
+    /// Executes the block on top of the head and commits it, the state is
+    /// left untouched if the execution fails.
+    pub async fn build<S: BlockSource>(
+        &self,
+        source: &S,
+        block_number: u64,
+    ) -> Result<BlockTrace, BlockTraceError> {
+        let chain_id = source.chain_id().await?;
+        let block = source.block(block_number).await?;
+        let receipts = source.receipts(block_number).await?;
+
+        let _exec = self.exec.lock().unwrap();
+        let head = self
+            .head()?
+            .ok_or(BlockTraceError::LocalStateNotInitialized)?;
+        if head.number + 1 != block_number {
+            return Err(BlockTraceError::LocalStateHeadMismatch {
+                head: head.number,
+                block: block_number,
+            });
+        }
+
+        let mut trace = BlockTrace {
+            chain_id,
+            block,
+            zk_parent_state_root_hash: head.root,
+            zk_end_state_root_hash: B256::default(),
+            zk_state_manager_version: LOCAL_STATE_MANAGER_VERSION.into(),
+            traces: Vec::new(),
+            state_proof: Vec::new(),
+            codes: Vec::new(),
+            receipts,
+            block_hashes: BTreeMap::new(),
+        };
+        let recorder = Arc::new(WitnessRecorder::default());
+        let db = DiskPrefixDB::new(Address::default(), self.store.clone());
+        let ctx = BlockTraceContext::with_db(trace.clone(), db)?.with_recorder(recorder.clone());
+        let result = LineaEvmExecutor::new(ctx.db(), ctx.spec_id())
+            .handle_block(&ctx)
+            .map_err(BlockTraceError::ExecuteBlock(&block_number));
+
+        let mut store = self.store.lock().unwrap();
+        let result = match result {
+            Ok(result) => result,
+            Err(err) => {
+                store.discard();
+                return Err(err);
+            }
+        };
+        store.set_head(block_number, result.new_state_root);
+        let committed = store
+            .commit()
+            .map_err(BlockTraceError::CommitLocalState(&block_number));
+        if committed.is_err() {
+            store.discard();
+        }
+        committed?;
+
+        let witness = recorder.witness.lock().unwrap().take().unwrap_or_default();
+        let traces = std::mem::take(&mut *recorder.traces.lock().unwrap());
+        trace.zk_end_state_root_hash = result.new_state_root;
+        trace.traces = dedup_reads(traces);
+        trace.state_proof = witness.state_proof;
+        trace.codes = witness.codes;
+
+        let mut traces = self.traces.lock().unwrap();
+        traces.insert(block_number, trace.clone());
+        while traces.len() > self.kept_traces {
+            traces.pop_first();
+        }
+        Ok(trace)
+    }
+}
+
+/// A read is recorded every time the executor loads the key, only the first
+/// read of a key at a root is kept.
+fn dedup_reads(traces: Vec<Trace>) -> Vec<Trace> {
+    let mut seen = BTreeSet::new();
+    traces
+        .into_iter()
+        .filter(|trace| {
+            trace.is_update()
+                || seen.insert((
+                    trace.old_top_hash(),
+                    trace.location().clone(),
+                    trace.key().clone(),
+                ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use alloy::primitives::{address, U256};
+    use alloy::rpc::types::{eth::Block, TransactionReceipt};
+    use async_trait::async_trait;
+    use linea_executor::AccountInfo;
+    use linea_zktrie::EMPTY_TRIE_NODE_HASH;
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::{block_trace_to_pob, ContextDB, LineaBatchVerifier, PobContext};
+
+    /// Serves the responses of the execution node recorded in `testdata`.
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Fixture {
+        chain_id: u64,
+        block: Block,
+        receipts: Vec<TransactionReceipt>,
+    }
+
+    #[async_trait]
+    impl BlockSource for Fixture {
+        async fn chain_id(&self) -> Result<u64, BlockTraceError> {
+            Ok(self.chain_id)
+        }
+
+        async fn block(&self, number: u64) -> Result<Block, BlockTraceError> {
+            match self.block.header.number == Some(number) {
+                true => Ok(self.block.clone()),
+                false => Err(BlockTraceError::BlockNotFound(number)),
+            }
+        }
+
+        async fn receipts(&self, number: u64) -> Result<Vec<TransactionReceipt>, BlockTraceError> {
+            match self.block.header.number == Some(number) {
+                true => Ok(self.receipts.clone()),
+                false => Err(BlockTraceError::BlockReceiptsNotFound(number)),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_build_from_local_state() {
+        let data = std::fs::read("testdata/local-state-8.json").unwrap();
+        let fixture: Fixture = serde_json::from_slice(&data).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let local = LocalWitnessGenerator::open(dir.path().join("state")).unwrap();
+
+        // the sender of the only transaction of the block
+        let sender = address!("19e7e376e7c213b7e7e7e46cc70a5dd086daff2a");
+        let info = AccountInfo {
+            balance: U256::from(10).pow(U256::from(18)),
+            ..Default::default()
+        };
+        let accounts = HashMap::from([(sender, info.into())]);
+        let db = DiskPrefixDB::new(Address::default(), local.store().clone());
+        let mut db = ContextDB::from_db(*EMPTY_TRIE_NODE_HASH, db, BTreeMap::new());
+        let state = db.commit_changes(*EMPTY_TRIE_NODE_HASH, &accounts).unwrap();
+        {
+            let mut store = local.store().lock().unwrap();
+            store.set_head(7, state.new_state_root);
+            store.commit().unwrap();
+        }
+
+        assert!(local.build_range(&fixture, 7, 8).await.is_err());
+        let traces = local.build_range(&fixture, 8, 8).await.unwrap();
+        assert_eq!(traces.len(), 1);
+        let trace = traces[0].clone();
+        assert_eq!(trace.zk_parent_state_root_hash, state.new_state_root);
+        assert_ne!(trace.zk_end_state_root_hash, state.new_state_root);
+        assert_eq!(local.head().unwrap().unwrap().number, 8);
+        assert_eq!(
+            local.head().unwrap().unwrap().root,
+            trace.zk_end_state_root_hash
+        );
+
+        // a retry is served from the kept traces
+        let retried = local.build_range(&fixture, 8, 8).await.unwrap();
+        assert_eq!(retried.len(), 1);
+        assert_eq!(
+            retried[0].zk_end_state_root_hash,
+            trace.zk_end_state_root_hash
+        );
+        assert_eq!(retried[0].traces.len(), trace.traces.len());
+        // the blocks that are not kept can't be rebuilt
+        let evicted = LocalWitnessGenerator::new(local.store.clone()).with_kept_traces(0);
+        assert!(matches!(
+            evicted.build_range(&fixture, 8, 8).await,
+            Err(BlockTraceError::LocalStateHeadMismatch { head: 8, block: 8 })
+        ));
+
+        // the witness alone is enough to execute the block again
+        let pob = block_trace_to_pob(trace).unwrap();
+        let ctx = PobContext::new(pob).unwrap();
+        LineaBatchVerifier::verify(vec![ctx]).await.unwrap();
+    }
+}
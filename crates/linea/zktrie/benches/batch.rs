@@ -0,0 +1,249 @@
+//! Compares `ZkTrie::put`/`remove` one by one against `ZkTrie::apply_batch`,
+//! on a store with the full index and on a witness store built from the
+//! traces of the block, like the one the verifier replays the block on.
+//!
+//! The writes are taken from the Shomei traces of real blocks when
+//! `LINEA_BLOCK_TRACES` lists `BlockTrace` json files (separated by `,`), like
+//! the ones saved by `linea-test` in `testdata/downloaded`. Otherwise a block
+//! of the same shape is generated.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use alloy::primitives::{keccak256, Address, Bytes, B256};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use linea_zktrie::{
+    mimc_safe, mimc_safe_encode, parse_prefix, trie_hash, MemStore, MemZkTrie, PrefixDB, Trace,
+    TraceLog, WriteBatch, EMPTY_TRIE_NODE_HASH,
+};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct BlockTrace {
+    traces: Vec<Trace>,
+}
+
+#[derive(Clone)]
+enum Write {
+    Put(Bytes, Bytes),
+    Remove(Bytes),
+}
+
+/// The writes of a block grouped by trie, with the values they overwrite.
+#[derive(Clone, Default)]
+struct BlockWrites {
+    tries: BTreeMap<Address, (Vec<(Bytes, Bytes)>, Vec<Write>)>,
+}
+
+impl BlockWrites {
+    fn from_traces(traces: &[Trace]) -> Self {
+        let mut out = Self::default();
+        for trace in traces {
+            let entry = out.tries.entry(parse_prefix(trace.location())).or_default();
+            match trace {
+                Trace::Insertion(t) => entry.1.push(Write::Put(t.key.clone(), t.value.clone())),
+                Trace::Update(t) => {
+                    entry.0.push((t.key.clone(), t.old_value.clone()));
+                    entry.1.push(Write::Put(t.key.clone(), t.new_value.clone()));
+                }
+                Trace::Deletion(t) => {
+                    entry.0.push((t.key.clone(), t.deleted_value.clone()));
+                    entry.1.push(Write::Remove(t.key.clone()));
+                }
+                Trace::Read(_) | Trace::ReadZero(_) => {}
+            }
+        }
+        out
+    }
+
+    /// 200 accounts, 50 of them with 20 slots written.
+    fn generate() -> Self {
+        let mut out = Self::default();
+        let digest = |n: u32| {
+            let mut digest = keccak256(n.to_be_bytes());
+            // the account values are hashed as field elements
+            digest[0] &= 0x0f;
+            Bytes::copy_from_slice(digest.as_slice())
+        };
+        let accounts = out.tries.entry(Address::default()).or_default();
+        for n in 0..200 {
+            let key = Bytes::copy_from_slice(&digest(n)[12..]);
+            accounts.0.push((key.clone(), digest(n + 1000)));
+            accounts.1.push(Write::Put(key, digest(n + 2000)));
+        }
+        for n in 0..50 {
+            let storage = out
+                .tries
+                .entry(Address::from_slice(&digest(n)[12..]))
+                .or_default();
+            for slot in 0..20 {
+                let key = digest(n * 100 + slot);
+                if slot % 2 == 0 {
+                    storage.0.push((key.clone(), digest(slot + 1000)));
+                }
+                storage.1.push(Write::Put(key, digest(slot + 2000)));
+            }
+        }
+        out
+    }
+
+    fn len(&self) -> usize {
+        self.tries.values().map(|(_, writes)| writes.len()).sum()
+    }
+}
+
+fn hash_key(prefix: &Address, key: &[u8]) -> B256 {
+    match prefix.is_zero() {
+        true => trie_hash(key).unwrap(),
+        false => mimc_safe(key).unwrap(),
+    }
+}
+
+fn hash_value(prefix: &Address, value: &[u8]) -> B256 {
+    match prefix.is_zero() {
+        true => trie_hash(value).unwrap(),
+        false => trie_hash(&mimc_safe_encode(value)).unwrap(),
+    }
+}
+
+/// The writes with their keys and values hashed.
+type HashedWrites = Vec<(B256, Bytes, Option<(B256, Vec<u8>)>)>;
+
+fn hash_writes(prefix: &Address, writes: &[Write]) -> HashedWrites {
+    writes
+        .iter()
+        .map(|write| match write {
+            Write::Put(key, value) => (
+                hash_key(prefix, key),
+                key.clone(),
+                Some((hash_value(prefix, value), value.to_vec())),
+            ),
+            Write::Remove(key) => (hash_key(prefix, key), key.clone(), None),
+        })
+        .collect()
+}
+
+fn to_batch(writes: HashedWrites) -> WriteBatch {
+    let mut batch = WriteBatch::new();
+    for (hkey, key, value) in writes {
+        match value {
+            Some((hval, value)) => batch.put(hkey, &key, hval, value),
+            None => batch.remove(hkey, &key),
+        }
+    }
+    batch
+}
+
+/// The tries holding the values overwritten by the block.
+fn seed(block: &BlockWrites) -> Vec<(PrefixDB, MemZkTrie, HashedWrites)> {
+    let store = Arc::new(Mutex::new(MemStore::new_full()));
+    let mut out = Vec::new();
+    for (prefix, (before, writes)) in &block.tries {
+        let mut db = PrefixDB::new(*prefix, store.clone());
+        let mut trie = MemZkTrie::new(*EMPTY_TRIE_NODE_HASH);
+        let before = before
+            .iter()
+            .map(|(key, value)| Write::Put(key.clone(), value.clone()))
+            .collect::<Vec<_>>();
+        let before = to_batch(hash_writes(prefix, &before));
+        trie.apply_batch(&mut db, before).unwrap();
+        out.push((db, trie, hash_writes(prefix, writes)));
+    }
+    out
+}
+
+/// The tries of `seed` on a store holding only the traces of the writes.
+fn witness(block: &BlockWrites) -> Vec<(PrefixDB, MemZkTrie, HashedWrites)> {
+    let log = TraceLog::default();
+    let mut roots = Vec::new();
+    for (prefix, (mut db, trie, writes)) in block.tries.keys().copied().zip(seed(block)) {
+        let location = match prefix.is_zero() {
+            true => Bytes::new(),
+            false => Bytes::copy_from_slice(prefix.as_slice()),
+        };
+        roots.push((prefix, *trie.top_root_hash(), writes.clone()));
+        let mut trie = trie.with_trace_log(location, log.clone());
+        for (hkey, key, value) in writes {
+            match value {
+                Some((hval, value)) => trie.put(&mut db, hkey, &key, hval, value).unwrap(),
+                None => trie.remove(&mut db, hkey, &key).unwrap(),
+            }
+        }
+    }
+    let traces = std::mem::take(&mut *log.lock().unwrap());
+    let store = Arc::new(Mutex::new(MemStore::from_traces(&traces).unwrap()));
+    roots
+        .into_iter()
+        .map(|(prefix, root, writes)| {
+            let db = PrefixDB::new(prefix, store.clone());
+            (db, MemZkTrie::new(root), writes)
+        })
+        .collect()
+}
+
+fn load_blocks() -> Vec<(String, BlockWrites)> {
+    let Ok(files) = std::env::var("LINEA_BLOCK_TRACES") else {
+        return vec![("generated".into(), BlockWrites::generate())];
+    };
+    files
+        .split(',')
+        .map(|file| {
+            let data = std::fs::read(file).unwrap();
+            let block: BlockTrace = serde_json::from_slice(&data).unwrap();
+            (file.into(), BlockWrites::from_traces(&block.traces))
+        })
+        .collect()
+}
+
+fn bench_batch(c: &mut Criterion) {
+    for (name, block) in load_blocks() {
+        let mut group = c.benchmark_group(format!("{} ({} writes)", name, block.len()));
+        group.sample_size(10);
+        group.bench_function("put", |b| {
+            b.iter_batched(
+                || seed(&block),
+                |tries| {
+                    for (mut db, mut trie, writes) in tries {
+                        for (hkey, key, value) in writes {
+                            match value {
+                                Some((hval, value)) => {
+                                    trie.put(&mut db, hkey, &key, hval, value).unwrap()
+                                }
+                                None => trie.remove(&mut db, hkey, &key).unwrap(),
+                            }
+                        }
+                    }
+                },
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_function("apply_batch", |b| {
+            b.iter_batched(
+                || seed(&block),
+                |tries| {
+                    for (mut db, mut trie, writes) in tries {
+                        trie.apply_batch(&mut db, to_batch(writes)).unwrap();
+                    }
+                },
+                BatchSize::LargeInput,
+            )
+        });
+        // the witness store can't resolve the key ranges at the staged
+        // roots, the batch goes through one write at a time
+        group.bench_function("witness apply_batch", |b| {
+            b.iter_batched(
+                || witness(&block),
+                |tries| {
+                    for (mut db, mut trie, writes) in tries {
+                        trie.apply_batch(&mut db, to_batch(writes)).unwrap();
+                    }
+                },
+                BatchSize::LargeInput,
+            )
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_batch);
+criterion_main!(benches);
@@ -0,0 +1,619 @@
+use alloy::primitives::{Address, Bytes, B256};
+use redb::{AccessGuard, StorageError, TableDefinition};
+use std::collections::BTreeMap;
+use std::ops::Bound;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    BranchNode, Database, Error, FlattenedLeaf, KeyRange, LeafNode, Node, NodeValue,
+    PrefixDatabase, ZkTrie, EMPTY_DB, EMPTY_KECCAK_CODE_HASH, EMPTY_TRIE_NODE_HASH,
+};
+
+const NODES: TableDefinition<&[u8], &[u8]> = TableDefinition::new("nodes");
+const INDEX: TableDefinition<&[u8], &[u8]> = TableDefinition::new("index");
+const CODES: TableDefinition<&[u8], &[u8]> = TableDefinition::new("codes");
+const META: TableDefinition<&str, &[u8]> = TableDefinition::new("meta");
+const ROOTS: TableDefinition<&[u8], &[u8]> = TableDefinition::new("roots");
+
+const META_HEAD: &str = "head";
+
+const NODE_BRANCH: u8 = 0;
+const NODE_LEAF: u8 = 1;
+const NODE_EMPTY_LEAF: u8 = 2;
+const NODE_NEXT_FREE: u8 = 3;
+
+type RawEntry<'a> = (
+    AccessGuard<'a, &'static [u8]>,
+    AccessGuard<'a, &'static [u8]>,
+);
+
+pub type DiskZkTrie = ZkTrie<DiskPrefixDB>;
+
+#[derive(Debug)]
+pub struct DiskPrefixDB {
+    prefix: Address,
+    raw: Arc<Mutex<DiskStore>>,
+}
+
+impl DiskPrefixDB {
+    pub fn new(prefix: Address, raw: Arc<Mutex<DiskStore>>) -> Self {
+        Self { prefix, raw }
+    }
+
+    pub fn new_prefix(&self, prefix: Address) -> Self {
+        DiskPrefixDB {
+            prefix,
+            raw: self.raw.clone(),
+        }
+    }
+
+    pub fn raw(&self) -> &Arc<Mutex<DiskStore>> {
+        &self.raw
+    }
+}
+
+impl Database for DiskPrefixDB {
+    type Node = Node;
+    fn get_code(&self, hash: &B256) -> Option<Arc<Bytes>> {
+        let raw = self.raw.lock().unwrap();
+        raw.get_code(hash)
+    }
+
+    fn set_code(&mut self, hash: B256, code: Arc<Bytes>) {
+        let mut raw = self.raw.lock().unwrap();
+        raw.set_code(hash, code)
+    }
+
+    fn get_nearest_keys(&self, root: &B256, k: &B256) -> Result<KeyRange, Error> {
+        let raw = self.raw.lock().unwrap();
+        raw.get_nearest_keys(self.prefix, root, k)
+    }
+
+    fn get_node(&self, key: &B256) -> Result<Option<Arc<Self::Node>>, Error> {
+        let raw = self.raw.lock().unwrap();
+        raw.get_node(key)
+    }
+
+    fn remove_index(&mut self, k: &B256) {
+        let mut raw = self.raw.lock().unwrap();
+        raw.remove_index(self.prefix, k)
+    }
+
+    fn update_index(&mut self, k: B256, v: FlattenedLeaf) {
+        let mut raw = self.raw.lock().unwrap();
+        raw.update_index(self.prefix, k, v)
+    }
+
+    fn update_node(&mut self, key: B256, node: Self::Node) -> Result<Arc<Self::Node>, Error> {
+        let mut raw = self.raw.lock().unwrap();
+        raw.update_node(key, node)
+    }
+
+    fn full_index(&self) -> bool {
+        true
+    }
+
+    fn update_root(&mut self, root: B256) {
+        let mut raw = self.raw.lock().unwrap();
+        raw.update_root(self.prefix, root)
+    }
+}
+
+impl PrefixDatabase for DiskPrefixDB {
+    fn new_prefix(&self, prefix: Address) -> Self {
+        DiskPrefixDB::new_prefix(self, prefix)
+    }
+}
+
+/// The block the persisted state belongs to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StateHead {
+    pub number: u64,
+    pub root: B256,
+}
+
+/// A `Database` persisted in a redb file, for keeping a full world state
+/// across blocks.
+///
+/// The writes are buffered until `commit`, so a block that fails halfway
+/// can be dropped with `discard`. The nodes of every committed root are
+/// kept, the next free node of a trie is the left child of its root node.
+/// Unlike the witness index of `MemStore`, the key index only follows the
+/// latest state: the key ranges can only be resolved at the latest root of
+/// each trie, including the pending one.
+#[derive(Debug)]
+pub struct DiskStore {
+    db: redb::Database,
+    // shared by the lookups until the next commit
+    read: Mutex<Option<redb::ReadTransaction>>,
+    nodes: BTreeMap<B256, Arc<Node>>,
+    // `None` marks a key removed since the last commit
+    index: BTreeMap<(Address, B256), Option<FlattenedLeaf>>,
+    codes: BTreeMap<B256, Arc<Bytes>>,
+    roots: BTreeMap<Address, B256>,
+    head: Option<StateHead>,
+}
+
+fn disk_err<E: std::fmt::Display>(err: E) -> Error {
+    Error::DiskStore(err.to_string())
+}
+
+impl DiskStore {
+    /// Opens the store at `path`, creating it if it doesn't exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let db = redb::Database::create(path).map_err(disk_err)?;
+        // the tables have to exist before they can be opened for reading
+        let txn = db.begin_write().map_err(disk_err)?;
+        txn.open_table(NODES).map_err(disk_err)?;
+        txn.open_table(INDEX).map_err(disk_err)?;
+        txn.open_table(META).map_err(disk_err)?;
+        txn.open_table(ROOTS).map_err(disk_err)?;
+        // the accounts without code point to the empty one
+        txn.open_table(CODES)
+            .map_err(disk_err)?
+            .insert(EMPTY_KECCAK_CODE_HASH.as_slice(), b"".as_slice())
+            .map_err(disk_err)?;
+        txn.commit().map_err(disk_err)?;
+        Ok(Self {
+            db,
+            read: Mutex::new(None),
+            nodes: BTreeMap::new(),
+            index: BTreeMap::new(),
+            codes: BTreeMap::new(),
+            roots: BTreeMap::new(),
+            head: None,
+        })
+    }
+
+    /// Runs `f` in the read transaction shared by the lookups, the persisted
+    /// state only changes in `commit`.
+    fn with_read<T, F>(&self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&redb::ReadTransaction) -> Result<T, Error>,
+    {
+        let mut read = self.read.lock().unwrap();
+        if read.is_none() {
+            *read = Some(self.db.begin_read().map_err(disk_err)?);
+        }
+        f(read.as_ref().unwrap())
+    }
+
+    pub fn head(&self) -> Result<Option<StateHead>, Error> {
+        if let Some(head) = self.head {
+            return Ok(Some(head));
+        }
+        self.with_read(|txn| {
+            let table = txn.open_table(META).map_err(disk_err)?;
+            let Some(raw) = table.get(META_HEAD).map_err(disk_err)? else {
+                return Ok(None);
+            };
+            let raw = raw.value();
+            if raw.len() != 40 {
+                return Err(Error::ZkTrieParseNodeFail(
+                    raw.to_vec().into(),
+                    "state head",
+                ));
+            }
+            Ok(Some(StateHead {
+                number: u64::from_be_bytes(raw[..8].try_into().unwrap()),
+                root: B256::from_slice(&raw[8..]),
+            }))
+        })
+    }
+
+    /// Committed along with the pending writes.
+    pub fn set_head(&mut self, number: u64, root: B256) {
+        self.head = Some(StateHead { number, root });
+    }
+
+    pub fn add_codes(&mut self, codes: Vec<Bytes>) {
+        for code in codes {
+            let hash = linea_mimc::keccak_hash(&code);
+            self.codes.insert(hash.into(), Arc::new(code));
+        }
+    }
+
+    /// Writes the pending nodes, index entries, codes and head in one
+    /// transaction.
+    pub fn commit(&mut self) -> Result<(), Error> {
+        let txn = self.db.begin_write().map_err(disk_err)?;
+        {
+            let mut nodes = txn.open_table(NODES).map_err(disk_err)?;
+            for (hash, node) in &self.nodes {
+                nodes
+                    .insert(hash.as_slice(), encode_node(node).as_slice())
+                    .map_err(disk_err)?;
+            }
+            let mut index = txn.open_table(INDEX).map_err(disk_err)?;
+            for ((prefix, k), leaf) in &self.index {
+                let key = index_key(prefix, k);
+                match leaf {
+                    Some(leaf) => index
+                        .insert(key.as_slice(), encode_flattened_leaf(leaf).as_slice())
+                        .map_err(disk_err)?,
+                    None => index.remove(key.as_slice()).map_err(disk_err)?,
+                };
+            }
+            let mut codes = txn.open_table(CODES).map_err(disk_err)?;
+            for (hash, code) in &self.codes {
+                codes
+                    .insert(hash.as_slice(), code.as_ref().as_ref())
+                    .map_err(disk_err)?;
+            }
+            let mut roots = txn.open_table(ROOTS).map_err(disk_err)?;
+            for (prefix, root) in &self.roots {
+                roots
+                    .insert(prefix.as_slice(), root.as_slice())
+                    .map_err(disk_err)?;
+            }
+            if let Some(head) = &self.head {
+                let mut raw = head.number.to_be_bytes().to_vec();
+                raw.extend_from_slice(head.root.as_slice());
+                let mut meta = txn.open_table(META).map_err(disk_err)?;
+                meta.insert(META_HEAD, raw.as_slice()).map_err(disk_err)?;
+            }
+        }
+        txn.commit().map_err(disk_err)?;
+        self.read.get_mut().unwrap().take();
+        self.discard();
+        Ok(())
+    }
+
+    /// Drops the writes since the last commit.
+    pub fn discard(&mut self) {
+        self.nodes.clear();
+        self.index.clear();
+        self.codes.clear();
+        self.roots.clear();
+        self.head = None;
+    }
+
+    fn get_code(&self, hash: &B256) -> Option<Arc<Bytes>> {
+        if let Some(code) = self.codes.get(hash) {
+            return Some(code.clone());
+        }
+        let load = self.with_read(|txn| {
+            let table = txn.open_table(CODES).map_err(disk_err)?;
+            let code = table.get(hash.as_slice()).map_err(disk_err)?;
+            Ok(code.map(|code| Arc::new(code.value().to_vec().into())))
+        });
+        match load {
+            Ok(code) => code,
+            Err(err) => {
+                log::error!("load code {:?} fail: {:?}", hash, err);
+                None
+            }
+        }
+    }
+
+    fn set_code(&mut self, hash: B256, code: Arc<Bytes>) {
+        self.codes.insert(hash, code);
+    }
+
+    fn get_node(&self, key: &B256) -> Result<Option<Arc<Node>>, Error> {
+        if let Some(node) = self.nodes.get(key) {
+            return Ok(Some(node.clone()));
+        }
+        if let Some(node) = EMPTY_DB.get_node(key).map_err(Error::DBRedirectStatic())? {
+            return Ok(Some(node));
+        }
+        self.with_read(|txn| {
+            let table = txn.open_table(NODES).map_err(disk_err)?;
+            match table.get(key.as_slice()).map_err(disk_err)? {
+                Some(raw) => Ok(Some(Arc::new(decode_node(*key, raw.value())?))),
+                None => Ok(None),
+            }
+        })
+    }
+
+    fn update_node(&mut self, key: B256, node: Node) -> Result<Arc<Node>, Error> {
+        let node = Arc::new(node);
+        self.nodes.insert(key, node.clone());
+        Ok(node)
+    }
+
+    fn update_index(&mut self, prefix: Address, k: B256, v: FlattenedLeaf) {
+        self.index.insert((prefix, k), Some(v));
+    }
+
+    fn remove_index(&mut self, prefix: Address, k: &B256) {
+        self.index.insert((prefix, *k), None);
+    }
+
+    fn update_root(&mut self, prefix: Address, root: B256) {
+        self.roots.insert(prefix, root);
+    }
+
+    /// The index can't answer for the roots older than the latest one of
+    /// the trie.
+    fn check_root(
+        &self,
+        txn: &redb::ReadTransaction,
+        prefix: Address,
+        root: &B256,
+        k: &B256,
+    ) -> Result<(), Error> {
+        let latest = match self.roots.get(&prefix) {
+            Some(root) => Some(*root),
+            None => txn
+                .open_table(ROOTS)
+                .map_err(disk_err)?
+                .get(prefix.as_slice())
+                .map_err(disk_err)?
+                .map(|raw| B256::from_slice(raw.value())),
+        };
+        if latest != Some(*root) {
+            return Err(Error::IndexNotFoundAtRoot {
+                root: *root,
+                prefix,
+                key: *k,
+            });
+        }
+        Ok(())
+    }
+
+    /// The head and the tail are implied, like in `MemStore::new_full`.
+    fn get_nearest_keys(&self, prefix: Address, root: &B256, k: &B256) -> Result<KeyRange, Error> {
+        if root == &*EMPTY_TRIE_NODE_HASH {
+            return Ok(KeyRange {
+                left_index: FlattenedLeaf::head().leaf_index,
+                center: None,
+                right_index: FlattenedLeaf::tail().leaf_index,
+            });
+        }
+        self.with_read(|txn| self.nearest_keys(txn, prefix, root, k))
+    }
+
+    fn nearest_keys(
+        &self,
+        txn: &redb::ReadTransaction,
+        prefix: Address,
+        root: &B256,
+        k: &B256,
+    ) -> Result<KeyRange, Error> {
+        self.check_root(txn, prefix, root, k)?;
+        let table = txn.open_table(INDEX).map_err(disk_err)?;
+
+        let key = (prefix, *k);
+        let center = match self.index.get(&key) {
+            Some(leaf) => leaf.clone(),
+            None => match table
+                .get(index_key(&prefix, k).as_slice())
+                .map_err(disk_err)?
+            {
+                Some(raw) => Some(decode_flattened_leaf(raw.value())?),
+                None => None,
+            },
+        };
+
+        let first = (prefix, B256::ZERO);
+        let last = (prefix, B256::repeat_byte(0xff));
+        let (raw_first, raw_key, raw_last) = (
+            index_key(&prefix, &first.1),
+            index_key(&prefix, k),
+            index_key(&prefix, &last.1),
+        );
+
+        let pending_left = self
+            .index
+            .range(first..key)
+            .rev()
+            .find_map(|((_, hkey), leaf)| Some((*hkey, leaf.as_ref()?.leaf_index)));
+        let disk_left = self.first_live(
+            prefix,
+            table
+                .range(raw_first.as_slice()..raw_key.as_slice())
+                .map_err(disk_err)?
+                .rev(),
+        )?;
+        let left_index = [pending_left, disk_left]
+            .into_iter()
+            .flatten()
+            .max_by_key(|(hkey, _)| *hkey)
+            .map_or(FlattenedLeaf::head().leaf_index, |(_, idx)| idx);
+
+        let pending_right = self
+            .index
+            .range((Bound::Excluded(key), Bound::Included(last)))
+            .find_map(|((_, hkey), leaf)| Some((*hkey, leaf.as_ref()?.leaf_index)));
+        let disk_right = self.first_live(
+            prefix,
+            table
+                .range::<&[u8]>((
+                    Bound::Excluded(raw_key.as_slice()),
+                    Bound::Included(raw_last.as_slice()),
+                ))
+                .map_err(disk_err)?,
+        )?;
+        let right_index = [pending_right, disk_right]
+            .into_iter()
+            .flatten()
+            .min_by_key(|(hkey, _)| *hkey)
+            .map_or(FlattenedLeaf::tail().leaf_index, |(_, idx)| idx);
+
+        Ok(KeyRange {
+            left_index,
+            center,
+            right_index,
+        })
+    }
+
+    /// The first persisted entry that isn't overridden by a pending write,
+    /// the pending entries are looked up separately.
+    fn first_live<'a, I>(&self, prefix: Address, iter: I) -> Result<Option<(B256, u64)>, Error>
+    where
+        I: Iterator<Item = Result<RawEntry<'a>, StorageError>>,
+    {
+        for entry in iter {
+            let (k, v) = entry.map_err(disk_err)?;
+            let hkey = B256::from_slice(&k.value()[20..]);
+            if self.index.contains_key(&(prefix, hkey)) {
+                continue;
+            }
+            return Ok(Some((hkey, decode_flattened_leaf(v.value())?.leaf_index)));
+        }
+        Ok(None)
+    }
+}
+
+fn index_key(prefix: &Address, k: &B256) -> [u8; 52] {
+    let mut key = [0_u8; 52];
+    key[..20].copy_from_slice(prefix.as_slice());
+    key[20..].copy_from_slice(k.as_slice());
+    key
+}
+
+fn encode_flattened_leaf(leaf: &FlattenedLeaf) -> Vec<u8> {
+    let mut out = Vec::with_capacity(8 + leaf.leaf_value.len());
+    out.extend_from_slice(&leaf.leaf_index.to_be_bytes());
+    out.extend_from_slice(&leaf.leaf_value);
+    out
+}
+
+fn decode_flattened_leaf(buf: &[u8]) -> Result<FlattenedLeaf, Error> {
+    if buf.len() < 8 {
+        return Err(Error::ZkTrieParseNodeFail(
+            buf.to_vec().into(),
+            "flattened leaf",
+        ));
+    }
+    Ok(FlattenedLeaf::new(
+        u64::from_be_bytes(buf[..8].try_into().unwrap()),
+        buf[8..].to_vec().into(),
+    ))
+}
+
+fn encode_node(node: &Node) -> Vec<u8> {
+    let leaf = |tag: u8, leaf: &LeafNode| {
+        let mut out = Vec::with_capacity(2 + leaf.path.len() + leaf.value.len());
+        out.push(tag);
+        out.push(leaf.path.len() as u8);
+        out.extend_from_slice(&leaf.path);
+        out.extend_from_slice(&leaf.value);
+        out
+    };
+    match node.raw() {
+        NodeValue::Branch(branch) => {
+            let mut out = Vec::with_capacity(65);
+            out.push(NODE_BRANCH);
+            out.extend_from_slice(branch.left.as_slice());
+            out.extend_from_slice(branch.right.as_slice());
+            out
+        }
+        NodeValue::Leaf(node) => leaf(NODE_LEAF, node),
+        NodeValue::NextFree(node) => leaf(NODE_NEXT_FREE, node),
+        NodeValue::EmptyLeaf => vec![NODE_EMPTY_LEAF],
+    }
+}
+
+/// The node is stored under its hash, so it's not recomputed.
+fn decode_node(hash: B256, buf: &[u8]) -> Result<Node, Error> {
+    let value = match buf.split_first() {
+        Some((&NODE_BRANCH, data)) if data.len() == 64 => NodeValue::Branch(BranchNode {
+            left: B256::from_slice(&data[..32]),
+            right: B256::from_slice(&data[32..]),
+        }),
+        Some((&NODE_EMPTY_LEAF, [])) => NodeValue::EmptyLeaf,
+        Some((&tag @ (NODE_LEAF | NODE_NEXT_FREE), [len, data @ ..]))
+            if data.len() >= *len as usize =>
+        {
+            let (path, value) = data.split_at(*len as usize);
+            let leaf = LeafNode {
+                path: path.to_vec().into(),
+                value: value.to_vec().into(),
+            };
+            match tag {
+                NODE_LEAF => NodeValue::Leaf(leaf),
+                _ => NodeValue::NextFree(leaf),
+            }
+        }
+        _ => return Err(Error::ZkTrieParseNodeFail(buf.to_vec().into(), "disk node")),
+    };
+    Ok(Node::with_hash(value, hash))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::utils::{dum_hkey, dum_key, dum_put, dum_value};
+    use crate::{MemStore, MemZkTrie, PrefixDB};
+
+    use super::*;
+
+    #[test]
+    fn test_reopen_disk_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.redb");
+
+        let mut mem_db = PrefixDB::new(
+            Address::default(),
+            Arc::new(Mutex::new(MemStore::new_full())),
+        );
+        let mut mem_trie = MemZkTrie::empty(&mut mem_db).unwrap();
+
+        let store = Arc::new(Mutex::new(DiskStore::open(&path).unwrap()));
+        let mut db = DiskPrefixDB::new(Address::default(), store.clone());
+        let mut trie = DiskZkTrie::empty(&mut db).unwrap();
+        for n in [5, 1, 9, 3, 7] {
+            dum_put(&mut trie, &mut db, n, n * 10);
+            dum_put(&mut mem_trie, &mut mem_db, n, n * 10);
+        }
+        assert_eq!(trie.top_root_hash(), mem_trie.top_root_hash());
+        let root = *trie.top_root_hash();
+        store.lock().unwrap().set_head(1, root);
+        store.lock().unwrap().commit().unwrap();
+
+        // uncommitted writes are dropped
+        dum_put(&mut trie, &mut db, 11, 110);
+        store.lock().unwrap().discard();
+        drop(db);
+        drop(store);
+
+        let store = Arc::new(Mutex::new(DiskStore::open(&path).unwrap()));
+        let head = store.lock().unwrap().head().unwrap().unwrap();
+        assert_eq!(head, StateHead { number: 1, root });
+
+        let mut db = DiskPrefixDB::new(Address::default(), store.clone());
+        let mut trie = DiskZkTrie::new(head.root);
+        assert_eq!(trie.next_free_node(&db).unwrap(), 7);
+        assert_eq!(
+            trie.read(&db, dum_hkey(3), &dum_key(3)).unwrap(),
+            Some(dum_value(30))
+        );
+        assert_eq!(trie.read(&db, dum_hkey(11), &dum_key(11)).unwrap(), None);
+
+        // the key ranges are resolved from the persisted index
+        trie.remove(&mut db, dum_hkey(5), &dum_key(5)).unwrap();
+        mem_trie
+            .remove(&mut mem_db, dum_hkey(5), &dum_key(5))
+            .unwrap();
+        dum_put(&mut trie, &mut db, 9, 91);
+        dum_put(&mut mem_trie, &mut mem_db, 9, 91);
+        dum_put(&mut trie, &mut db, 4, 40);
+        dum_put(&mut mem_trie, &mut mem_db, 4, 40);
+        assert_eq!(trie.top_root_hash(), mem_trie.top_root_hash());
+        assert_eq!(trie.read(&db, dum_hkey(5), &dum_key(5)).unwrap(), None);
+        assert_eq!(
+            trie.read(&db, dum_hkey(4), &dum_key(4)).unwrap(),
+            Some(dum_value(40))
+        );
+        // the index only follows the latest root of the trie
+        assert!(matches!(
+            db.get_nearest_keys(&head.root, &dum_hkey(3)),
+            Err(Error::IndexNotFoundAtRoot { .. })
+        ));
+
+        // a storage trie under another prefix doesn't see the account keys
+        let mut storage_db = db.new_prefix(Address::repeat_byte(1));
+        let mut storage = DiskZkTrie::empty(&mut storage_db).unwrap();
+        dum_put(&mut storage, &mut storage_db, 5, 50);
+        assert_eq!(
+            storage.read(&storage_db, dum_hkey(5), &dum_key(5)).unwrap(),
+            Some(dum_value(50))
+        );
+        assert_eq!(
+            storage.read(&storage_db, dum_hkey(4), &dum_key(4)).unwrap(),
+            None
+        );
+    }
+}
@@ -37,8 +37,13 @@ fn cell(a: usize, b: usize) -> usize {
     val
 }
 
+/// The code is hashed in chunks of 16 bytes, the empty code hashes as a
+/// single zero chunk.
 pub fn mimc_safe_code_hash(code: &[u8]) -> B256 {
     const CHUNK_SIZE: usize = 16;
+    if code.is_empty() {
+        return *EMPTY_MIMC_CODE_HASH;
+    }
     let num_chunks = cell(code.len(), CHUNK_SIZE);
     let mut buf = vec![0_u8; num_chunks * 32];
     let mut offset = 0;
@@ -49,3 +54,99 @@ pub fn mimc_safe_code_hash(code: &[u8]) -> B256 {
     }
     trie_hash(&buf).unwrap()
 }
+
+#[cfg(test)]
+mod test {
+    use alloy::primitives::{address, b256, hex, Address};
+
+    use super::*;
+
+    /// Computed with a port of gnark-crypto's bls12-377 MiMC and the
+    /// encodings of Shomei.
+    #[test]
+    fn test_trie_hash() {
+        assert_eq!(
+            *EMPTY_MIMC_CODE_HASH,
+            b256!("0134373b65f439c874734ff51ea349327c140cde2e47a933146e6f9f2ad8eb17")
+        );
+        assert_eq!(
+            *EMPTY_KECCAK_CODE_HASH,
+            b256!("c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470")
+        );
+        // short inputs are left padded
+        assert_eq!(trie_hash(&[0]).unwrap(), *EMPTY_MIMC_CODE_HASH);
+        assert!(trie_hash(&[]).is_err());
+        assert!(trie_hash(&[0; 33]).is_err());
+        // the keccak hashes may not fit in a field element
+        assert!(trie_hash(EMPTY_KECCAK_CODE_HASH.as_slice()).is_err());
+        assert_eq!(
+            trie_hash(&mimc_safe_encode(EMPTY_KECCAK_CODE_HASH.as_slice())).unwrap(),
+            b256!("11d5b62aad59b28f0c4a243b3d0e44c048edaea69f591f6eb6449cafc04ba8a9")
+        );
+    }
+
+    #[test]
+    fn test_account_key_hash() {
+        let cases = [
+            (
+                Address::ZERO,
+                b256!("0134373b65f439c874734ff51ea349327c140cde2e47a933146e6f9f2ad8eb17"),
+            ),
+            (
+                address!("19e7e376e7c213b7e7e7e46cc70a5dd086daff2a"),
+                b256!("03fcbfc8042c08afc089f1109810e7efa1b508260039eab5e4780dd6ba586cff"),
+            ),
+            (
+                address!("ffffffffffffffffffffffffffffffffffffffff"),
+                b256!("10715aaa59367dba57e820b85c06405e3bfe33e21b4b2225ecb943461fa7bcc9"),
+            ),
+        ];
+        for (addr, hkey) in cases {
+            assert_eq!(trie_hash(addr.as_slice()).unwrap(), hkey, "{}", addr);
+        }
+    }
+
+    #[test]
+    fn test_slot_key_hash() {
+        let cases = [
+            (
+                B256::ZERO,
+                b256!("022efa6a7d3f639a3831916b27daaa5cbcaf1165e47f04ffbe1e707e30a32469"),
+            ),
+            (
+                B256::with_last_byte(1),
+                b256!("06dfb2dfdd68b5704ca772658e48873c6b5e394a6b32f861c22bb7c5431decc3"),
+            ),
+            (
+                B256::repeat_byte(0xff),
+                b256!("0568525760510d15207090093444e12a50ad90d2f601ef1cb615989f1c9bc6e9"),
+            ),
+        ];
+        for (slot, hkey) in cases {
+            assert_eq!(mimc_safe(slot.as_slice()).unwrap(), hkey, "{}", slot);
+            // the values of the slots are encoded the same way
+            assert_eq!(trie_hash(&mimc_safe_encode(slot.as_slice())).unwrap(), hkey);
+        }
+    }
+
+    #[test]
+    fn test_mimc_safe_code_hash() {
+        let cases = [
+            ("", "0134373b65f439c874734ff51ea349327c140cde2e47a933146e6f9f2ad8eb17"),
+            ("00", "0134373b65f439c874734ff51ea349327c140cde2e47a933146e6f9f2ad8eb17"),
+            ("60", "05906bd72f5130e55e82523e060c811101c0d27af58f6df24d1fa17c5230dd54"),
+            ("6080604052", "0738a346608f4f8f53c58ca44dfad5ed257b2ebaa7dbf6ab9ea757e5c8e98026"),
+            // one full chunk
+            ("000102030405060708090a0b0c0d0e0f", "01aeb5b4e3c349e28b8369ac19c74ecc8164c1f66767963c5f71fbe5a13b3588"),
+            // the last chunk is left padded
+            ("000102030405060708090a0b0c0d0e0f10", "09a2b1704e2cb3b18e006165b143daa85e7497a9f695f5ab7ff8e95128faa121"),
+            ("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f202122232425262728292a2b2c2d2e2f", "03c5291d029ba883b496ef0b55ee93e2a539bfb2b9c1f78ba38c302ff4955c92"),
+            ("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f202122232425262728292a2b2c2d2e2f303132333435363738393a3b3c3d3e3f404142434445464748494a4b4c4d4e4f505152535455565758595a5b5c5d5e5f60616263", "063b886d1ae4f4eaba61419397a2840349fcdac21bf37338fd6269b77a736138"),
+        ];
+        for (code, hash) in cases {
+            let code = hex::decode(code).unwrap();
+            let hash = hash.parse::<B256>().unwrap();
+            assert_eq!(mimc_safe_code_hash(&code), hash, "{}", hex::encode(&code));
+        }
+    }
+}
@@ -21,6 +21,17 @@ pub use sparse_merkle_trie::*;
 mod utils;
 pub use utils::*;
 
+mod proof;
+pub use proof::*;
+
+mod snapshot;
+pub use snapshot::*;
+
+#[cfg(feature = "disk")]
+mod disk;
+#[cfg(feature = "disk")]
+pub use disk::*;
+
 base::stack_error!(
     #[derive(Clone, Debug)]
     name: Error,
@@ -38,6 +49,7 @@ base::stack_error!(
         ZkTrieParseNodeFail(Bytes, &'static str),
         HashFail(String),
         InvalidBranchNode(Bytes),
+        NonCanonicalWord(B256),
         InvalidProof{
             want_sub_root: B256,
             got_sub_root: B256,
@@ -45,6 +57,21 @@ base::stack_error!(
         },
         RootNodeNotFound(B256),
         RootNodeExpectToBeBranchNode(Arc<Node>),
+        ProofExpectBranchNode(usize, B256),
+        InvalidProofNodes { want: usize, got: usize },
+        InvalidLeafOpening(Bytes),
+        ProofLeafIndexOutOfRange { leaf_index: u64, next_free_node: u64 },
+        ProofRootMismatch { want: B256, got: B256 },
+        ProofLeafKeyMismatch { leaf_index: u64, want: B256, got: B256 },
+        ProofValueMissing { leaf_index: u64 },
+        ProofValueMismatch { leaf_index: u64, want: B256, got: B256 },
+        ProofKeyOutOfRange { left: B256, key: B256, right: B256 },
+        ProofLeafLinkage { left_index: u64, left_next: u64, right_index: u64, right_prev: u64 },
+        DiskStore(String),
+        SnapshotIncomplete { root: B256, pruned: usize },
+        SnapshotLeafValueMissing { leaf_index: u64 },
+        SnapshotValueMismatch { leaf_index: u64, want: B256, got: B256 },
+        SnapshotRootMismatch { want: B256, got: B256 },
     },
     wrap: {},
     stack: {
@@ -70,5 +97,10 @@ base::stack_error!(
         ZktrieRemove(),
         ZktrieRead(),
         ZktriePut(),
+        ZktrieProve(),
+        ZktrieProveLeft(),
+        ZktrieProveRight(),
+        VerifyLeftProof(),
+        VerifyRightProof(),
     }
 );
@@ -121,6 +121,11 @@ impl Node {
         }
     }
 
+    /// For nodes loaded from a store keyed by their hash.
+    pub(crate) fn with_hash(raw: NodeValue, hash: B256) -> Node {
+        Node { raw, hash }
+    }
+
     pub fn empty_leaf() -> Arc<Node> {
         lazy_static::lazy_static! {
             static ref EMPTY_LEAF: Arc<Node> = Arc::new(Node::new(NodeValue::EmptyLeaf));
@@ -196,6 +201,8 @@ impl NodeValue {
         }
     }
 
+    /// The words of the node have to be canonical field elements, the nodes
+    /// received from the clients are checked with `check_field_words`.
     pub fn hash(&self) -> B256 {
         lazy_static::lazy_static! {
             static ref EMPTY_TRIE_NODE_HASH: B256 = keccak_hash(&[]).into();
@@ -234,6 +241,7 @@ impl NodeValue {
         if buf.len() != 64 {
             return Err(Error::InvalidBranchNode(buf.to_vec().into()));
         }
+        check_field_words(buf)?;
         Ok(NodeValue::Branch(BranchNode {
             left: B256::from_slice(&buf[..32]),
             right: B256::from_slice(&buf[32..]),
@@ -244,6 +252,7 @@ impl NodeValue {
         if buf.len() != 64 {
             return Err(Error::InvalidBranchNode(buf.to_vec().into()));
         }
+        check_field_words(buf)?;
         Ok(NodeValue::Branch(BranchNode {
             left: B256::from_slice(&buf[..32]).into(),
             right: B256::from_slice(&buf[32..]),
@@ -263,6 +272,17 @@ impl NodeValue {
     }
 }
 
+/// Checks that every 32 bytes word of `buf` is below the field order, the
+/// node hash would fail on it.
+pub fn check_field_words(buf: &[u8]) -> Result<(), Error> {
+    for word in buf.chunks(32) {
+        if linea_mimc::Fr::from_be(word).is_err() {
+            return Err(Error::NonCanonicalWord(B256::left_padding_from(word)));
+        }
+    }
+    Ok(())
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct BranchNode {
     pub left: B256,
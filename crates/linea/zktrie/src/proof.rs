@@ -0,0 +1,441 @@
+use std::sync::Arc;
+
+use alloy::primitives::{Bytes, B256};
+use serde::{Deserialize, Serialize};
+
+use crate::{check_field_words, utils, Error, LeafOpening, Node, NodeValue, ZK_TRIE_DEPTH};
+
+const EMPTY_LEAF_LEN: usize = 32;
+const LEAF_OPENING_LEN: usize = 128;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct MerkleProof {
+    pub key: Bytes,
+    #[serde(flatten)]
+    pub inclusion: Option<MerkleInclusionProof>,
+    #[serde(flatten)]
+    pub non_inclusion: Option<MerkleNonInclusionProof>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct MerkleInclusionProof {
+    pub leaf_index: u64,
+    pub proof: Proof,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct MerkleNonInclusionProof {
+    pub left_proof: Proof,
+    pub right_proof: Proof,
+    pub left_leaf_index: u64,
+    pub right_leaf_index: u64,
+}
+
+impl MerkleProof {
+    pub fn proof(&self) -> Result<&MerkleInclusionProof, &MerkleNonInclusionProof> {
+        if let Some(proof) = &self.inclusion {
+            return Ok(proof);
+        }
+        if let Some(proof) = &self.non_inclusion {
+            return Err(proof);
+        }
+        unreachable!()
+    }
+    // pub fn inclusion(&self) -> Option<(u64, &Proof)> {
+    //     let proof = self.proof.as_ref()?;
+    //     let leaf_index = self.leaf_index?;
+    //     Some((leaf_index, proof))
+    // }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct Proof {
+    pub value: Option<Bytes>,
+    pub proof_related_nodes: Vec<Bytes>,
+}
+
+impl Proof {
+    /// `nodes` are the root, the siblings down to the leaf level and the
+    /// leaf, as returned by `SpareMerkleTrie::get_proof_nodes`.
+    pub fn from_nodes(value: Option<Bytes>, nodes: &[Arc<Node>]) -> Self {
+        Self {
+            value,
+            proof_related_nodes: nodes.iter().map(|node| proof_node_bytes(node)).collect(),
+        }
+    }
+}
+
+/// Empty leaves are sent as 32 zero bytes, see `NodeValue::parse_leaf`.
+fn proof_node_bytes(node: &Node) -> Bytes {
+    match node.raw() {
+        NodeValue::EmptyLeaf => vec![0_u8; 32].into(),
+        raw => raw.to_bytes().into(),
+    }
+}
+
+/// Checks the proof of the leaf at `leaf_index` against the top `root` and
+/// returns the leaf opening of `hkey`. The `value` of the proof is checked
+/// against the `hval` of the leaf with `hash_value`, the preimage depends on
+/// the trie the leaf belongs to (e.g. `trie_hash` for a storage slot).
+pub fn verify_inclusion<F>(
+    root: &B256,
+    hkey: &B256,
+    proof: &MerkleInclusionProof,
+    hash_value: F,
+) -> Result<LeafOpening, Error>
+where
+    F: FnOnce(&[u8]) -> Result<B256, Error>,
+{
+    let leaf = verify_leaf(root, proof.leaf_index, &proof.proof)?;
+    if leaf.hkey != *hkey {
+        return Err(Error::ProofLeafKeyMismatch {
+            leaf_index: proof.leaf_index,
+            want: *hkey,
+            got: leaf.hkey,
+        });
+    }
+    let value = proof.proof.value.as_ref().ok_or(Error::ProofValueMissing {
+        leaf_index: proof.leaf_index,
+    })?;
+    let hval = hash_value(value)?;
+    if hval != leaf.hval {
+        return Err(Error::ProofValueMismatch {
+            leaf_index: proof.leaf_index,
+            want: leaf.hval,
+            got: hval,
+        });
+    }
+    Ok(leaf)
+}
+
+/// Checks the proofs of the two leaves around `hkey` against the top
+/// `root`, and that they are linked to each other.
+pub fn verify_non_inclusion(
+    root: &B256,
+    hkey: &B256,
+    proof: &MerkleNonInclusionProof,
+) -> Result<(), Error> {
+    let left = verify_leaf(root, proof.left_leaf_index, &proof.left_proof)
+        .map_err(Error::VerifyLeftProof())?;
+    let right = verify_leaf(root, proof.right_leaf_index, &proof.right_proof)
+        .map_err(Error::VerifyRightProof())?;
+    if !(left.hkey < *hkey && *hkey < right.hkey) {
+        return Err(Error::ProofKeyOutOfRange {
+            left: left.hkey,
+            key: *hkey,
+            right: right.hkey,
+        });
+    }
+    if left.next_leaf != proof.right_leaf_index || right.prev_leaf != proof.left_leaf_index {
+        return Err(Error::ProofLeafLinkage {
+            left_index: proof.left_leaf_index,
+            left_next: left.next_leaf,
+            right_index: proof.right_leaf_index,
+            right_prev: right.prev_leaf,
+        });
+    }
+    Ok(())
+}
+
+fn verify_leaf(root: &B256, leaf_index: u64, proof: &Proof) -> Result<LeafOpening, Error> {
+    let nodes = build_node_branch(leaf_index, &proof.proof_related_nodes)?;
+    let top_root = *nodes.last().unwrap().hash();
+    if top_root != *root {
+        return Err(Error::ProofRootMismatch {
+            want: *root,
+            got: top_root,
+        });
+    }
+    Ok(LeafOpening::parse(
+        &proof.proof_related_nodes[ZK_TRIE_DEPTH + 1],
+    ))
+}
+
+/// Checks the encoding of every node of the proof, and that the leaf index
+/// was allocated according to the `next_free_node` of the root.
+fn check_proof_nodes(leaf_index: u64, siblings: &[Bytes]) -> Result<(), Error> {
+    let want = ZK_TRIE_DEPTH + 2;
+    if siblings.len() != want {
+        return Err(Error::InvalidProofNodes {
+            want,
+            got: siblings.len(),
+        });
+    }
+    let root = NodeValue::parse_root(&siblings[0]).map_err(Error::ParseRootFromSibling())?;
+    let next_free_node = utils::parse_node_index(root.branch().unwrap().left.as_slice());
+    if leaf_index >= next_free_node {
+        return Err(Error::ProofLeafIndexOutOfRange {
+            leaf_index,
+            next_free_node,
+        });
+    }
+    for (idx, sibling) in siblings.iter().enumerate().take(ZK_TRIE_DEPTH).skip(1) {
+        NodeValue::parse_branch(sibling).map_err(Error::ParseBranchNode(&idx))?;
+    }
+    let leaf_sibling = &siblings[ZK_TRIE_DEPTH];
+    if leaf_sibling.len() != EMPTY_LEAF_LEN && leaf_sibling.len() != LEAF_OPENING_LEN {
+        return Err(Error::InvalidLeafOpening(leaf_sibling.clone()));
+    }
+    let leaf = &siblings[ZK_TRIE_DEPTH + 1];
+    if leaf.len() != LEAF_OPENING_LEN {
+        return Err(Error::InvalidLeafOpening(leaf.clone()));
+    }
+    check_field_words(leaf_sibling)?;
+    check_field_words(leaf)?;
+    Ok(())
+}
+
+fn build_node_branch_uncheck(
+    trie_path: &[u8],
+    mut siblings: &[Bytes],
+    fallback: bool,
+    // siblings:
+    //   root
+    //   subProof
+    //   leaf(leafOpening)
+) -> Result<Vec<Node>, Error> {
+    let mut out = Vec::new();
+    let leaf_value = &siblings[siblings.len() - 1];
+    siblings = &siblings[..siblings.len() - 1];
+    let mut leaf = Node::new(NodeValue::parse_leaf(
+        vec![trie_path[trie_path.len() - 1]].into(),
+        leaf_value.clone(),
+    ));
+    let mut leaf_hash = *leaf.hash();
+    out.push(leaf);
+
+    let sibling_leaf_idx = siblings.len() - 1;
+    for (idx, sibling_bytes) in siblings.into_iter().enumerate().rev() {
+        if idx == 0 {
+            let root = Node::new(
+                NodeValue::parse_root(&sibling_bytes).map_err(Error::ParseRootFromSibling())?,
+            );
+            out.push(root);
+            break;
+        }
+        let sibling = if idx == sibling_leaf_idx {
+            let leaf = if !fallback {
+                Node::new(NodeValue::parse_leaf(
+                    vec![trie_path[trie_path.len() - 1]].into(),
+                    sibling_bytes.clone(),
+                ))
+            } else {
+                Node::new(NodeValue::EmptyLeaf)
+            };
+            leaf
+        } else {
+            Node::new(
+                NodeValue::parse_branch(&sibling_bytes).map_err(Error::ParseBranchNode(&idx))?,
+            )
+        };
+        let sibling_hash = *sibling.hash();
+        leaf = Node::raw_branch_auto(trie_path[idx], leaf_hash, sibling_hash);
+        leaf_hash = *leaf.hash();
+        out.push(sibling);
+        out.push(leaf);
+    }
+    Ok(out)
+}
+
+/// Rebuilds the nodes on the path of the leaf at `leaf_index` from the
+/// `proof_related_nodes`, from the leaf up to the root node. The sub root
+/// is checked against the root node, not the top root hash.
+pub(crate) fn build_node_branch(leaf_index: u64, siblings: &[Bytes]) -> Result<Vec<Node>, Error> {
+    let mut fallback = false;
+    log::trace!("{:?}", siblings);
+    check_proof_nodes(leaf_index, siblings)?;
+    let trie_path = utils::get_leaf_path(leaf_index);
+
+    loop {
+        let out = build_node_branch_uncheck(&trie_path, siblings, fallback)?;
+
+        let leaf_hash = *out[out.len() - 2].hash();
+        let root = out.last().unwrap().raw().branch().unwrap();
+
+        if leaf_hash != root.right {
+            // glog::info!("{:?}", siblings);
+            // glog::info!("leaf: {:?}", LeafOpening::parse(leaf_value));
+            // glog::info!("leaf-sibling: {:?}", LeafOpening::parse(&siblings[siblings.len()-1]));
+            if !fallback {
+                fallback = true;
+                continue;
+            }
+
+            for (idx, node) in out.iter().enumerate() {
+                log::trace!("[{}] {:?}", idx, node);
+            }
+            let next_free_node = utils::parse_node_index(root.left.as_slice());
+            return Err(Error::InvalidProof {
+                got_sub_root: leaf_hash,
+                want_sub_root: root.right,
+                got_top_root: *Node::root_node(next_free_node, leaf_hash).hash(),
+            });
+        }
+
+        return Ok(out);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloy::primitives::Address;
+
+    use crate::{trie_hash, utils, MemStore, MemZkTrie, PrefixDB};
+
+    use super::*;
+
+    fn build_trie() -> (PrefixDB, MemZkTrie) {
+        utils::dum_trie((2..=10).step_by(2))
+    }
+
+    fn prove(db: &PrefixDB, trie: &MemZkTrie, n: u32) -> (B256, MerkleProof) {
+        let hkey = utils::dum_hkey(n);
+        (hkey, trie.prove(db, hkey, &utils::dum_key(n)).unwrap())
+    }
+
+    #[test]
+    fn test_verify_inclusion() {
+        let (db, trie) = build_trie();
+        let root = *trie.top_root_hash();
+        for n in (2..=10).step_by(2) {
+            let (hkey, proof) = prove(&db, &trie, n);
+            let inclusion = proof.inclusion.unwrap();
+            let leaf = verify_inclusion(&root, &hkey, &inclusion, trie_hash).unwrap();
+            let value = utils::create_dum_digest(n * 10).0;
+            assert_eq!(leaf.hval, trie_hash(&value).unwrap());
+            assert_eq!(inclusion.proof.value, Some(value.to_vec().into()));
+        }
+
+        let (hkey, proof) = prove(&db, &trie, 4);
+        let inclusion = proof.inclusion.unwrap();
+        assert!(matches!(
+            verify_inclusion(&B256::default(), &hkey, &inclusion, trie_hash),
+            Err(Error::ProofRootMismatch { .. })
+        ));
+        let other = trie_hash(&utils::create_dum_digest(6).0).unwrap();
+        assert!(matches!(
+            verify_inclusion(&root, &other, &inclusion, trie_hash),
+            Err(Error::ProofLeafKeyMismatch { .. })
+        ));
+
+        let mut tampered = inclusion.clone();
+        let mut sibling = tampered.proof.proof_related_nodes[5].to_vec();
+        sibling[63] ^= 1;
+        tampered.proof.proof_related_nodes[5] = sibling.into();
+        assert!(matches!(
+            verify_inclusion(&root, &hkey, &tampered, trie_hash),
+            Err(Error::InvalidProof { .. })
+        ));
+
+        let mut other_value = inclusion.clone();
+        other_value.proof.value = Some(utils::create_dum_digest(60).0.to_vec().into());
+        assert!(matches!(
+            verify_inclusion(&root, &hkey, &other_value, trie_hash),
+            Err(Error::ProofValueMismatch { leaf_index, .. }) if leaf_index == inclusion.leaf_index
+        ));
+        let mut no_value = inclusion.clone();
+        no_value.proof.value = None;
+        assert!(matches!(
+            verify_inclusion(&root, &hkey, &no_value, trie_hash),
+            Err(Error::ProofValueMissing { .. })
+        ));
+
+        let mut non_canonical = inclusion.clone();
+        non_canonical.proof.proof_related_nodes[ZK_TRIE_DEPTH + 1] =
+            vec![0xff_u8; LEAF_OPENING_LEN].into();
+        assert!(matches!(
+            verify_inclusion(&root, &hkey, &non_canonical, trie_hash),
+            Err(Error::NonCanonicalWord(_))
+        ));
+        let mut store = MemStore::new();
+        let key = Bytes::from(utils::create_dum_digest(4).0.to_vec());
+        assert!(store
+            .add_inclusion_proof(
+                Address::default(),
+                non_canonical.leaf_index,
+                &key,
+                hkey,
+                None,
+                &non_canonical.proof.proof_related_nodes,
+            )
+            .is_err());
+
+        let mut truncated = inclusion.clone();
+        truncated.proof.proof_related_nodes.pop();
+        assert!(matches!(
+            verify_inclusion(&root, &hkey, &truncated, trie_hash),
+            Err(Error::InvalidProofNodes { .. })
+        ));
+
+        let mut unallocated = inclusion;
+        unallocated.leaf_index = 100;
+        assert!(matches!(
+            verify_inclusion(&root, &hkey, &unallocated, trie_hash),
+            Err(Error::ProofLeafIndexOutOfRange { .. })
+        ));
+    }
+
+    #[test]
+    fn test_verify_non_inclusion() {
+        let (db, trie) = build_trie();
+        let root = *trie.top_root_hash();
+        for n in (1..=11).step_by(2) {
+            let (hkey, proof) = prove(&db, &trie, n);
+            assert!(proof.inclusion.is_none());
+            verify_non_inclusion(&root, &hkey, &proof.non_inclusion.unwrap()).unwrap();
+        }
+
+        let (hkey, proof) = prove(&db, &trie, 3);
+        let non_inclusion = proof.non_inclusion.unwrap();
+        // the leaf errors are stacked, check the left one directly
+        assert!(verify_non_inclusion(&B256::default(), &hkey, &non_inclusion).is_err());
+        assert!(matches!(
+            verify_leaf(
+                &B256::default(),
+                non_inclusion.left_leaf_index,
+                &non_inclusion.left_proof
+            ),
+            Err(Error::ProofRootMismatch { .. })
+        ));
+        let present = trie_hash(&utils::create_dum_digest(2).0).unwrap();
+        assert!(matches!(
+            verify_non_inclusion(&root, &present, &non_inclusion),
+            Err(Error::ProofKeyOutOfRange { .. })
+        ));
+
+        // the leaf after the right one still bounds the key, but it isn't
+        // linked to the left one
+        let inclusions = (2..=10)
+            .step_by(2)
+            .map(|n| prove(&db, &trie, n).1.inclusion.unwrap())
+            .collect::<Vec<_>>();
+        let (hkey, mut unlinked, next) = (1..=11)
+            .step_by(2)
+            .find_map(|n| {
+                let (hkey, proof) = prove(&db, &trie, n);
+                let non_inclusion = proof.non_inclusion.unwrap();
+                let right = LeafOpening::parse(
+                    &non_inclusion.right_proof.proof_related_nodes[ZK_TRIE_DEPTH + 1],
+                );
+                let next = inclusions
+                    .iter()
+                    .find(|inclusion| inclusion.leaf_index == right.next_leaf)?;
+                Some((hkey, non_inclusion, next.clone()))
+            })
+            .unwrap();
+        unlinked.right_leaf_index = next.leaf_index;
+        unlinked.right_proof = next.proof;
+        assert!(matches!(
+            verify_non_inclusion(&root, &hkey, &unlinked),
+            Err(Error::ProofLeafLinkage { .. })
+        ));
+    }
+}
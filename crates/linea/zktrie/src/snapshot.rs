@@ -0,0 +1,299 @@
+use alloy::primitives::{Bytes, B256};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    check_field_words, init_world_state, utils, Database, Error, FlattenedLeaf, LeafOpening, Node,
+    NodeValue, SpareMerkleTrie, ZkTrie, EMPTY_TRIE_NODE, ZK_TRIE_DEPTH,
+};
+
+/// A leaf of a trie, with the value indexed under its `hkey` when the store
+/// knows it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrieLeaf {
+    pub leaf_index: u64,
+    pub hkey: B256,
+    pub hval: B256,
+    pub prev_leaf: u64,
+    pub next_leaf: u64,
+    pub value: Option<Bytes>,
+}
+
+impl TrieLeaf {
+    /// The head and the tail bound the keys, they hold no value.
+    pub fn is_sentinel(&self) -> bool {
+        self.leaf_index == FlattenedLeaf::head().leaf_index
+            || self.leaf_index == FlattenedLeaf::tail().leaf_index
+    }
+
+    pub fn opening(&self) -> LeafOpening {
+        LeafOpening::new(self.prev_leaf, self.next_leaf, self.hkey, self.hval)
+    }
+}
+
+/// Walks the leaves of a trie in the order of their index, the head and the
+/// tail included. The subtrees missing from the store, e.g. the ones a
+/// witness doesn't cover, are skipped and counted in `pruned`.
+pub struct Leaves<'a, D> {
+    db: &'a D,
+    root: B256,
+    // (level, index of the subtree at its level, node hash)
+    stack: Vec<(usize, u64, B256)>,
+    pruned: usize,
+}
+
+impl<'a, D: Database<Node = Node>> Leaves<'a, D> {
+    pub fn pruned(&self) -> usize {
+        self.pruned
+    }
+
+    fn value(&self, leaf_index: u64, hkey: &B256) -> Option<Bytes> {
+        let range = self.db.get_nearest_keys(&self.root, hkey).ok()?;
+        range
+            .center
+            .filter(|leaf| leaf.leaf_index == leaf_index)
+            .map(|leaf| leaf.leaf_value)
+    }
+}
+
+impl<'a, D: Database<Node = Node>> Iterator for Leaves<'a, D> {
+    type Item = Result<TrieLeaf, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((lvl, index, hash)) = self.stack.pop() {
+            if EMPTY_TRIE_NODE.contains_key(&hash) {
+                continue;
+            }
+            if lvl > ZK_TRIE_DEPTH + 1 {
+                return Some(Err(Error::ReachedMaxLevel));
+            }
+            let node = match self.db.get_node(&hash) {
+                Ok(Some(node)) => node,
+                Ok(None) => {
+                    self.pruned += 1;
+                    continue;
+                }
+                Err(err) => return Some(Err(err)),
+            };
+            match node.raw() {
+                NodeValue::Branch(branch) => {
+                    self.stack.push((lvl + 1, index * 2 + 1, branch.right));
+                    self.stack.push((lvl + 1, index * 2, branch.left));
+                }
+                NodeValue::Leaf(leaf) => {
+                    // the bits below the node, the last byte is the leaf type
+                    let bits = &leaf.path[..leaf.path.len().saturating_sub(1)];
+                    let leaf_index = bits.iter().fold(index, |n, bit| n * 2 + *bit as u64);
+                    let opening = LeafOpening::parse(&leaf.value);
+                    return Some(Ok(TrieLeaf {
+                        leaf_index,
+                        value: self.value(leaf_index, &opening.hkey),
+                        hkey: opening.hkey,
+                        hval: opening.hval,
+                        prev_leaf: opening.prev_leaf,
+                        next_leaf: opening.next_leaf,
+                    }));
+                }
+                NodeValue::EmptyLeaf | NodeValue::NextFree(_) => {}
+            }
+        }
+        None
+    }
+}
+
+/// Every leaf of a trie with its value, enough to rebuild the trie in
+/// another store. Serialized as json.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrieSnapshot {
+    pub root: B256,
+    pub next_free_node: u64,
+    pub leaves: Vec<TrieLeaf>,
+}
+
+impl<D: Database<Node = Node>> ZkTrie<D> {
+    pub fn leaves<'a>(&self, db: &'a D) -> Result<Leaves<'a, D>, Error> {
+        Ok(Leaves {
+            db,
+            root: *self.top_root_hash(),
+            stack: vec![(1, 0, self.sub_root_hash(db)?)],
+            pruned: 0,
+        })
+    }
+
+    /// Fails if a subtree or the value of a leaf is missing from the store.
+    pub fn export(&self, db: &D) -> Result<TrieSnapshot, Error> {
+        let mut iter = self.leaves(db)?;
+        let mut leaves = Vec::new();
+        for leaf in &mut iter {
+            let leaf = leaf?;
+            if leaf.value.is_none() && !leaf.is_sentinel() {
+                return Err(Error::SnapshotLeafValueMissing {
+                    leaf_index: leaf.leaf_index,
+                });
+            }
+            leaves.push(leaf);
+        }
+        if iter.pruned() > 0 {
+            return Err(Error::SnapshotIncomplete {
+                root: *self.top_root_hash(),
+                pruned: iter.pruned(),
+            });
+        }
+        Ok(TrieSnapshot {
+            root: *self.top_root_hash(),
+            next_free_node: self.next_free_node(db)?,
+            leaves,
+        })
+    }
+}
+
+impl TrieSnapshot {
+    /// Writes the leaves into `db` and checks the root of the rebuilt trie.
+    /// The value of each leaf is checked against its `hval` with
+    /// `hash_value`, the preimage depends on the trie (e.g. `trie_hash` for
+    /// a storage slot).
+    pub fn import<D, F>(&self, db: &mut D, hash_value: F) -> Result<ZkTrie<D>, Error>
+    where
+        D: Database<Node = Node>,
+        F: Fn(&[u8]) -> Result<B256, Error>,
+    {
+        let (empty_root, _) = init_world_state();
+        let mut state = SpareMerkleTrie::new(*empty_root.hash());
+        let mut leaves = Vec::with_capacity(self.leaves.len());
+        for leaf in &self.leaves {
+            check_field_words(&leaf.opening().to_bytes())?;
+            let value = match (&leaf.value, leaf.leaf_index) {
+                (Some(value), _) => {
+                    let hval = hash_value(value)?;
+                    if hval != leaf.hval {
+                        return Err(Error::SnapshotValueMismatch {
+                            leaf_index: leaf.leaf_index,
+                            want: leaf.hval,
+                            got: hval,
+                        });
+                    }
+                    value.clone()
+                }
+                (None, 0) => FlattenedLeaf::head().leaf_value.clone(),
+                (None, 1) => FlattenedLeaf::tail().leaf_value.clone(),
+                (None, leaf_index) => return Err(Error::SnapshotLeafValueMissing { leaf_index }),
+            };
+            db.update_index(leaf.hkey, FlattenedLeaf::new(leaf.leaf_index, value));
+            let path = utils::get_leaf_path(leaf.leaf_index);
+            leaves.push((path, Some(leaf.opening().to_bytes())));
+        }
+        leaves.sort_by(|(a, _), (b, _)| a.cmp(b));
+        state.put_leaves(db, self.next_free_node, &leaves)?;
+        if state.root_hash() != &self.root {
+            return Err(Error::SnapshotRootMismatch {
+                want: self.root,
+                got: *state.root_hash(),
+            });
+        }
+        Ok(ZkTrie::new(self.root))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use alloy::primitives::Address;
+
+    use super::*;
+    use crate::{trie_hash, MemStore, MemZkTrie, PrefixDB};
+
+    fn build() -> (PrefixDB, MemZkTrie) {
+        let (mut db, mut trie) = utils::dum_trie(1..20);
+        for n in (1..20).step_by(3) {
+            trie.remove(&mut db, utils::dum_hkey(n), &utils::dum_key(n))
+                .unwrap();
+        }
+        (db, trie)
+    }
+
+    #[test]
+    fn test_export_and_import() {
+        let (db, trie) = build();
+
+        let snapshot = trie.export(&db).unwrap();
+        assert_eq!(snapshot.leaves.len(), 2 + 19 - 7);
+        assert!(snapshot
+            .leaves
+            .windows(2)
+            .all(|n| n[0].leaf_index < n[1].leaf_index));
+        for leaf in snapshot.leaves.iter().filter(|leaf| !leaf.is_sentinel()) {
+            let value = leaf.value.clone().unwrap();
+            assert_eq!(leaf.hval, trie_hash(&value).unwrap());
+        }
+
+        let json = serde_json::to_vec(&snapshot).unwrap();
+        let snapshot: TrieSnapshot = serde_json::from_slice(&json).unwrap();
+        let store = Arc::new(Mutex::new(MemStore::new_full()));
+        let mut other = PrefixDB::new(Address::default(), store);
+        let imported = snapshot.import(&mut other, trie_hash).unwrap();
+        assert_eq!(imported.top_root_hash(), trie.top_root_hash());
+        assert_eq!(imported.export(&other).unwrap(), snapshot);
+
+        let key = utils::create_dum_digest(2).0;
+        let value = imported
+            .read(&other, trie_hash(&key).unwrap(), &key)
+            .unwrap();
+        assert_eq!(value, Some(utils::create_dum_digest(20).0.to_vec()));
+
+        let mut broken = snapshot.clone();
+        broken.leaves[2].hval = B256::default();
+        let store = Arc::new(Mutex::new(MemStore::new_full()));
+        let mut other = PrefixDB::new(Address::default(), store);
+        assert!(matches!(
+            broken.import(&mut other, trie_hash),
+            Err(Error::SnapshotValueMismatch { .. })
+        ));
+
+        // a value that doesn't match its leaf is rejected before the root
+        // is checked
+        let mut swapped = snapshot.clone();
+        swapped.leaves[2].value = swapped.leaves[3].value.clone();
+        let store = Arc::new(Mutex::new(MemStore::new_full()));
+        let mut other = PrefixDB::new(Address::default(), store);
+        assert!(matches!(
+            swapped.import(&mut other, trie_hash),
+            Err(Error::SnapshotValueMismatch { leaf_index, .. })
+                if leaf_index == snapshot.leaves[2].leaf_index
+        ));
+    }
+
+    #[test]
+    fn test_leaves_of_a_witness() {
+        let (db, trie) = build();
+
+        // only the nodes on the path of one key
+        let key = utils::create_dum_digest(5).0;
+        let hkey = trie_hash(&key).unwrap();
+        let proof = trie.prove(&db, hkey, &key).unwrap();
+        let inclusion = proof.inclusion.unwrap();
+        let mut witness = MemStore::new();
+        let root = witness
+            .add_inclusion_proof(
+                Address::default(),
+                inclusion.leaf_index,
+                &proof.key,
+                hkey,
+                inclusion.proof.value.as_deref(),
+                &inclusion.proof.proof_related_nodes,
+            )
+            .unwrap();
+        assert_eq!(&root, trie.top_root_hash());
+        let witness = PrefixDB::new(Address::default(), Arc::new(Mutex::new(witness)));
+
+        let mut iter = trie.leaves(&witness).unwrap();
+        let leaves = iter.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+        assert!(leaves.len() < 2 + 19 - 7);
+        let leaf = leaves.iter().find(|leaf| leaf.hkey == hkey).unwrap();
+        assert_eq!(leaf.leaf_index, inclusion.leaf_index);
+        assert_eq!(leaf.value, inclusion.proof.value);
+        assert!(iter.pruned() > 0);
+        assert!(trie.export(&witness).is_err());
+    }
+}
@@ -1,7 +1,7 @@
 use std::prelude::v1::*;
 
 use crate::{prefix_len, utils, Error, FlattenedLeaf, KeyRange, Node, NodeValue};
-use alloy::primitives::{Bytes, B256};
+use alloy::primitives::{Address, Bytes, B256};
 use std::sync::Arc;
 
 pub const ZK_TRIE_DEPTH: usize = 40;
@@ -15,6 +15,23 @@ pub trait Database {
     fn remove_index(&mut self, k: &B256);
     fn get_code(&self, hash: &B256) -> Option<Arc<Bytes>>;
     fn set_code(&mut self, hash: B256, code: Arc<Bytes>);
+
+    /// Whether the key ranges are resolved from every leaf put into the
+    /// store, instead of only at the roots covered by a witness.
+    fn full_index(&self) -> bool {
+        false
+    }
+
+    /// Called with the top root of the trie after every write, for the
+    /// stores whose index only follows the latest root.
+    fn update_root(&mut self, _root: B256) {}
+}
+
+/// A `Database` shared by the world state and the storage tries, the key
+/// index of each trie is kept under its own prefix. The storage tries of
+/// different accounts may be written from different threads.
+pub trait PrefixDatabase: Database<Node = Node> + Send + Sized {
+    fn new_prefix(&self, prefix: Address) -> Self;
 }
 
 #[derive(Debug)]
@@ -84,6 +101,64 @@ impl SpareMerkleTrie {
         Ok(())
     }
 
+    /// Writes the leaves at once, each node above them is hashed once.
+    /// `leaves` is sorted by path, `None` removes the leaf.
+    pub fn put_leaves<D: Database<Node = Node>>(
+        &mut self,
+        db: &mut D,
+        next_free_node: u64,
+        leaves: &[([u8; ZK_TRIE_DEPTH + 2], Option<Vec<u8>>)],
+    ) -> Result<(), Error> {
+        let root_node = self.root_node(db)?;
+        let branch_node = root_node
+            .raw()
+            .branch()
+            .ok_or_else(|| Error::RootNodeExpectToBeBranchNode(root_node.clone()))?;
+        let mut sub_root = branch_node.right;
+        if !leaves.is_empty() {
+            sub_root = *self.add_leaves(db, 1, &sub_root, leaves)?.hash();
+        }
+        let root = Node::root_node(next_free_node, sub_root);
+        let root = db.update_node(*root.hash(), root)?;
+        self.root_hash = *root.hash();
+        Ok(())
+    }
+
+    fn add_leaves<D: Database<Node = Node>>(
+        &self,
+        db: &mut D,
+        lvl: usize,
+        current: &B256,
+        leaves: &[([u8; ZK_TRIE_DEPTH + 2], Option<Vec<u8>>)],
+    ) -> Result<Arc<Node>, Error> {
+        if lvl >= ZK_TRIE_DEPTH + 2 {
+            return Err(Error::ReachedMaxLevel);
+        }
+        let n = match db.get_node(current)? {
+            Some(n) => n,
+            None => return Err(Error::NodeNotFound(lvl, *current)),
+        };
+        match n.raw() {
+            NodeValue::Branch(branch) => {
+                // the paths below this node share the first `lvl` bits
+                let split = leaves.partition_point(|(path, _)| path[lvl] == 0);
+                let (left, right) = leaves.split_at(split);
+                let mut updated = branch.clone();
+                if !left.is_empty() {
+                    updated.left = *self.add_leaves(db, lvl + 1, &branch.left, left)?.hash();
+                }
+                if !right.is_empty() {
+                    updated.right = *self.add_leaves(db, lvl + 1, &branch.right, right)?.hash();
+                }
+                self.db_add(db, updated.into())
+            }
+            _ => match &leaves[0] {
+                (path, Some(value)) => self.add_leaf(db, lvl, current, path, value.clone()),
+                (_, None) => Ok(Node::empty_leaf()),
+            },
+        }
+    }
+
     pub fn remove<D: Database<Node = Node>>(
         &mut self,
         db: &mut D,
@@ -173,6 +248,35 @@ impl SpareMerkleTrie {
         db.update_node(*node.hash(), node)
     }
 
+    /// The root, the sibling of every node on `path` below the sub trie
+    /// root, and the leaf, in the order of Shomei's `proofRelatedNodes`.
+    pub fn get_proof_nodes<D: Database<Node = Node>>(
+        &self,
+        db: &D,
+        path: &[u8],
+    ) -> Result<Vec<Arc<Node>>, Error> {
+        let get = |lvl: usize, hash: &B256| match db.get_node(hash)? {
+            Some(n) => Ok(n),
+            None => Err(Error::NodeNotFound(lvl, *hash)),
+        };
+        let mut out = Vec::with_capacity(path.len());
+        let mut current = self.root_node(db)?;
+        out.push(current.clone());
+        for lvl in 0..path.len() - 1 {
+            let branch = match current.raw() {
+                NodeValue::Branch(branch) => branch,
+                _ => return Err(Error::ProofExpectBranchNode(lvl, *current.hash())),
+            };
+            // the left child of the root is the next free node index
+            if lvl > 0 {
+                out.push(get(lvl + 1, branch.child(1 - path[lvl]))?);
+            }
+            current = get(lvl + 1, branch.child(path[lvl]))?;
+        }
+        out.push(current);
+        Ok(out)
+    }
+
     pub fn get_node<D: Database<Node = Node>>(
         &self,
         db: &D,
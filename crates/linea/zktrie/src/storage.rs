@@ -2,12 +2,13 @@ use alloy::primitives::{Address, Bytes, B256};
 use lazy_static::lazy_static;
 use linea_mimc::keccak_hash;
 use std::collections::BTreeMap;
+use std::ops::Bound;
 use std::sync::Arc;
 use std::sync::Mutex;
 
 use crate::{
-    mimc_safe, trie_hash, utils, Database, Error, LeafOpening, Node, NodeValue, Trace, EMPTY_DB,
-    EMPTY_TRIE_NODE, EMPTY_TRIE_NODE_HASH, ZK_TRIE_DEPTH,
+    build_node_branch, mimc_safe, trie_hash, utils, Database, Error, LeafOpening, Node,
+    PrefixDatabase, Trace, EMPTY_DB, EMPTY_TRIE_NODE, EMPTY_TRIE_NODE_HASH, ZK_TRIE_DEPTH,
 };
 
 #[derive(Debug)]
@@ -59,26 +60,39 @@ impl Database for PrefixDB {
 
     fn remove_index(&mut self, k: &B256) {
         let mut raw = self.raw.lock().unwrap();
-        raw.remove_index(k)
+        raw.remove_index(self.prefix, k)
     }
 
     fn update_index(&mut self, k: B256, v: FlattenedLeaf) {
         let mut raw = self.raw.lock().unwrap();
-        raw.update_index(k, v)
+        raw.update_index(self.prefix, k, v)
     }
 
     fn update_node(&mut self, key: B256, node: Self::Node) -> Result<Arc<Self::Node>, Error> {
         let mut raw = self.raw.lock().unwrap();
         raw.update_node(key, node)
     }
+
+    fn full_index(&self) -> bool {
+        self.raw.lock().unwrap().full_index
+    }
+}
+
+impl PrefixDatabase for PrefixDB {
+    fn new_prefix(&self, prefix: Address) -> Self {
+        PrefixDB::new_prefix(self, prefix)
+    }
 }
 
 #[derive(Debug)]
 pub struct MemStore {
     use_static_data: bool,
+    // the staged index holds every leaf put into the store, so the key
+    // ranges can be resolved without a witness.
+    full_index: bool,
     nodes: BTreeMap<B256, Arc<Node>>,
     index: LevelMap,
-    staging: BTreeMap<B256, FlattenedLeaf>,
+    staging: BTreeMap<(Address, B256), FlattenedLeaf>,
     codes: BTreeMap<B256, Arc<Bytes>>,
 }
 
@@ -93,6 +107,7 @@ impl LevelMap {
         let mut base = LevelMap::new();
         while idx < traces.len() {
             let trace = &traces[idx];
+            trace.check_words()?;
             let prefix = utils::parse_prefix(trace.location());
             let top_hash = trace.old_top_hash();
             let root_map = base.vals.entry(top_hash).or_insert_with(|| BTreeMap::new());
@@ -159,6 +174,7 @@ impl MemStore {
     pub fn new() -> Self {
         Self {
             use_static_data: true,
+            full_index: false,
             codes: BTreeMap::new(),
             nodes: BTreeMap::new(),
             index: LevelMap::new(),
@@ -166,6 +182,15 @@ impl MemStore {
         }
     }
 
+    /// A store for tries built locally from empty, the key ranges are
+    /// looked up in the leaves put into it when they are not covered by a
+    /// witness.
+    pub fn new_full() -> Self {
+        let mut ms = Self::new();
+        ms.full_index = true;
+        ms
+    }
+
     pub(crate) fn new_on_init() -> Self {
         let mut ms = Self::new();
         ms.use_static_data = false;
@@ -173,11 +198,14 @@ impl MemStore {
     }
 
     pub fn from_traces(traces: &[Trace]) -> Result<Self, Error> {
+        // the traces are checked while indexed, before their nodes are hashed
+        let index = LevelMap::from_traces(traces)?;
         Ok(Self {
             use_static_data: true,
+            full_index: false,
             codes: BTreeMap::new(),
             nodes: trace_nodes(traces),
-            index: LevelMap::from_traces(traces)?,
+            index,
             staging: BTreeMap::new(),
         })
     }
@@ -189,97 +217,6 @@ impl MemStore {
         }
     }
 
-    fn build_node_branch_uncheck(
-        &self,
-        trie_path: &[u8],
-        mut siblings: &[Bytes],
-        fallback: bool,
-        // siblings:
-        //   root
-        //   subProof
-        //   leaf(leafOpening)
-    ) -> Result<Vec<Node>, Error> {
-        let mut out = Vec::new();
-        let leaf_value = &siblings[siblings.len() - 1];
-        siblings = &siblings[..siblings.len() - 1];
-        let mut leaf = Node::new(NodeValue::parse_leaf(
-            vec![trie_path[trie_path.len() - 1]].into(),
-            leaf_value.clone(),
-        ));
-        let mut leaf_hash = *leaf.hash();
-        out.push(leaf);
-
-        let sibling_leaf_idx = siblings.len() - 1;
-        for (idx, sibling_bytes) in siblings.into_iter().enumerate().rev() {
-            if idx == 0 {
-                let root = Node::new(
-                    NodeValue::parse_root(&sibling_bytes).map_err(Error::ParseRootFromSibling())?,
-                );
-                out.push(root);
-                break;
-            }
-            let sibling = if idx == sibling_leaf_idx {
-                let leaf = if !fallback {
-                    Node::new(NodeValue::parse_leaf(
-                        vec![trie_path[trie_path.len() - 1]].into(),
-                        sibling_bytes.clone(),
-                    ))
-                } else {
-                    Node::new(NodeValue::EmptyLeaf)
-                };
-                leaf
-            } else {
-                Node::new(
-                    NodeValue::parse_branch(&sibling_bytes)
-                        .map_err(Error::ParseBranchNode(&idx))?,
-                )
-            };
-            let sibling_hash = *sibling.hash();
-            leaf = Node::raw_branch_auto(trie_path[idx], leaf_hash, sibling_hash);
-            leaf_hash = *leaf.hash();
-            out.push(sibling);
-            out.push(leaf);
-        }
-        Ok(out)
-    }
-
-    fn build_node_branch(&self, leaf_index: u64, siblings: &[Bytes]) -> Result<Vec<Node>, Error> {
-        let mut fallback = false;
-        log::trace!("{:?}", siblings);
-        let trie_path = utils::get_leaf_path(leaf_index);
-        assert_eq!(siblings.len(), trie_path.len());
-
-        loop {
-            let out = self.build_node_branch_uncheck(&trie_path, siblings, fallback)?;
-            // TODO: sanity check for node hashes
-
-            let leaf_hash = *out[out.len() - 2].hash();
-            let root = out.last().unwrap().raw().branch().unwrap();
-
-            if leaf_hash != root.right {
-                // glog::info!("{:?}", siblings);
-                // glog::info!("leaf: {:?}", LeafOpening::parse(leaf_value));
-                // glog::info!("leaf-sibling: {:?}", LeafOpening::parse(&siblings[siblings.len()-1]));
-                if !fallback {
-                    fallback = true;
-                    continue;
-                }
-
-                for (idx, node) in out.iter().enumerate() {
-                    log::trace!("[{}] {:?}", idx, node);
-                }
-                let next_free_node = utils::parse_node_index(root.left.as_slice());
-                return Err(Error::InvalidProof {
-                    got_sub_root: leaf_hash,
-                    want_sub_root: root.right,
-                    got_top_root: *Node::root_node(next_free_node, leaf_hash).hash(),
-                });
-            }
-
-            return Ok(out);
-        }
-    }
-
     pub fn add_non_inclusion_proof(
         &mut self,
         prefix: Address,
@@ -290,11 +227,9 @@ impl MemStore {
         left_siblings: &[Bytes],
         right_siblings: &[Bytes],
     ) -> Result<B256, Error> {
-        let right_nodes = self
-            .build_node_branch(right_leaf_index, right_siblings)
+        let right_nodes = build_node_branch(right_leaf_index, right_siblings)
             .map_err(Error::BuildNonInclusionProofRight(&prefix, &key))?;
-        let left_nodes = self
-            .build_node_branch(left_leaf_index, left_siblings)
+        let left_nodes = build_node_branch(left_leaf_index, left_siblings)
             .map_err(Error::BuildNonInclusionProofLeft(&prefix, &key))?;
         let root_hash = left_nodes.last().map(|n| *n.hash()).unwrap();
         for node in left_nodes {
@@ -331,8 +266,7 @@ impl MemStore {
         value: Option<&[u8]>,
         siblings: &[Bytes],
     ) -> Result<B256, Error> {
-        let out = self
-            .build_node_branch(leaf_index, siblings)
+        let out = build_node_branch(leaf_index, siblings)
             .map_err(Error::BuildInclusionProof(&prefix, &key))?;
         let root_hash = out.last().map(|n| *n.hash()).unwrap();
         for node in out {
@@ -363,7 +297,7 @@ impl MemStore {
         self.codes.insert(hash, code);
     }
 
-    fn get_node(&self, key: &B256) -> Result<Option<Arc<Node>>, Error> {
+    pub(crate) fn get_node(&self, key: &B256) -> Result<Option<Arc<Node>>, Error> {
         if let Some(empty_db) = self.empty_db() {
             if let Some(node) = empty_db.get_node(key).map_err(Error::DBRedirectStatic())? {
                 return Ok(Some(node));
@@ -384,8 +318,8 @@ impl MemStore {
         Ok(node)
     }
 
-    fn update_index(&mut self, k: B256, v: FlattenedLeaf) {
-        self.staging.insert(k, v);
+    fn update_index(&mut self, prefix: Address, k: B256, v: FlattenedLeaf) {
+        self.staging.insert((prefix, k), v);
     }
 
     fn empty_db(&self) -> Option<&'static Self> {
@@ -411,6 +345,9 @@ impl MemStore {
             Some(map) => match map.get(&(prefix, *k)) {
                 Some(r) => Ok(r.clone()),
                 None => {
+                    if let Some(r) = self.get_staged_nearest_keys(prefix, k) {
+                        return Ok(r);
+                    }
                     // dbg!(&map);
                     return Err(Error::IndexNotFoundAtRoot {
                         root: *root,
@@ -424,12 +361,45 @@ impl MemStore {
                 right_index: 1,
                 center: None,
             }),
-            None => Err(Error::UnknownRoot { root: *root }),
+            None => match self.get_staged_nearest_keys(prefix, k) {
+                Some(r) => Ok(r),
+                None => Err(Error::UnknownRoot { root: *root }),
+            },
         }
     }
 
-    fn remove_index(&mut self, k: &B256) {
-        self.staging.remove(k);
+    fn remove_index(&mut self, prefix: Address, k: &B256) {
+        self.staging.remove(&(prefix, *k));
+    }
+
+    /// The head and the tail are implied, so tries created from
+    /// `EMPTY_TRIE_NODE_HASH` resolve as well.
+    fn get_staged_nearest_keys(&self, prefix: Address, k: &B256) -> Option<KeyRange> {
+        if !self.full_index {
+            return None;
+        }
+        let key = (prefix, *k);
+        let left_index = self
+            .staging
+            .range(..key)
+            .next_back()
+            .filter(|((p, _), _)| *p == prefix)
+            .map_or(FlattenedLeaf::head().leaf_index, |(_, leaf)| {
+                leaf.leaf_index
+            });
+        let right_index = self
+            .staging
+            .range((Bound::Excluded(key), Bound::Unbounded))
+            .next()
+            .filter(|((p, _), _)| *p == prefix)
+            .map_or(FlattenedLeaf::tail().leaf_index, |(_, leaf)| {
+                leaf.leaf_index
+            });
+        Some(KeyRange {
+            left_index,
+            center: self.staging.get(&key).cloned(),
+            right_index,
+        })
     }
 }
 
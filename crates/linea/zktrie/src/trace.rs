@@ -5,7 +5,7 @@ use alloy::primitives::{Bytes, B256};
 use core::convert::TryFrom;
 use serde::{Deserialize, Serialize, Serializer};
 
-use crate::{utils, FlattenedLeaf, KeyRange, LeafOpening, Node};
+use crate::{check_field_words, utils, Error, FlattenedLeaf, KeyRange, LeafOpening, Node};
 
 #[derive(Clone, Debug, Deserialize)]
 #[serde(try_from = "RawTrace")]
@@ -86,6 +86,16 @@ impl Trace {
         }
     }
 
+    pub fn new_state(&self) -> (u64, B256) {
+        match self {
+            Trace::Deletion(n) => (n.new_next_free_node, n.new_sub_root),
+            Trace::Insertion(n) => (n.new_next_free_node, n.new_sub_root),
+            Trace::Read(n) => (n.next_free_node, n.sub_root),
+            Trace::ReadZero(n) => (n.next_free_node, n.sub_root),
+            Trace::Update(n) => (n.new_next_free_node, n.new_sub_root),
+        }
+    }
+
     pub fn old_top_hash(&self) -> B256 {
         *self.old_top_node().hash()
     }
@@ -164,6 +174,46 @@ impl Trace {
         }
     }
 
+    /// Checks the words hashed when the nodes of the trace are rebuilt, see
+    /// `check_field_words`.
+    pub fn check_words(&self) -> Result<(), Error> {
+        let (roots, proofs, leaves) = match self {
+            Self::Read(n) => (vec![n.sub_root], vec![&n.proof], vec![&n.leaf]),
+            Self::ReadZero(n) => (
+                vec![n.sub_root],
+                vec![&n.left_proof, &n.right_proof],
+                vec![&n.left_leaf, &n.right_leaf],
+            ),
+            Self::Insertion(n) => (
+                vec![n.old_sub_root, n.new_sub_root],
+                vec![&n.left_proof, &n.new_proof, &n.right_proof],
+                vec![&n.prior_left_leaf, &n.prior_right_leaf],
+            ),
+            Self::Update(n) => (
+                vec![n.old_sub_root, n.new_sub_root],
+                vec![&n.proof],
+                vec![&n.prior_updated_leaf],
+            ),
+            Self::Deletion(n) => (
+                vec![n.old_sub_root, n.new_sub_root],
+                vec![&n.left_proof, &n.deleted_proof, &n.right_proof],
+                vec![
+                    &n.prior_left_leaf,
+                    &n.prior_deleted_leaf,
+                    &n.prior_right_leaf,
+                ],
+            ),
+        };
+        let words = roots
+            .into_iter()
+            .chain(proofs.into_iter().flat_map(|proof| proof.siblings.clone()))
+            .chain(leaves.into_iter().flat_map(|leaf| [leaf.hkey, leaf.hval]));
+        for word in words {
+            check_field_words(word.as_slice())?;
+        }
+        Ok(())
+    }
+
     pub fn nodes(&self) -> Vec<Node> {
         match self {
             Self::Update(n) => n.proof.build_nodes(Some(&n.prior_updated_leaf)),
@@ -184,6 +234,47 @@ impl Trace {
     }
 }
 
+/// The first write two trace lists disagree on, `index` counts the writes
+/// only.
+#[derive(Debug)]
+pub struct TraceDiff<'a> {
+    pub index: usize,
+    pub local: Option<&'a Trace>,
+    pub remote: Option<&'a Trace>,
+}
+
+/// Compares the writes of `local` and `remote`, e.g. the traces recorded
+/// while committing a block against the ones returned by
+/// `rollup_getZkEVMStateMerkleProofV0`. The reads are skipped, they depend
+/// on how the block was executed.
+pub fn diff_traces<'a>(local: &'a [Trace], remote: &'a [Trace]) -> Option<TraceDiff<'a>> {
+    let mut local = local.iter().filter(|trace| trace.is_update());
+    let mut remote = remote.iter().filter(|trace| trace.is_update());
+    let mut index = 0;
+    loop {
+        let (a, b) = (local.next(), remote.next());
+        let same = match (a, b) {
+            (Some(a), Some(b)) => {
+                core::mem::discriminant(a) == core::mem::discriminant(b)
+                    && a.location() == b.location()
+                    && a.key() == b.key()
+                    && a.old_state() == b.old_state()
+                    && a.new_state() == b.new_state()
+            }
+            (None, None) => return None,
+            _ => false,
+        };
+        if !same {
+            return Some(TraceDiff {
+                index,
+                local: a,
+                remote: b,
+            });
+        }
+        index += 1;
+    }
+}
+
 #[repr(u32)]
 pub enum TraceType {
     Read = 0,
@@ -351,7 +442,11 @@ pub struct ReadZeroTrace {
 
 #[cfg(test)]
 mod test {
-    use alloy::primitives::{hex::FromHex, Bytes};
+    use std::sync::{Arc, Mutex};
+
+    use alloy::primitives::{hex::FromHex, Address, Bytes};
+
+    use crate::{MemStore, MemZkTrie, PrefixDB, TraceLog};
 
     use super::*;
 
@@ -381,4 +476,82 @@ mod test {
     //     let update_trace = parse_trace("update_trace");
     //     assert!(matches!(update_trace, Trace::Update(_)));
     // }
+
+    enum Op {
+        Read(u32),
+        Put(u32, u32),
+        Remove(u32),
+    }
+
+    fn apply(trie: &mut MemZkTrie, db: &mut PrefixDB, op: &Op) -> Option<Vec<u8>> {
+        use utils::{dum_hkey, dum_key, dum_put};
+        match *op {
+            Op::Read(n) => trie.read(db, dum_hkey(n), &dum_key(n)).unwrap(),
+            Op::Put(n, v) => {
+                dum_put(trie, db, n, v);
+                None
+            }
+            Op::Remove(n) => {
+                trie.remove(db, dum_hkey(n), &dum_key(n)).unwrap();
+                None
+            }
+        }
+    }
+
+    #[test]
+    fn test_replay_recorded_traces() {
+        let (mut db, trie) = utils::dum_trie([2, 4, 6]);
+        let old_root = *trie.top_root_hash();
+
+        let ops = [
+            Op::Read(4),
+            Op::Read(3),
+            Op::Put(8, 80),
+            Op::Put(4, 41),
+            Op::Remove(6),
+            Op::Read(6),
+        ];
+        let log = TraceLog::default();
+        let mut trie = MemZkTrie::new(old_root).with_trace_log(Bytes::new(), log.clone());
+        let reads = ops
+            .iter()
+            .map(|op| apply(&mut trie, &mut db, op))
+            .collect::<Vec<_>>();
+        let traces = log.lock().unwrap().clone();
+        assert_eq!(traces.len(), ops.len());
+        assert!(matches!(traces[0], Trace::Read(_)));
+        assert!(matches!(traces[1], Trace::ReadZero(_)));
+        assert!(matches!(traces[2], Trace::Insertion(_)));
+        assert!(matches!(traces[3], Trace::Update(_)));
+        assert!(matches!(traces[4], Trace::Deletion(_)));
+        assert!(matches!(traces[5], Trace::ReadZero(_)));
+
+        // the traces alone are enough to replay the operations, like the
+        // ones from Shomei
+        let store = Arc::new(Mutex::new(MemStore::from_traces(&traces).unwrap()));
+        let mut witness = PrefixDB::new(Address::default(), store);
+        let mut replay = MemZkTrie::new(old_root);
+        let replayed = ops
+            .iter()
+            .map(|op| apply(&mut replay, &mut witness, op))
+            .collect::<Vec<_>>();
+        assert_eq!(replayed, reads);
+        assert_eq!(replay.top_root_hash(), trie.top_root_hash());
+
+        // the words of client traces are checked before they are hashed
+        let mut non_canonical = traces.clone();
+        if let Trace::Update(n) = &mut non_canonical[3] {
+            n.proof.siblings[10] = B256::repeat_byte(0xff);
+        }
+        assert!(matches!(
+            MemStore::from_traces(&non_canonical),
+            Err(Error::NonCanonicalWord(_))
+        ));
+
+        assert!(diff_traces(&traces, &traces).is_none());
+        let diff = diff_traces(&traces, &traces[..3]).unwrap();
+        assert_eq!(diff.index, 1);
+        assert!(matches!(diff.local, Some(Trace::Update(_))));
+        assert!(diff.remote.is_none());
+    }
 }
@@ -1,11 +1,14 @@
 use core::marker::PhantomData;
+use std::collections::BTreeMap;
 use std::sync::{Arc, Mutex};
 
 use alloy::primitives::{Address, Bytes, B256};
 
 use crate::{
-    init_world_state, utils, Database, Error, FlattenedLeaf, LeafOpening, MemStore, Node, PrefixDB,
-    SpareMerkleTrie,
+    init_world_state, utils, Database, DeletionTrace, Error, FlattenedLeaf, InsertionTrace,
+    KeyRange, LeafOpening, MemStore, MerkleInclusionProof, MerkleNonInclusionProof, MerkleProof,
+    Node, PrefixDB, Proof, ReadTrace, ReadZeroTrace, SpareMerkleTrie, Trace, TraceProof,
+    UpdateTrace,
 };
 
 lazy_static::lazy_static! {
@@ -24,9 +27,79 @@ lazy_static::lazy_static! {
 
 pub type MemZkTrie = ZkTrie<PrefixDB>;
 
+/// The traces recorded by one or more tries, in the order of the reads and
+/// writes.
+pub type TraceLog = Arc<Mutex<Vec<Trace>>>;
+
+#[derive(Debug)]
+struct TraceRecorder {
+    location: Bytes,
+    log: TraceLog,
+}
+
+/// The writes applied together by `ZkTrie::apply_batch`, in order.
+#[derive(Clone, Debug, Default)]
+pub struct WriteBatch {
+    ops: Vec<BatchOp>,
+}
+
+#[derive(Clone, Debug)]
+enum BatchOp {
+    Put {
+        hkey: B256,
+        key: Bytes,
+        hval: B256,
+        value: Vec<u8>,
+    },
+    Remove {
+        hkey: B256,
+        key: Bytes,
+    },
+}
+
+impl WriteBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn put(&mut self, hkey: B256, key: &[u8], hval: B256, value: Vec<u8>) {
+        self.ops.push(BatchOp::Put {
+            hkey,
+            key: Bytes::copy_from_slice(key),
+            hval,
+            value,
+        });
+    }
+
+    pub fn remove(&mut self, hkey: B256, key: &[u8]) {
+        self.ops.push(BatchOp::Remove {
+            hkey,
+            key: Bytes::copy_from_slice(key),
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+}
+
+/// The leaves written while a batch is applied, keyed by the leaf index.
+/// `None` is a removed leaf.
+#[derive(Debug)]
+struct PendingLeaves {
+    next_free_node: u64,
+    leaves: BTreeMap<u64, Option<Vec<u8>>>,
+}
+
 #[derive(Debug)]
 pub struct ZkTrie<D: Database<Node = Node>> {
     state: SpareMerkleTrie,
+    tracer: Option<TraceRecorder>,
+    pending: Option<PendingLeaves>,
     _marker: PhantomData<D>,
 }
 
@@ -34,14 +107,25 @@ impl<D: Database<Node = Node>> ZkTrie<D> {
     pub fn new(root: B256) -> Self {
         ZkTrie {
             state: SpareMerkleTrie::new(root),
+            tracer: None,
+            pending: None,
             _marker: PhantomData,
         }
     }
 
+    /// Records the Shomei trace of every `read`, `put` and `remove` into
+    /// `log`. `location` is the account of a storage trie, and empty for the
+    /// world state.
+    pub fn with_trace_log(mut self, location: Bytes, log: TraceLog) -> Self {
+        self.tracer = Some(TraceRecorder { location, log });
+        self
+    }
+
     pub fn empty(db: &mut D) -> Result<Self, Error> {
         let (empty_node, _) = init_world_state();
         let mut trie = Self::new(*empty_node.hash());
         trie.set_head_and_tail(db)?;
+        db.update_root(*trie.top_root_hash());
         Ok(trie)
     }
 
@@ -70,10 +154,17 @@ impl<D: Database<Node = Node>> ZkTrie<D> {
     }
 
     pub fn next_free_node(&self, db: &D) -> Result<u64, Error> {
+        if let Some(pending) = &self.pending {
+            return Ok(pending.next_free_node);
+        }
         self.state.next_free_node(db)
     }
 
     pub fn increment_next_free_leaf_node_index(&mut self, db: &mut D) -> Result<u64, Error> {
+        if let Some(pending) = &mut self.pending {
+            pending.next_free_node += 1;
+            return Ok(pending.next_free_node);
+        }
         let found_free_node = self.state.next_free_node(db)?;
         let next_free_node = found_free_node + 1;
         self.state
@@ -100,54 +191,154 @@ impl<D: Database<Node = Node>> ZkTrie<D> {
         }
     }
 
-    pub fn remove(&mut self, db: &mut D, hkey: B256, _key: &[u8]) -> Result<(), Error> {
+    fn leaf(&self, db: &D, leaf_index: u64) -> Result<LeafOpening, Error> {
+        let path = utils::get_leaf_path(leaf_index);
+        match self
+            .pending
+            .as_ref()
+            .and_then(|n| n.leaves.get(&leaf_index))
+        {
+            Some(Some(value)) => Ok(LeafOpening::parse(value)),
+            Some(None) => Err(Error::ZKTrieKeyNotFound(Bytes::copy_from_slice(&path))),
+            None => self.parse_node(db, &path, LeafOpening::parse),
+        }
+    }
+
+    fn put_leaf(&mut self, db: &mut D, leaf_index: u64, leaf: &LeafOpening) -> Result<(), Error> {
+        match &mut self.pending {
+            Some(pending) => {
+                pending.leaves.insert(leaf_index, Some(leaf.to_bytes()));
+                Ok(())
+            }
+            None => self
+                .state
+                .put(db, &utils::get_leaf_path(leaf_index), leaf.to_bytes()),
+        }
+    }
+
+    fn remove_leaf(&mut self, db: &mut D, leaf_index: u64) -> Result<(), Error> {
+        match &mut self.pending {
+            Some(pending) => {
+                pending.leaves.insert(leaf_index, None);
+                Ok(())
+            }
+            None => self.state.remove(db, &utils::get_leaf_path(leaf_index)),
+        }
+    }
+
+    fn trace_proof(&self, db: &D, leaf_index: u64) -> Result<TraceProof, Error> {
+        let nodes = self
+            .state
+            .get_proof_nodes(db, &utils::get_leaf_path(leaf_index))?;
+        // the sibling hashes from the leaf level up to the sub root
+        let siblings = nodes[1..nodes.len() - 1]
+            .iter()
+            .rev()
+            .map(|node| *node.hash())
+            .collect();
+        Ok(TraceProof {
+            leaf_index,
+            siblings,
+        })
+    }
+
+    /// Runs `f` only when the traces are recorded.
+    fn traced<T, F>(&self, f: F) -> Result<Option<T>, Error>
+    where
+        F: FnOnce() -> Result<T, Error>,
+    {
+        match &self.tracer {
+            Some(_) => f().map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn record(&self, trace: Trace) {
+        if let Some(tracer) = &self.tracer {
+            tracer.log.lock().unwrap().push(trace);
+        }
+    }
+
+    fn location(&self) -> Bytes {
+        self.tracer
+            .as_ref()
+            .map(|tracer| tracer.location.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn remove(&mut self, db: &mut D, hkey: B256, key: &[u8]) -> Result<(), Error> {
         let nearest_key = db
             .get_nearest_keys(self.top_root_hash(), &hkey)
             .map_err(Error::ZktrieRemove())?;
         match &nearest_key.center {
             Some(current_flat_leaf_value) => {
-                let left_leaf_path = nearest_key.left_path();
-                let right_leaf_path = nearest_key.right_path();
+                let old_state =
+                    self.traced(|| Ok((self.next_free_node(db)?, self.sub_root_hash(db)?)))?;
 
                 // UPDATE HKey- with HKey+ for next
+                let prior_left_leaf = self
+                    .leaf(db, nearest_key.left_index)
+                    .map_err(Error::ZktrieRemove())?;
+                let left_proof = self.traced(|| self.trace_proof(db, nearest_key.left_index))?;
                 {
-                    let prior_left_leaf = self
-                        .parse_node(db, &left_leaf_path, LeafOpening::parse)
-                        .map_err(Error::ZktrieRemove())?;
                     let new_left_leaf =
                         prior_left_leaf.new_next_leaf(nearest_key.right_index.into());
-                    self.state
-                        .put(db, &left_leaf_path, new_left_leaf.to_bytes())
+                    self.put_leaf(db, nearest_key.left_index, &new_left_leaf)
                         .map_err(Error::ZktrieRemove())?;
                 }
 
                 // REMOVE hash(k)
+                let leaf_index_to_delete = current_flat_leaf_value.leaf_index;
+                let deleted = self.traced(|| {
+                    let prior_deleted_leaf = self.leaf(db, leaf_index_to_delete)?;
+                    let deleted_proof = self.trace_proof(db, leaf_index_to_delete)?;
+                    Ok((prior_deleted_leaf, deleted_proof))
+                })?;
                 {
-                    let leaf_path_to_delete =
-                        utils::get_leaf_path(current_flat_leaf_value.leaf_index);
-                    // let prior_deleted_leaf =
-                    //     self.parse_node(db, &leaf_path_to_delete, LeafOpening::parse)?;
                     db.remove_index(&hkey);
 
-                    self.state
-                        .remove(db, &leaf_path_to_delete)
+                    self.remove_leaf(db, leaf_index_to_delete)
                         .map_err(Error::ZktrieRemove())?;
                 }
 
                 // UPDATE HKey+ with HKey- for prev
+                let prior_right_leaf = self
+                    .leaf(db, nearest_key.right_index)
+                    .map_err(Error::ZktrieRemove())?;
+                let right_proof = self.traced(|| self.trace_proof(db, nearest_key.right_index))?;
                 {
-                    let prior_right_leaf = self
-                        .parse_node(db, &right_leaf_path, LeafOpening::parse)
-                        .map_err(Error::ZktrieRemove())?;
                     let new_right_leaf =
                         prior_right_leaf.new_prev_leaf(nearest_key.left_index.into());
-                    self.state
-                        .put(db, &right_leaf_path, new_right_leaf.to_bytes())
+                    self.put_leaf(db, nearest_key.right_index, &new_right_leaf)
                         .map_err(Error::ZktrieRemove())?;
                 }
+
+                if let (
+                    Some((next_free_node, old_sub_root)),
+                    Some(left_proof),
+                    Some((prior_deleted_leaf, deleted_proof)),
+                    Some(right_proof),
+                ) = (old_state, left_proof, deleted, right_proof)
+                {
+                    self.record(Trace::Deletion(DeletionTrace {
+                        location: self.location(),
+                        new_next_free_node: next_free_node,
+                        old_sub_root,
+                        new_sub_root: self.sub_root_hash(db)?,
+                        left_proof,
+                        deleted_proof,
+                        right_proof,
+                        key: Bytes::copy_from_slice(key),
+                        deleted_value: current_flat_leaf_value.leaf_value.clone(),
+                        prior_left_leaf,
+                        prior_deleted_leaf,
+                        prior_right_leaf,
+                    }));
+                }
             }
             None => (),
         }
+        db.update_root(*self.top_root_hash());
         Ok(())
     }
 
@@ -166,42 +357,119 @@ impl<D: Database<Node = Node>> ZkTrie<D> {
             self.state.root_hash(),
             nearest_keys
         );
+        if self.tracer.is_some() {
+            self.record_read(db, key, &nearest_keys)
+                .map_err(Error::ZktrieRead())?;
+        }
         Ok(match nearest_keys.center {
             Some(leaf) => Some(leaf.leaf_value.into()),
             None => None,
         })
     }
 
+    fn record_read(&self, db: &D, key: &[u8], nearest_keys: &KeyRange) -> Result<(), Error> {
+        let location = self.location();
+        let next_free_node = self.next_free_node(db)?;
+        let sub_root = self.sub_root_hash(db)?;
+        let key = Bytes::copy_from_slice(key);
+        let trace = match &nearest_keys.center {
+            Some(leaf) => Trace::Read(ReadTrace {
+                location,
+                next_free_node,
+                sub_root,
+                leaf: self.parse_node(db, &leaf.leaf_path(), LeafOpening::parse)?,
+                proof: self.trace_proof(db, leaf.leaf_index)?,
+                key,
+                value: leaf.leaf_value.clone(),
+            }),
+            None => Trace::ReadZero(ReadZeroTrace {
+                location,
+                next_free_node,
+                sub_root,
+                left_leaf: self.parse_node(db, &nearest_keys.left_path(), LeafOpening::parse)?,
+                right_leaf: self.parse_node(db, &nearest_keys.right_path(), LeafOpening::parse)?,
+                left_proof: self.trace_proof(db, nearest_keys.left_index)?,
+                right_proof: self.trace_proof(db, nearest_keys.right_index)?,
+                key,
+            }),
+        };
+        self.record(trace);
+        Ok(())
+    }
+
+    /// Proves the presence of `hkey`, or its absence with the proofs of the
+    /// two leaves around it, in the format returned by Shomei's
+    /// `rollup_getProof`.
+    pub fn prove(&self, db: &D, hkey: B256, key: &[u8]) -> Result<MerkleProof, Error> {
+        let nearest_keys = db
+            .get_nearest_keys(self.top_root_hash(), &hkey)
+            .map_err(Error::ZktrieProve())?;
+        let mut proof = MerkleProof {
+            key: Bytes::copy_from_slice(key),
+            inclusion: None,
+            non_inclusion: None,
+        };
+        match nearest_keys.center {
+            Some(leaf) => {
+                let nodes = self
+                    .state
+                    .get_proof_nodes(db, &leaf.leaf_path())
+                    .map_err(Error::ZktrieProve())?;
+                proof.inclusion = Some(MerkleInclusionProof {
+                    leaf_index: leaf.leaf_index,
+                    proof: Proof::from_nodes(Some(leaf.leaf_value), &nodes),
+                });
+            }
+            None => {
+                let left_nodes = self
+                    .state
+                    .get_proof_nodes(db, &nearest_keys.left_path())
+                    .map_err(Error::ZktrieProveLeft())?;
+                let right_nodes = self
+                    .state
+                    .get_proof_nodes(db, &nearest_keys.right_path())
+                    .map_err(Error::ZktrieProveRight())?;
+                proof.non_inclusion = Some(MerkleNonInclusionProof {
+                    left_proof: Proof::from_nodes(None, &left_nodes),
+                    right_proof: Proof::from_nodes(None, &right_nodes),
+                    left_leaf_index: nearest_keys.left_index,
+                    right_leaf_index: nearest_keys.right_index,
+                });
+            }
+        }
+        Ok(proof)
+    }
+
     pub fn put(
         &mut self,
         db: &mut D,
         hkey: B256,
-        _key: &[u8],
+        key: &[u8],
         hval: B256,
         value: Vec<u8>,
     ) -> Result<(), Error> {
         let nearest_keys = db
             .get_nearest_keys(self.top_root_hash(), &hkey)
             .map_err(Error::ZktriePut())?;
+        let value = Bytes::from(value);
+        let old_sub_root = self.traced(|| self.sub_root_hash(db))?;
         match nearest_keys.center {
             None => {
-                let left_leaf_path = nearest_keys.left_path();
-                let right_leaf_path = nearest_keys.right_path();
-                let next_free_node = self.state.next_free_node(db).map_err(Error::ZkTriePut())?;
+                let next_free_node = self.next_free_node(db).map_err(Error::ZkTriePut())?;
 
                 // UPDATE HKey- with hash(k) for next
+                let prior_left_leaf = self
+                    .leaf(db, nearest_keys.left_index)
+                    .map_err(Error::ParseLeftNode())?;
+                let left_proof = self.traced(|| self.trace_proof(db, nearest_keys.left_index))?;
+                let new_proof;
                 {
-                    let prior_left_leaf = self
-                        .parse_node(db, &left_leaf_path, LeafOpening::parse)
-                        .map_err(Error::ParseLeftNode())?;
                     let new_left_leaf = prior_left_leaf.new_next_leaf(next_free_node.into());
 
-                    self.state
-                        .put(db, &left_leaf_path, new_left_leaf.to_bytes())
+                    self.put_leaf(db, nearest_keys.left_index, &new_left_leaf)
                         .map_err(Error::PutLeftLeaf())?;
 
-                    let leaf_path_to_add = utils::get_leaf_path(next_free_node);
-                    db.update_index(hkey, FlattenedLeaf::new(next_free_node, value.into()));
+                    db.update_index(hkey, FlattenedLeaf::new(next_free_node, value.clone()));
 
                     let new_leaf_value = LeafOpening::new(
                         nearest_keys.left_index,
@@ -210,42 +478,123 @@ impl<D: Database<Node = Node>> ZkTrie<D> {
                         hval,
                     );
 
-                    self.state
-                        .put(db, &leaf_path_to_add, new_leaf_value.to_bytes())
+                    new_proof = self.traced(|| self.trace_proof(db, next_free_node))?;
+                    self.put_leaf(db, next_free_node, &new_leaf_value)
                         .map_err(Error::PutCenterLeaf())?;
                 }
 
                 // UPDATE HKey+ with hash(k) for prev
+                let prior_right_leaf = self
+                    .leaf(db, nearest_keys.right_index)
+                    .map_err(Error::ParseRightNode())?;
+                let right_proof = self.traced(|| self.trace_proof(db, nearest_keys.right_index))?;
                 {
-                    let prior_right_leaf = self
-                        .parse_node(db, &right_leaf_path, LeafOpening::parse)
-                        .map_err(Error::ParseRightNode())?;
                     let new_right_leaf = prior_right_leaf.new_prev_leaf(next_free_node.into());
-                    self.state
-                        .put(db, &right_leaf_path, new_right_leaf.to_bytes())
+                    self.put_leaf(db, nearest_keys.right_index, &new_right_leaf)
                         .map_err(Error::PutRightLeaf())?;
                 }
 
-                self.increment_next_free_leaf_node_index(db)?;
+                let new_next_free_node = self.increment_next_free_leaf_node_index(db)?;
+
+                if let (Some(old_sub_root), Some(left_proof), Some(new_proof), Some(right_proof)) =
+                    (old_sub_root, left_proof, new_proof, right_proof)
+                {
+                    self.record(Trace::Insertion(InsertionTrace {
+                        location: self.location(),
+                        new_next_free_node,
+                        old_sub_root,
+                        new_sub_root: self.sub_root_hash(db)?,
+                        left_proof,
+                        new_proof,
+                        right_proof,
+                        key: Bytes::copy_from_slice(key),
+                        value,
+                        prior_left_leaf,
+                        prior_right_leaf,
+                    }));
+                }
             }
             Some(current_flat_leaf_value) => {
-                let leaf_path_to_update = current_flat_leaf_value.leaf_path();
+                let leaf_index_to_update = current_flat_leaf_value.leaf_index;
+                let proof =
+                    self.traced(|| self.trace_proof(db, current_flat_leaf_value.leaf_index))?;
                 db.update_index(
                     hkey,
-                    FlattenedLeaf::new(current_flat_leaf_value.leaf_index, value.into()),
+                    FlattenedLeaf::new(current_flat_leaf_value.leaf_index, value.clone()),
                 );
 
-                let prior_updated_leaf =
-                    self.parse_node(db, &leaf_path_to_update, LeafOpening::parse)?;
+                let prior_updated_leaf = self.leaf(db, leaf_index_to_update)?;
 
                 let new_updated_leaf = prior_updated_leaf.new_hval(hval);
 
-                self.state
-                    .put(db, &leaf_path_to_update, new_updated_leaf.to_bytes())?;
+                self.put_leaf(db, leaf_index_to_update, &new_updated_leaf)?;
+
+                if let (Some(old_sub_root), Some(proof)) = (old_sub_root, proof) {
+                    self.record(Trace::Update(UpdateTrace {
+                        location: self.location(),
+                        new_next_free_node: self.next_free_node(db)?,
+                        old_sub_root,
+                        new_sub_root: self.sub_root_hash(db)?,
+                        proof,
+                        key: Bytes::copy_from_slice(key),
+                        old_value: current_flat_leaf_value.leaf_value,
+                        new_value: value,
+                        prior_updated_leaf,
+                    }));
+                }
             }
         }
+        db.update_root(*self.top_root_hash());
         Ok(())
     }
+
+    /// Applies the writes of `batch` in order, with the same result as
+    /// calling `put` and `remove` one by one.
+    ///
+    /// The leaves are staged and the nodes above them are hashed once at the
+    /// end. The writes go through one by one when the traces are recorded,
+    /// or when `db` has no full index: a witness store, like the one built
+    /// by `MemStore::from_traces`, only resolves the key ranges at the roots
+    /// of its traces, which are never reached while the leaves are staged.
+    /// So the verifier replays its blocks at the cost of `put`, see the
+    /// `witness apply_batch` bench.
+    pub fn apply_batch(&mut self, db: &mut D, batch: WriteBatch) -> Result<(), Error> {
+        let result = batch.ops.into_iter().try_for_each(|op| {
+            if self.pending.is_none() && self.can_defer(db) {
+                self.pending = Some(PendingLeaves {
+                    next_free_node: self.state.next_free_node(db)?,
+                    leaves: BTreeMap::new(),
+                });
+            }
+            match op {
+                BatchOp::Put {
+                    hkey,
+                    key,
+                    hval,
+                    value,
+                } => self.put(db, hkey, &key, hval, value),
+                BatchOp::Remove { hkey, key } => self.remove(db, hkey, &key),
+            }
+        });
+        let pending = self.pending.take();
+        result?;
+        if let Some(pending) = pending {
+            let leaves = pending
+                .leaves
+                .into_iter()
+                .map(|(leaf_index, value)| (utils::get_leaf_path(leaf_index), value))
+                .collect::<Vec<_>>();
+            self.state.put_leaves(db, pending.next_free_node, &leaves)?;
+            db.update_root(*self.top_root_hash());
+        }
+        Ok(())
+    }
+
+    fn can_defer(&self, db: &D) -> bool {
+        // the stores resolve the empty root without their index, the first
+        // write into an empty trie goes through
+        self.tracer.is_none() && db.full_index() && self.top_root_hash() != &*EMPTY_TRIE_NODE_HASH
+    }
 }
 
 #[cfg(test)]
@@ -532,6 +881,64 @@ mod test {
         );
     }
 
+    fn batch_ops(round: u32) -> WriteBatch {
+        let mut batch = WriteBatch::new();
+        for n in 1..40 {
+            let key = utils::create_dum_digest(n).0;
+            let hkey = trie_hash(&key).unwrap();
+            if (n + round) % 5 == 0 {
+                batch.remove(hkey, &key);
+            } else {
+                let value = utils::create_dum_digest(n * 100 + round).0.to_vec();
+                batch.put(hkey, &key, trie_hash(&value).unwrap(), value);
+            }
+        }
+        batch
+    }
+
+    #[test]
+    fn test_apply_batch() {
+        let mut seq_db = PrefixDB::new(
+            Address::default(),
+            Arc::new(Mutex::new(MemStore::new_full())),
+        );
+        let mut seq_trie = MemZkTrie::new(*EMPTY_TRIE_NODE_HASH);
+        let mut db = PrefixDB::new(
+            Address::default(),
+            Arc::new(Mutex::new(MemStore::new_full())),
+        );
+        let mut trie = MemZkTrie::new(*EMPTY_TRIE_NODE_HASH);
+        for round in 0..3 {
+            let batch = batch_ops(round);
+            for op in batch.ops.clone() {
+                match op {
+                    BatchOp::Put {
+                        hkey,
+                        key,
+                        hval,
+                        value,
+                    } => seq_trie.put(&mut seq_db, hkey, &key, hval, value).unwrap(),
+                    BatchOp::Remove { hkey, key } => {
+                        seq_trie.remove(&mut seq_db, hkey, &key).unwrap()
+                    }
+                }
+            }
+            trie.apply_batch(&mut db, batch).unwrap();
+            assert_eq!(trie.top_root_hash(), seq_trie.top_root_hash());
+        }
+
+        // the traced writes go through one by one
+        let log = TraceLog::default();
+        let mut traced =
+            MemZkTrie::new(*seq_trie.top_root_hash()).with_trace_log(Bytes::new(), log.clone());
+        let batch = batch_ops(3);
+        let len = batch.len();
+        traced.apply_batch(&mut seq_db, batch.clone()).unwrap();
+        trie.apply_batch(&mut db, batch).unwrap();
+        assert_eq!(traced.top_root_hash(), trie.top_root_hash());
+        assert_eq!(log.lock().unwrap().len(), len);
+    }
+
     // #[test]
     // fn performance() {
     //     let prefix = Address::default();
@@ -60,7 +60,11 @@ pub fn get_leaf_path(idx: u64) -> [u8; ZK_TRIE_DEPTH + 2] {
 
 #[cfg(test)]
 mod test {
-    use alloy::primitives::B256;
+    use std::sync::{Arc, Mutex};
+
+    use alloy::primitives::{Address, B256};
+
+    use crate::{trie_hash, Database, MemStore, MemZkTrie, Node, PrefixDB, ZkTrie};
 
     pub fn create_dum_digest(n: u32) -> B256 {
         let mut val = B256::default();
@@ -68,6 +72,37 @@ mod test {
         val.0[28..].copy_from_slice(&n);
         val
     }
+
+    pub fn dum_key(n: u32) -> [u8; 32] {
+        create_dum_digest(n).0
+    }
+
+    pub fn dum_hkey(n: u32) -> B256 {
+        trie_hash(&dum_key(n)).unwrap()
+    }
+
+    pub fn dum_value(n: u32) -> Vec<u8> {
+        create_dum_digest(n).0.to_vec()
+    }
+
+    /// Puts `dum_value(v)` under `dum_key(n)`.
+    pub fn dum_put<D: Database<Node = Node>>(trie: &mut ZkTrie<D>, db: &mut D, n: u32, v: u32) {
+        let hval = trie_hash(&dum_value(v)).unwrap();
+        trie.put(db, dum_hkey(n), &dum_key(n), hval, dum_value(v))
+            .unwrap();
+    }
+
+    /// A world state holding `dum_value(n * 10)` under `dum_key(n)` for each
+    /// of `keys`, in a store with the full index.
+    pub fn dum_trie(keys: impl IntoIterator<Item = u32>) -> (PrefixDB, MemZkTrie) {
+        let store = Arc::new(Mutex::new(MemStore::new_full()));
+        let mut db = PrefixDB::new(Address::default(), store);
+        let mut trie = MemZkTrie::empty(&mut db).unwrap();
+        for n in keys {
+            dum_put(&mut trie, &mut db, n, n * 10);
+        }
+        (db, trie)
+    }
 }
 
 #[cfg(test)]
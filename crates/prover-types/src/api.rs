@@ -1,14 +1,16 @@
-use alloy::primitives::{Bytes, B256};
-use base::prover::keccak_encode;
+use alloy::primitives::{Address, Bytes, Signature, B256};
 use serde::{Deserialize, Serialize};
 
-use crate::{Poe, TaskType};
+use crate::{keccak_encode, Pob, Poe, TaskType};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct ProveTaskParams {
     pub batch: Option<Bytes>,
     pub pob_hash: B256,
     pub task_type: Option<u64>,
+    /// Selects the chain when the prover serves several networks of the
+    /// same task type, defaults to the chain id of the PoB list.
+    pub chain_id: Option<u64>,
     pub start: Option<u64>,
     pub end: Option<u64>,
     pub starting_state_root: Option<B256>,
@@ -29,14 +31,98 @@ impl ProveTaskParams {
             .ok_or(ParamsError::MissingBatch)
             .map(|n| &n[..])
     }
+
+    pub fn block_range(&self) -> Result<(u64, u64), ParamsError> {
+        match (self.start, self.end) {
+            (Some(start), Some(end)) => Ok((start, end)),
+            _ => Err(ParamsError::MissingBlockRange),
+        }
+    }
+
+    /// Check that `pob_list` covers exactly `start..=end` in order, that each
+    /// block starts from the state root its predecessor ended with, and that
+    /// the list agrees with `starting_state_root` and `final_state_root`.
+    ///
+    /// `post_state_root` returns the root a block ends with, which is chain
+    /// specific (e.g. linea reports its zktrie root outside of the header).
+    pub fn check_pob_list<F>(
+        &self,
+        pob_list: &[Pob],
+        (start, end): (u64, u64),
+        post_state_root: F,
+    ) -> Result<(), ParamsError>
+    where
+        F: Fn(&Pob) -> B256,
+    {
+        let want = (self.start.unwrap_or(start), self.end.unwrap_or(end));
+        if want != (start, end) {
+            return Err(ParamsError::BlockRangeMismatch {
+                want,
+                got: (start, end),
+            });
+        }
+
+        let (first, last) = match (pob_list.first(), pob_list.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Err(ParamsError::EmptyPobList),
+        };
+        for (idx, pob) in pob_list.iter().enumerate() {
+            let expect = start + idx as u64;
+            let got: u64 = pob.block.number.to();
+            if got != expect {
+                return Err(ParamsError::NonContiguousBlock { expect, got });
+            }
+        }
+        let got = (first.block.number.to(), last.block.number.to());
+        if got != (start, end) {
+            return Err(ParamsError::BlockRangeMismatch {
+                want: (start, end),
+                got,
+            });
+        }
+
+        for pair in pob_list.windows(2) {
+            let expect = post_state_root(&pair[0]);
+            if pair[1].data.prev_state_root != expect {
+                return Err(ParamsError::PrevStateRootMismatch {
+                    number: pair[1].block.number.to(),
+                    expect,
+                    got: pair[1].data.prev_state_root,
+                });
+            }
+        }
+
+        if let Some(expect) = self.starting_state_root {
+            if first.data.prev_state_root != expect {
+                return Err(ParamsError::StartingStateRootMismatch {
+                    expect,
+                    got: first.data.prev_state_root,
+                });
+            }
+        }
+        if let Some(expect) = self.final_state_root {
+            let got = post_state_root(last);
+            if got != expect {
+                return Err(ParamsError::FinalStateRootMismatch { expect, got });
+            }
+        }
+        Ok(())
+    }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum ParamsError {
     MissingBatch,
+    MissingBlockRange,
+    EmptyPobList,
+    BlockRangeMismatch { want: (u64, u64), got: (u64, u64) },
+    NonContiguousBlock { expect: u64, got: u64 },
+    PrevStateRootMismatch { number: u64, expect: B256, got: B256 },
+    StartingStateRootMismatch { expect: B256, got: B256 },
+    FinalStateRootMismatch { expect: B256, got: B256 },
 }
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct PoeResponse {
     pub not_ready: bool,
     pub batch_id: u64,
@@ -46,6 +132,71 @@ pub struct PoeResponse {
     pub poe_signature: Option<Bytes>,
 }
 
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AggregatedPoeResponse {
+    pub batch_ids: Vec<u64>,
+    pub batch_hashes: Vec<B256>,
+    pub start_block: u64,
+    pub end_block: u64,
+    pub poe: Poe,
+    pub poe_signature: Bytes,
+}
+
+#[derive(Debug)]
+pub enum PoeError {
+    MissingPoe,
+    MissingSignature,
+    InvalidSignature(String),
+    RecoverFailed(String),
+    SignerMismatch { expected: Address, got: Address },
+}
+
+fn recover_signer(digest: &B256, sig: &[u8]) -> Result<Address, PoeError> {
+    let sig = Signature::try_from(sig)
+        .map_err(|err| PoeError::InvalidSignature(format!("{:?}", err)))?;
+    sig.recover_address_from_prehash(digest)
+        .map_err(|err| PoeError::RecoverFailed(format!("{:?}", err)))
+}
+
+fn check_signer(signer: Address, expected_signer: Address) -> Result<(), PoeError> {
+    if signer != expected_signer {
+        return Err(PoeError::SignerMismatch {
+            expected: expected_signer,
+            got: signer,
+        });
+    }
+    Ok(())
+}
+
+impl PoeResponse {
+    /// Recover the address which signed `poe_digest(poe)`.
+    pub fn recover_signer(&self) -> Result<Address, PoeError> {
+        let poe = self.poe.as_ref().ok_or(PoeError::MissingPoe)?;
+        let sig = self
+            .poe_signature
+            .as_ref()
+            .ok_or(PoeError::MissingSignature)?;
+        recover_signer(&poe_digest(poe), sig)
+    }
+
+    pub fn verify(&self, expected_signer: Address) -> Result<(), PoeError> {
+        check_signer(self.recover_signer()?, expected_signer)
+    }
+}
+
+impl AggregatedPoeResponse {
+    /// Recover the address which signed `aggregated_poe_digest` of the report
+    /// and its block range.
+    pub fn recover_signer(&self) -> Result<Address, PoeError> {
+        let digest = aggregated_poe_digest(&self.poe, self.start_block, self.end_block);
+        recover_signer(&digest, &self.poe_signature)
+    }
+
+    pub fn verify(&self, expected_signer: Address) -> Result<(), PoeError> {
+        check_signer(self.recover_signer()?, expected_signer)
+    }
+}
+
 pub fn poe_digest(poe: &Poe) -> B256 {
     keccak_encode(|hash| {
         hash(poe.batch_hash.as_slice());
@@ -54,4 +205,237 @@ pub fn poe_digest(poe: &Poe) -> B256 {
         hash(poe.new_state_root.as_slice());
         hash(poe.withdrawal_root.as_slice());
     })
-}
\ No newline at end of file
+}
+
+/// Prefixed to the digest of an aggregated report, so its signature can't
+/// pass for the one of a single batch whose hash is the aggregated
+/// `batch_hash`.
+pub const AGGREGATED_POE_DOMAIN: &[u8] = b"automata/aggregated-poe/v1";
+
+/// Binds the block range as well, the batch hashes of the Linea reports are
+/// left empty.
+pub fn aggregated_poe_digest(poe: &Poe, start_block: u64, end_block: u64) -> B256 {
+    keccak_encode(|hash| {
+        hash(AGGREGATED_POE_DOMAIN);
+        hash(poe_digest(poe).as_slice());
+        hash(&start_block.to_be_bytes());
+        hash(&end_block.to_be_bytes());
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use alloy::primitives::U64;
+    use base::eth::Keypair;
+
+    use super::*;
+    use crate::{ChainData, PobBlock, PobData, ScrollData, POB_DATA_VERSION};
+
+    fn params(start: Option<u64>, end: Option<u64>) -> ProveTaskParams {
+        ProveTaskParams {
+            batch: None,
+            pob_hash: B256::default(),
+            task_type: None,
+            chain_id: None,
+            start,
+            end,
+            starting_state_root: None,
+            final_state_root: None,
+            from: None,
+        }
+    }
+
+    // block `n` moves the state from root `n` to root `n + 1`
+    fn pob(n: u64) -> Pob {
+        let block = PobBlock {
+            number: U64::from(n),
+            state_root: B256::with_last_byte(n as u8 + 1),
+            ..Default::default()
+        };
+        let data = PobData {
+            version: POB_DATA_VERSION,
+            chain_id: 534352,
+            coinbase: None,
+            prev_state_root: B256::with_last_byte(n as u8),
+            block_hashes: Default::default(),
+            mpt_nodes: vec![],
+            codes: vec![],
+            chain: ChainData::Scroll(ScrollData::default()),
+        };
+        Pob::new(block, data)
+    }
+
+    fn check(params: &ProveTaskParams, pob_list: &[Pob]) -> Result<(), ParamsError> {
+        params.check_pob_list(pob_list, (10, 12), |pob| pob.block.state_root)
+    }
+
+    #[test]
+    fn test_check_pob_list() {
+        let pob_list = (10..=12).map(pob).collect::<Vec<_>>();
+        check(&params(None, None), &pob_list).unwrap();
+        check(&params(Some(10), Some(12)), &pob_list).unwrap();
+
+        let mut params = params(Some(10), Some(12));
+        params.starting_state_root = Some(B256::with_last_byte(10));
+        params.final_state_root = Some(B256::with_last_byte(13));
+        check(&params, &pob_list).unwrap();
+    }
+
+    #[test]
+    fn test_check_pob_list_rejected() {
+        let pob_list = (10..=12).map(pob).collect::<Vec<_>>();
+
+        let err = check(&params(Some(10), Some(11)), &pob_list);
+        assert!(matches!(
+            err,
+            Err(ParamsError::BlockRangeMismatch {
+                want: (10, 11),
+                got: (10, 12)
+            })
+        ));
+        assert!(matches!(
+            check(&params(None, None), &[]),
+            Err(ParamsError::EmptyPobList)
+        ));
+        // a block is missing
+        let gap = vec![pob(10), pob(12)];
+        assert!(matches!(
+            check(&params(None, None), &gap),
+            Err(ParamsError::NonContiguousBlock {
+                expect: 11,
+                got: 12
+            })
+        ));
+        // the list stops before the end of the range
+        assert!(matches!(
+            check(&params(None, None), &pob_list[..2]),
+            Err(ParamsError::BlockRangeMismatch {
+                want: (10, 12),
+                got: (10, 11)
+            })
+        ));
+
+        let mut forked = pob_list.clone();
+        forked[2].data.prev_state_root = B256::repeat_byte(0xff);
+        assert!(matches!(
+            check(&params(None, None), &forked),
+            Err(ParamsError::PrevStateRootMismatch { number: 12, .. })
+        ));
+
+        let mut params = params(None, None);
+        params.starting_state_root = Some(B256::with_last_byte(9));
+        assert!(matches!(
+            check(&params, &pob_list),
+            Err(ParamsError::StartingStateRootMismatch { .. })
+        ));
+        params.starting_state_root = None;
+        params.final_state_root = Some(B256::with_last_byte(12));
+        assert!(matches!(
+            check(&params, &pob_list),
+            Err(ParamsError::FinalStateRootMismatch { .. })
+        ));
+    }
+
+    fn signed_response(keypair: &Keypair) -> PoeResponse {
+        let poe = Poe {
+            batch_hash: B256::repeat_byte(1),
+            state_hash: B256::repeat_byte(2),
+            prev_state_root: B256::repeat_byte(3),
+            new_state_root: B256::repeat_byte(4),
+            ..Default::default()
+        };
+        let sig = Keypair::sign_digest_ecdsa(&keypair.secret_key(), poe_digest(&poe).into());
+        PoeResponse {
+            not_ready: false,
+            batch_id: 1,
+            start_block: 10,
+            end_block: 12,
+            poe: Some(poe),
+            poe_signature: Some(sig.to_vec().into()),
+        }
+    }
+
+    #[test]
+    fn test_poe_signature_round_trip() {
+        let keypair = Keypair::new();
+        let resp = signed_response(&keypair);
+        let sig = resp.poe_signature.clone().unwrap();
+        assert_eq!(sig.len(), 65);
+        // the signature is checked with ecrecover on L1, which takes v = 27 + recovery id
+        assert!(matches!(sig[64], 27 | 28), "v = {}", sig[64]);
+        assert_eq!(resp.recover_signer().unwrap(), keypair.address());
+        resp.verify(keypair.address()).unwrap();
+    }
+
+    #[test]
+    fn test_poe_signature_rejected() {
+        let keypair = Keypair::new();
+        let resp = signed_response(&keypair);
+
+        let other = Keypair::new();
+        assert!(matches!(
+            resp.verify(other.address()),
+            Err(PoeError::SignerMismatch { expected, got })
+                if expected == other.address() && got == keypair.address()
+        ));
+
+        // the signature doesn't cover another report
+        let mut tampered = resp.clone();
+        tampered.poe.as_mut().unwrap().new_state_root = B256::repeat_byte(5);
+        assert!(matches!(
+            tampered.verify(keypair.address()),
+            Err(PoeError::SignerMismatch { .. }) | Err(PoeError::RecoverFailed(_))
+        ));
+
+        let mut truncated = resp.clone();
+        truncated.poe_signature = Some(Bytes::copy_from_slice(&[0; 64]));
+        assert!(matches!(
+            truncated.recover_signer(),
+            Err(PoeError::InvalidSignature(_))
+        ));
+
+        let mut unsigned = resp.clone();
+        unsigned.poe_signature = None;
+        assert!(matches!(
+            unsigned.recover_signer(),
+            Err(PoeError::MissingSignature)
+        ));
+
+        let mut empty = resp;
+        empty.poe = None;
+        assert!(matches!(empty.recover_signer(), Err(PoeError::MissingPoe)));
+    }
+
+    #[test]
+    fn test_aggregated_poe_signature() {
+        let keypair = Keypair::new();
+        let single = signed_response(&keypair);
+        let poe = single.poe.clone().unwrap();
+        let digest = aggregated_poe_digest(&poe, 10, 20);
+        assert_ne!(digest, poe_digest(&poe));
+
+        let sig = Keypair::sign_digest_ecdsa(&keypair.secret_key(), digest.into());
+        let aggregated = AggregatedPoeResponse {
+            batch_ids: vec![1, 2],
+            batch_hashes: vec![B256::repeat_byte(8), B256::repeat_byte(9)],
+            start_block: 10,
+            end_block: 20,
+            poe: poe.clone(),
+            poe_signature: sig.to_vec().into(),
+        };
+        aggregated.verify(keypair.address()).unwrap();
+
+        // the block range is signed
+        let mut other_range = aggregated.clone();
+        other_range.end_block = 21;
+        assert!(other_range.verify(keypair.address()).is_err());
+
+        // the signatures of a single and an aggregated report aren't interchangeable
+        let mut as_single = single.clone();
+        as_single.poe_signature = Some(aggregated.poe_signature.clone());
+        assert!(as_single.verify(keypair.address()).is_err());
+        let mut as_aggregated = aggregated;
+        as_aggregated.poe_signature = single.poe_signature.unwrap();
+        assert!(as_aggregated.verify(keypair.address()).is_err());
+    }
+}
@@ -0,0 +1,168 @@
+//! The PoB types of `base`, which the Scroll verifier linked from
+//! scroll-prover is built on. The prover converts from and to them when it
+//! calls into that verifier, everything else uses the types of this crate.
+
+pub use base::prover::{
+    Pob as BasePob, PobBlock as BasePobBlock, PobData as BasePobData, Poe as BasePoe,
+};
+
+use crate::{ChainData, LineaData, Pob, PobBlock, PobData, Poe, ScrollData, POB_DATA_VERSION};
+
+impl From<&PobBlock> for BasePobBlock {
+    fn from(block: &PobBlock) -> Self {
+        let block = block.clone();
+        BasePobBlock {
+            miner: block.miner,
+            state_root: block.state_root,
+            difficulty: block.difficulty,
+            number: block.number,
+            gas_limit: block.gas_limit,
+            timestamp: block.timestamp,
+            extra_data: block.extra_data,
+            parent_hash: block.parent_hash,
+            uncles_hash: block.uncles_hash,
+            transactions_root: block.transactions_root,
+            receipts_root: block.receipts_root,
+            logs_bloom: block.logs_bloom,
+            nonce: block.nonce,
+            gas_used: block.gas_used,
+            mix_hash: block.mix_hash,
+            base_fee_per_gas: block.base_fee_per_gas,
+            block_hash: block.block_hash,
+            transactions: block.transactions,
+        }
+    }
+}
+
+impl From<BasePobBlock> for PobBlock {
+    fn from(block: BasePobBlock) -> Self {
+        PobBlock {
+            miner: block.miner,
+            state_root: block.state_root,
+            difficulty: block.difficulty,
+            number: block.number,
+            gas_limit: block.gas_limit,
+            timestamp: block.timestamp,
+            extra_data: block.extra_data,
+            parent_hash: block.parent_hash,
+            uncles_hash: block.uncles_hash,
+            transactions_root: block.transactions_root,
+            receipts_root: block.receipts_root,
+            logs_bloom: block.logs_bloom,
+            nonce: block.nonce,
+            gas_used: block.gas_used,
+            mix_hash: block.mix_hash,
+            base_fee_per_gas: block.base_fee_per_gas,
+            block_hash: block.block_hash,
+            transactions: block.transactions,
+        }
+    }
+}
+
+/// The hash is recomputed with the rule of `base`.
+impl From<&Pob> for BasePob {
+    fn from(pob: &Pob) -> Self {
+        let scroll = pob.data.scroll().cloned().unwrap_or_default();
+        let linea = pob.data.linea().cloned().unwrap_or_default();
+        let data = BasePobData {
+            chain_id: pob.data.chain_id,
+            coinbase: pob.data.coinbase,
+            prev_state_root: pob.data.prev_state_root,
+            block_hashes: pob.data.block_hashes.clone(),
+            mpt_nodes: pob.data.mpt_nodes.clone(),
+            codes: pob.data.codes.clone(),
+            start_l1_queue_index: scroll.start_l1_queue_index,
+            withdrawal_root: scroll.withdrawal_root,
+            linea_traces: linea.traces,
+            linea_proofs: linea.proofs,
+            linea_zkroot: linea.zkroot,
+        };
+        BasePob::new((&pob.block).into(), data)
+    }
+}
+
+/// The flat layout is split like a version 0 input, the PoB is rehashed as
+/// the current version.
+impl From<BasePob> for Pob {
+    fn from(pob: BasePob) -> Self {
+        let data = pob.data;
+        // every Linea PoB carries the zktrie root, the Scroll ones don't
+        let chain = if !data.linea_zkroot.is_zero() {
+            ChainData::Linea(LineaData {
+                traces: data.linea_traces,
+                proofs: data.linea_proofs,
+                zkroot: data.linea_zkroot,
+            })
+        } else {
+            ChainData::Scroll(ScrollData {
+                start_l1_queue_index: data.start_l1_queue_index,
+                withdrawal_root: data.withdrawal_root,
+            })
+        };
+        let data = PobData {
+            version: POB_DATA_VERSION,
+            chain_id: data.chain_id,
+            coinbase: data.coinbase,
+            prev_state_root: data.prev_state_root,
+            block_hashes: data.block_hashes,
+            mpt_nodes: data.mpt_nodes,
+            codes: data.codes,
+            chain,
+        };
+        Pob::new(pob.block.into(), data)
+    }
+}
+
+impl From<BasePoe> for Poe {
+    fn from(poe: BasePoe) -> Self {
+        Poe {
+            batch_hash: poe.batch_hash,
+            state_hash: poe.state_hash,
+            prev_state_root: poe.prev_state_root,
+            new_state_root: poe.new_state_root,
+            withdrawal_root: poe.withdrawal_root,
+            signature: poe.signature,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use alloy::primitives::{Bytes, B256, U64};
+
+    use super::*;
+
+    #[test]
+    fn test_base_pob_round_trip() {
+        let block = PobBlock {
+            number: U64::from(7),
+            block_hash: Some(B256::repeat_byte(7)),
+            transactions: vec![Bytes::from_static(&[0x02, 0xc0])],
+            ..Default::default()
+        };
+        let data = PobData {
+            version: POB_DATA_VERSION,
+            chain_id: 534352,
+            coinbase: None,
+            prev_state_root: B256::repeat_byte(1),
+            block_hashes: BTreeMap::from([(6, B256::repeat_byte(6))]),
+            mpt_nodes: vec![vec![2; 40].into(), vec![1; 40].into()],
+            codes: vec![vec![0x60, 0x80].into()],
+            chain: ChainData::Scroll(ScrollData {
+                start_l1_queue_index: 3,
+                withdrawal_root: B256::repeat_byte(4),
+            }),
+        };
+        let pob = Pob::new(block, data);
+
+        let base = BasePob::from(&pob);
+        assert_eq!(base.data.withdrawal_root, B256::repeat_byte(4));
+        assert!(base.data.linea_zkroot.is_zero());
+        let back = Pob::from(base);
+        assert_eq!(back.block, pob.block);
+        assert_eq!(back.hash, pob.hash);
+        assert_eq!(back.data.scroll().unwrap().start_l1_queue_index, 3);
+    }
+}
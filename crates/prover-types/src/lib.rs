@@ -13,4 +13,17 @@ pub use api::*;
 mod log;
 pub use log::*;
 
-pub use base::prover::{Pob, PobBlock, PobData, Poe, SuccinctPobList, keccak_encode};
\ No newline at end of file
+mod pob;
+pub use pob::*;
+
+mod poe;
+pub use poe::*;
+
+mod compat;
+pub use compat::*;
+
+mod trie;
+pub use trie::*;
+
+mod wire;
+pub use wire::*;
@@ -1,9 +1,15 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::Infallible;
+use std::time::{Duration, Instant};
 
-use alloy::primitives::{Address, Bloom, Bytes, Keccak256, B256, B64, U256, U64};
+use alloy::primitives::{keccak256, Address, Bloom, Bytes, Keccak256, B256, B64, U256, U64};
+use alloy::rlp::{encode_list, Encodable, Header};
 use serde::{Deserialize, Serialize};
 
-use crate::{StringInterning, StringInterningReader};
+use crate::{
+    ordered_trie_root, InterningCodec, InterningError, StringInterning, StringInterningReader,
+    TaskType,
+};
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Pob<T: Default = Bytes> {
@@ -49,6 +55,154 @@ pub struct PobBlock {
     pub transactions: Vec<Bytes>,
 }
 
+impl PobBlock {
+    /// RLP encoded header with the given `extra_data`. Scroll and Linea share
+    /// the Ethereum layout; the clique seal hash uses the same encoding with
+    /// the signature stripped from `extra_data`.
+    pub fn header_rlp(&self, extra_data: &Bytes) -> Vec<u8> {
+        let mut enc: Vec<&dyn Encodable> = vec![
+            &self.parent_hash,
+            &self.uncles_hash,
+            &self.miner,
+            &self.state_root,
+            &self.transactions_root,
+            &self.receipts_root,
+            &self.logs_bloom,
+            &self.difficulty,
+            &self.number,
+            &self.gas_limit,
+            &self.gas_used,
+            &self.timestamp,
+            extra_data,
+            &self.mix_hash,
+            &self.nonce,
+        ];
+        // BaseFee was added by EIP-1559 and is ignored in legacy headers.
+        if let Some(base_fee) = &self.base_fee_per_gas {
+            enc.push(base_fee);
+        }
+        let mut buf = Vec::new();
+        encode_list::<_, dyn Encodable>(&enc, &mut buf);
+        buf
+    }
+
+    pub fn header_hash(&self) -> B256 {
+        keccak256(self.header_rlp(&self.extra_data))
+    }
+
+    /// The transactions are stored either in the EIP-2718 encoding or
+    /// wrapped in an RLP string (the network encoding), the trie is always
+    /// built from the EIP-2718 encoding.
+    pub fn compute_transactions_root(&self) -> Result<B256, HeaderError> {
+        let mut txs = Vec::with_capacity(self.transactions.len());
+        for (idx, tx) in self.transactions.iter().enumerate() {
+            txs.push(tx_envelope(tx).ok_or(HeaderError::InvalidTransaction { idx })?);
+        }
+        Ok(ordered_trie_root(&txs))
+    }
+
+    /// Checks the header fields against `block_hash` and the transactions
+    /// against `transactions_root`.
+    pub fn verify(&self) -> Result<(), HeaderError> {
+        let number = self.number.to();
+        let expect = self.block_hash.ok_or(HeaderError::MissingBlockHash { number })?;
+        let got = self.header_hash();
+        if got != expect {
+            return Err(HeaderError::BlockHashMismatch { number, expect, got });
+        }
+        let got = self.compute_transactions_root()?;
+        if got != self.transactions_root {
+            return Err(HeaderError::TransactionsRootMismatch {
+                number,
+                expect: self.transactions_root,
+                got,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum HeaderError {
+    MissingBlockHash {
+        number: u64,
+    },
+    InvalidTransaction {
+        idx: usize,
+    },
+    BlockHashMismatch {
+        number: u64,
+        expect: B256,
+        got: B256,
+    },
+    TransactionsRootMismatch {
+        number: u64,
+        expect: B256,
+        got: B256,
+    },
+    ParentHashMismatch {
+        number: u64,
+        expect: B256,
+        got: B256,
+    },
+}
+
+/// EIP-2718 envelope of a transaction as stored in `PobBlock::transactions`.
+/// Typed transactions may come in their network encoding, wrapped in an RLP
+/// string header, which is stripped here.
+pub fn tx_envelope(tx: &[u8]) -> Option<&[u8]> {
+    match tx.first()? {
+        0x80..=0xbf => {
+            let mut buf = tx;
+            let header = Header::decode(&mut buf).ok()?;
+            if header.payload_length != buf.len() || buf.is_empty() {
+                return None;
+            }
+            Some(buf)
+        }
+        _ => Some(tx),
+    }
+}
+
+/// Verifies every header of the list and that each block links to the
+/// previous one, so the execution can trust the block fields of the PoBs.
+pub fn verify_block_headers(pob_list: &[Pob]) -> Result<(), HeaderError> {
+    let mut parent: Option<B256> = None;
+    for pob in pob_list {
+        pob.block.verify()?;
+        if let Some(expect) = parent {
+            if pob.block.parent_hash != expect {
+                return Err(HeaderError::ParentHashMismatch {
+                    number: pob.block.number.to(),
+                    expect,
+                    got: pob.block.parent_hash,
+                });
+            }
+        }
+        parent = pob.block.block_hash;
+    }
+    Ok(())
+}
+
+/// The result of one codec in `SuccinctPobList::bench_codecs`.
+#[derive(Clone, Debug)]
+pub struct CodecBench {
+    pub codec: InterningCodec,
+    pub interning_size: usize,
+    pub compress_time: Duration,
+    pub uncompress_time: Duration,
+}
+
+impl std::fmt::Display for CodecBench {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{:?}] interning size: {}, compress: {:?}, uncompress: {:?}",
+            self.codec, self.interning_size, self.compress_time, self.uncompress_time
+        )
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct SuccinctPobList {
     pub pob: Vec<Pob<usize>>,
@@ -56,30 +210,257 @@ pub struct SuccinctPobList {
     pub hash: B256,
 }
 
-#[derive(Default, Debug, Deserialize, Serialize, Clone)]
+/// Layout version of `PobData`. Version 0 is the original flat layout with
+/// the Scroll and Linea fields side by side, it is still accepted on input
+/// (see `PobDataJson`) and keeps its original hashing rule, which doesn't
+/// cover `coinbase` and the zkroot. The PoB lists put into the DA are
+/// checked against the rule of their version, then upgraded to the current
+/// one and rehashed, see `SuccinctPobList::verify_unwrap`.
+pub const POB_DATA_VERSION: u8 = 1;
+
+/// Checks the version of a decoded `PobData`, unknown versions are returned
+/// as the error.
+pub(crate) fn check_data_version(version: u8) -> Result<u8, u8> {
+    match version {
+        0 | POB_DATA_VERSION => Ok(version),
+        other => Err(other),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(try_from = "PobDataJson<T>")]
 pub struct PobData<T: Default> {
+    pub version: u8,
     pub chain_id: u64,
     pub coinbase: Option<Address>,
     pub prev_state_root: B256,
     pub block_hashes: BTreeMap<u64, B256>,
     pub mpt_nodes: Vec<T>,
     pub codes: Vec<T>,
+    pub chain: ChainData<T>,
+}
+
+/// The chain specific part of a PoB, tagged by `type` in JSON.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ChainData<T> {
+    Scroll(ScrollData),
+    Linea(LineaData<T>),
+}
 
-    // scroll
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct ScrollData {
     pub start_l1_queue_index: u64,
     pub withdrawal_root: B256,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LineaData<T> {
+    pub traces: Vec<T>,
+    pub proofs: Vec<T>,
+    pub zkroot: B256,
+}
 
-    // linea
+impl<T> Default for LineaData<T> {
+    fn default() -> Self {
+        Self {
+            traces: Vec::new(),
+            proofs: Vec::new(),
+            zkroot: B256::default(),
+        }
+    }
+}
+
+/// Accepts both the current layout and the version 0 layout.
+#[derive(Deserialize)]
+struct PobDataJson<T> {
+    #[serde(default)]
+    version: u8,
+    chain_id: u64,
+    coinbase: Option<Address>,
+    prev_state_root: B256,
+    block_hashes: BTreeMap<u64, B256>,
+    mpt_nodes: Vec<T>,
+    codes: Vec<T>,
+    #[serde(default)]
+    chain: Option<ChainData<T>>,
+
+    // version 0
     #[serde(default)]
-    pub linea_traces: Vec<T>,
+    start_l1_queue_index: u64,
     #[serde(default)]
-    pub linea_proofs: Vec<T>,
+    withdrawal_root: B256,
     #[serde(default)]
-    pub linea_zkroot: B256,
+    linea_traces: Vec<T>,
+    #[serde(default)]
+    linea_proofs: Vec<T>,
+    #[serde(default)]
+    linea_zkroot: B256,
+}
+
+impl<T: Default> TryFrom<PobDataJson<T>> for PobData<T> {
+    type Error = String;
+
+    fn try_from(data: PobDataJson<T>) -> Result<Self, Self::Error> {
+        let version = check_data_version(data.version)
+            .map_err(|version| format!("unsupported PoB data version: {}", version))?;
+        let chain = match data.chain {
+            Some(chain) => chain,
+            // every Linea PoB carries the zktrie root, the Scroll ones don't
+            None if !data.linea_zkroot.is_zero() => ChainData::Linea(LineaData {
+                traces: data.linea_traces,
+                proofs: data.linea_proofs,
+                zkroot: data.linea_zkroot,
+            }),
+            None => ChainData::Scroll(ScrollData {
+                start_l1_queue_index: data.start_l1_queue_index,
+                withdrawal_root: data.withdrawal_root,
+            }),
+        };
+        Ok(PobData {
+            version,
+            chain_id: data.chain_id,
+            coinbase: data.coinbase,
+            prev_state_root: data.prev_state_root,
+            block_hashes: data.block_hashes,
+            mpt_nodes: data.mpt_nodes,
+            codes: data.codes,
+            chain,
+        })
+    }
+}
+
+impl<T: Default> PobData<T> {
+    pub fn task_type(&self) -> TaskType {
+        match &self.chain {
+            ChainData::Scroll(_) => TaskType::Scroll,
+            ChainData::Linea(_) => TaskType::Linea,
+        }
+    }
+
+    pub fn scroll(&self) -> Option<&ScrollData> {
+        match &self.chain {
+            ChainData::Scroll(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    pub fn linea(&self) -> Option<&LineaData<T>> {
+        match &self.chain {
+            ChainData::Linea(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    /// Every interned item list of the PoB: mpt nodes, codes and the chain
+    /// specific ones.
+    pub fn item_lists(&self) -> Vec<&Vec<T>> {
+        let mut out = vec![&self.mpt_nodes, &self.codes];
+        if let ChainData::Linea(data) = &self.chain {
+            out.push(&data.traces);
+            out.push(&data.proofs);
+        }
+        out
+    }
+
+    pub fn item_lists_mut(&mut self) -> Vec<&mut Vec<T>> {
+        let mut out = vec![&mut self.mpt_nodes, &mut self.codes];
+        if let ChainData::Linea(data) = &mut self.chain {
+            out.push(&mut data.traces);
+            out.push(&mut data.proofs);
+        }
+        out
+    }
+
+    /// Converts every item list with `f`, keeping the other fields.
+    pub fn try_map<U, E, F>(&self, mut f: F) -> Result<PobData<U>, E>
+    where
+        U: Default,
+        F: FnMut(&[T]) -> Result<Vec<U>, E>,
+    {
+        let chain = match &self.chain {
+            ChainData::Scroll(data) => ChainData::Scroll(data.clone()),
+            ChainData::Linea(data) => ChainData::Linea(LineaData {
+                traces: f(&data.traces)?,
+                proofs: f(&data.proofs)?,
+                zkroot: data.zkroot,
+            }),
+        };
+        Ok(PobData {
+            version: self.version,
+            chain_id: self.chain_id,
+            coinbase: self.coinbase,
+            prev_state_root: self.prev_state_root,
+            block_hashes: self.block_hashes.clone(),
+            mpt_nodes: f(&self.mpt_nodes)?,
+            codes: f(&self.codes)?,
+            chain,
+        })
+    }
+
+    pub fn map<U, F>(&self, mut f: F) -> PobData<U>
+    where
+        U: Default,
+        F: FnMut(&[T]) -> Vec<U>,
+    {
+        self.try_map(|items| Ok::<_, Infallible>(f(items)))
+            .unwrap_or_else(|err| match err {})
+    }
 }
 
 impl PobData<Bytes> {
     pub fn hash(&self) -> B256 {
+        match self.version {
+            0 => self.hash_v0(),
+            _ => self.hash_v1(),
+        }
+    }
+
+    /// Covers every field, with the chain tag and length prefixes so that
+    /// different PoBs can't produce the same preimage.
+    fn hash_v1(&self) -> B256 {
+        keccak_encode(|hash| {
+            hash(&[self.version]);
+            hash(&self.chain_id.to_be_bytes());
+            match &self.coinbase {
+                Some(coinbase) => {
+                    hash(&[1]);
+                    hash(coinbase.as_slice());
+                }
+                None => hash(&[0]),
+            }
+            hash(self.prev_state_root.as_slice());
+            hash(&(self.block_hashes.len() as u64).to_be_bytes());
+            for (blk, block_hash) in &self.block_hashes {
+                hash(&blk.to_be_bytes());
+                hash(block_hash.as_slice());
+            }
+            hash_items(hash, &self.mpt_nodes);
+            hash_items(hash, &self.codes);
+            match &self.chain {
+                ChainData::Scroll(data) => {
+                    hash(b"scroll");
+                    hash(&data.start_l1_queue_index.to_be_bytes());
+                    hash(data.withdrawal_root.as_slice());
+                }
+                ChainData::Linea(data) => {
+                    hash(b"linea");
+                    hash_items(hash, &data.traces);
+                    hash_items(hash, &data.proofs);
+                    hash(data.zkroot.as_slice());
+                }
+            }
+        })
+    }
+
+    /// The hashing rule of the version 0 layout, which doesn't cover
+    /// `coinbase` and the zkroot.
+    fn hash_v0(&self) -> B256 {
+        let scroll = self.scroll().cloned().unwrap_or_default();
+        let (traces, proofs) = match self.linea() {
+            Some(data) => (&data.traces[..], &data.proofs[..]),
+            None => (&[][..], &[][..]),
+        };
         keccak_encode(|hash| {
             hash(&self.chain_id.to_be_bytes());
             hash(self.prev_state_root.as_slice());
@@ -96,51 +477,53 @@ impl PobData<Bytes> {
             for code in &self.codes {
                 hash(code);
             }
-            hash(&self.start_l1_queue_index.to_be_bytes());
-            hash(self.withdrawal_root.as_slice());
+            hash(&scroll.start_l1_queue_index.to_be_bytes());
+            hash(scroll.withdrawal_root.as_slice());
 
-            for trace in &self.linea_traces {
+            for trace in traces {
                 hash(trace);
             }
-            for proof in &self.linea_proofs {
+            for proof in proofs {
                 hash(proof);
             }
         })
-        .into()
     }
 
     pub fn intern(&self, si: &mut StringInterning) -> PobData<usize> {
-        PobData {
-            chain_id: self.chain_id,
-            coinbase: self.coinbase,
-            prev_state_root: self.prev_state_root,
-            block_hashes: self.block_hashes.clone(),
-            mpt_nodes: si.offsets(&self.mpt_nodes),
-            codes: si.offsets(&self.codes),
-            start_l1_queue_index: self.start_l1_queue_index,
-            withdrawal_root: self.withdrawal_root,
-            linea_proofs: si.offsets(&self.linea_proofs),
-            linea_traces: si.offsets(&self.linea_traces),
-            linea_zkroot: self.linea_zkroot,
-        }
+        self.map(|items| si.offsets(items))
+    }
+
+    pub fn hashed<F>(&self, mut f: F) -> PobData<B256>
+    where
+        F: FnMut(&Bytes) -> B256,
+    {
+        self.map(|items| items.iter().map(&mut f).collect())
+    }
+}
+
+fn hash_items(hash: &mut dyn FnMut(&[u8]), items: &[Bytes]) {
+    hash(&(items.len() as u64).to_be_bytes());
+    for item in items {
+        hash(&(item.len() as u64).to_be_bytes());
+        hash(item);
+    }
+}
+
+impl PobData<B256> {
+    pub fn resolve<F, E>(&self, f: F) -> Result<PobData<Bytes>, E>
+    where
+        F: Fn(&B256) -> Result<Bytes, E>,
+    {
+        self.try_map(|hashes| hashes.iter().map(&f).collect())
     }
 }
 
 impl PobData<usize> {
-    pub fn unintern(&self, si: &StringInterningReader) -> PobData<Bytes> {
-        PobData {
-            chain_id: self.chain_id,
-            prev_state_root: self.prev_state_root,
-            coinbase: self.coinbase,
-            block_hashes: self.block_hashes.clone(),
-            mpt_nodes: si.read(&self.mpt_nodes),
-            codes: si.read(&self.codes),
-            start_l1_queue_index: self.start_l1_queue_index,
-            withdrawal_root: self.withdrawal_root,
-            linea_proofs: si.read(&self.linea_proofs),
-            linea_traces: si.read(&self.linea_traces),
-            linea_zkroot: self.linea_zkroot,
-        }
+    pub fn try_unintern(
+        &self,
+        si: &StringInterningReader,
+    ) -> Result<PobData<Bytes>, InterningError> {
+        self.try_map(|offs| si.try_read(offs))
     }
 }
 
@@ -159,10 +542,28 @@ impl Pob<Bytes> {
             for item in &self.data.mpt_nodes {
                 hash(&item);
             }
+            if let Some(linea) = self.data.linea() {
+                for trace in &linea.traces {
+                    hash(trace);
+                }
+                for proof in &linea.proofs {
+                    hash(proof);
+                }
+            }
         })
         .into()
     }
 
+    /// The per-block commitment reported in `Poe::state_hash`, binding the
+    /// witness data to the transactions and the block it was executed for.
+    pub fn state_commitment(&self) -> B256 {
+        keccak_encode(|hash| {
+            hash(self.state_hash().as_slice());
+            hash(self.block.transactions_root.as_slice());
+            hash(self.block.block_hash.unwrap_or_default().as_slice());
+        })
+    }
+
     pub fn pob_hash(&self) -> B256 {
         keccak_encode(|hash| {
             hash(self.block.block_hash.unwrap_or_default().as_slice());
@@ -170,44 +571,73 @@ impl Pob<Bytes> {
         })
         .into()
     }
+
+    /// Moves a PoB of an older data version to the current one, its hash is
+    /// recomputed with the current rule.
+    pub fn upgrade(mut self) -> Self {
+        if self.data.version != POB_DATA_VERSION {
+            self.data.version = POB_DATA_VERSION;
+            self.hash = self.pob_hash();
+        }
+        self
+    }
 }
 
 impl SuccinctPobList {
-    pub fn unwrap(self) -> Vec<Pob> {
-        let mut out = Vec::new();
-        let reader = StringInterningReader::new(&self.interning);
-        for pob in self.pob {
-            let mut new_pob = Pob {
+    /// Rejects a list whose interning blob is malformed or whose PoB hashes
+    /// or list hash don't match the decoded content. The hashes are checked
+    /// with the rule of the data version they were sent with, the returned
+    /// PoBs are upgraded to the current version, so the list hash of a
+    /// version 0 list changes.
+    pub fn verify_unwrap(self) -> Result<Vec<Pob>, PobListError> {
+        let reader = StringInterningReader::try_new(&self.interning)?;
+        let mut out = Vec::with_capacity(self.pob.len());
+        for (index, pob) in self.pob.into_iter().enumerate() {
+            let new_pob = Pob {
                 block: pob.block,
-                data: pob.data.unintern(&reader),
-                hash: B256::default(),
+                data: pob.data.try_unintern(&reader)?,
+                hash: pob.hash,
             };
-            new_pob.hash = new_pob.pob_hash();
-            out.push(new_pob);
+            out.push(new_pob.verify_hash(index)?);
         }
-        out
+        verify_list_hash(&out, self.hash)?;
+        Ok(out.into_iter().map(Pob::upgrade).collect())
     }
 
     pub fn compress(list: &[Pob<Bytes>]) -> SuccinctPobList {
+        Self::compress_with(list, InterningCodec::default())
+    }
+
+    /// Compresses and unwraps `list` with every interning codec.
+    pub fn bench_codecs(list: &[Pob<Bytes>]) -> Result<Vec<CodecBench>, PobListError> {
+        let mut out = Vec::with_capacity(InterningCodec::ALL.len());
+        for codec in InterningCodec::ALL {
+            let start = Instant::now();
+            let succinct = Self::compress_with(list, codec);
+            let compress_time = start.elapsed();
+
+            let interning_size = succinct.interning.len();
+            let start = Instant::now();
+            succinct.verify_unwrap()?;
+            out.push(CodecBench {
+                codec,
+                interning_size,
+                compress_time,
+                uncompress_time: start.elapsed(),
+            });
+        }
+        Ok(out)
+    }
+
+    pub fn compress_with(list: &[Pob<Bytes>], codec: InterningCodec) -> SuccinctPobList {
         let mut si = StringInterning::new();
-        let hash = keccak_encode(|hash| {
-            for item in list {
-                hash(item.hash.as_slice());
-            }
-        });
+        let hash = pob_list_hash(list);
         let mut pob_list = Vec::with_capacity(list.len());
         for item in list {
-            for item in &item.data.mpt_nodes {
-                si.collect(item.clone().into());
-            }
-            for item in &item.data.codes {
-                si.collect(item.clone().into());
-            }
-            for item in &item.data.linea_proofs {
-                si.collect(item.clone().into());
-            }
-            for item in &item.data.linea_traces {
-                si.collect(item.clone().into());
+            for items in item.data.item_lists() {
+                for item in items {
+                    si.collect(item.clone().into());
+                }
             }
         }
         si.build();
@@ -221,12 +651,147 @@ impl SuccinctPobList {
 
         SuccinctPobList {
             pob: pob_list,
-            interning: si.to_compress_bytes().into(),
-            hash: hash.into(),
+            interning: si.to_bytes(codec).into(),
+            hash,
+        }
+    }
+}
+
+/// A PoB list where the mpt nodes, codes, traces and proofs are referenced by
+/// their keccak hash. `blobs` only carries the items the prover reported as
+/// missing, the others are taken from the prover's node pool.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DeltaPobList {
+    pub pob: Vec<Pob<B256>>,
+    pub blobs: Vec<Bytes>,
+    pub hash: B256,
+}
+
+#[derive(Clone, Debug)]
+pub enum PobListError {
+    Interning(InterningError),
+    MissingNode(B256),
+    PobHashMismatch {
+        index: usize,
+        expect: B256,
+        got: B256,
+    },
+    ListHashMismatch {
+        expect: B256,
+        got: B256,
+    },
+}
+
+impl From<InterningError> for PobListError {
+    fn from(err: InterningError) -> Self {
+        Self::Interning(err)
+    }
+}
+
+impl Pob<Bytes> {
+    fn verify_hash(self, index: usize) -> Result<Self, PobListError> {
+        let got = self.pob_hash();
+        if got != self.hash {
+            return Err(PobListError::PobHashMismatch {
+                index,
+                expect: self.hash,
+                got,
+            });
+        }
+        Ok(self)
+    }
+}
+
+fn verify_list_hash(list: &[Pob<Bytes>], expect: B256) -> Result<(), PobListError> {
+    let got = pob_list_hash(list);
+    if got != expect {
+        return Err(PobListError::ListHashMismatch { expect, got });
+    }
+    Ok(())
+}
+
+impl DeltaPobList {
+    /// All the item hashes referenced by the list, used to query
+    /// `da_missingNodes`.
+    pub fn node_hashes(list: &[Pob<Bytes>]) -> Vec<B256> {
+        let mut hashes = BTreeSet::new();
+        for pob in list {
+            pob.data.hashed(|item| {
+                let hash = keccak256(item);
+                hashes.insert(hash);
+                hash
+            });
+        }
+        hashes.into_iter().collect()
+    }
+
+    pub fn build(list: &[Pob<Bytes>], missing: &[B256]) -> Self {
+        let missing = missing.iter().collect::<BTreeSet<_>>();
+        let mut blobs = BTreeMap::new();
+        let hash = pob_list_hash(list);
+        let pob = list
+            .iter()
+            .map(|pob| Pob {
+                block: pob.block.clone(),
+                data: pob.data.hashed(|item| {
+                    let hash = keccak256(item);
+                    if missing.contains(&hash) {
+                        blobs.insert(hash, item.clone());
+                    }
+                    hash
+                }),
+                hash: pob.hash,
+            })
+            .collect();
+        DeltaPobList {
+            pob,
+            blobs: blobs.into_values().collect(),
+            hash,
+        }
+    }
+
+    /// Resolves the items with the blobs of the list, then with `lookup`.
+    /// The hashes are checked and the PoBs upgraded like
+    /// `SuccinctPobList::verify_unwrap` does.
+    pub fn unwrap<F>(self, lookup: F) -> Result<Vec<Pob>, PobListError>
+    where
+        F: Fn(&B256) -> Option<Bytes>,
+    {
+        let blobs = self
+            .blobs
+            .into_iter()
+            .map(|blob| (keccak256(&blob), blob))
+            .collect::<BTreeMap<_, _>>();
+        let mut out = Vec::with_capacity(self.pob.len());
+        for (index, pob) in self.pob.into_iter().enumerate() {
+            let data = pob.data.resolve(|hash| {
+                blobs
+                    .get(hash)
+                    .cloned()
+                    .or_else(|| lookup(hash))
+                    .ok_or(PobListError::MissingNode(*hash))
+            })?;
+            let new_pob = Pob {
+                block: pob.block,
+                data,
+                hash: pob.hash,
+            };
+            out.push(new_pob.verify_hash(index)?);
         }
+        verify_list_hash(&out, self.hash)?;
+        Ok(out.into_iter().map(Pob::upgrade).collect())
     }
 }
 
+/// The key of a PoB list in the prover's DA, `keccak(pob[0].hash || ...)`.
+pub fn pob_list_hash(list: &[Pob<Bytes>]) -> B256 {
+    keccak_encode(|hash| {
+        for pob in list {
+            hash(pob.hash.as_slice());
+        }
+    })
+}
+
 pub fn keccak_encode<F>(f: F) -> B256
 where
     F: FnOnce(&mut dyn FnMut(&[u8])),
@@ -235,3 +800,227 @@ where
     f(&mut |data: &[u8]| keccak.update(data));
     keccak.finalize()
 }
+
+#[cfg(test)]
+mod test {
+    use alloy::primitives::{b256, hex};
+
+    use super::*;
+
+    fn sample_list() -> Vec<Pob> {
+        (1..3)
+            .map(|number| {
+                let block = PobBlock {
+                    number: U64::from(number),
+                    block_hash: Some(B256::repeat_byte(number as u8)),
+                    ..Default::default()
+                };
+                let data = PobData {
+                    version: POB_DATA_VERSION,
+                    chain_id: 534352,
+                    coinbase: None,
+                    prev_state_root: B256::default(),
+                    block_hashes: BTreeMap::new(),
+                    mpt_nodes: vec![vec![number as u8; 40].into(), vec![9; 40].into()],
+                    codes: vec![vec![0x60, 0x80].into()],
+                    chain: ChainData::Scroll(ScrollData::default()),
+                };
+                Pob::new(block, data)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_verify_unwrap() {
+        let list = sample_list();
+        let succinct = SuccinctPobList::compress(&list);
+        assert_eq!(succinct.hash, pob_list_hash(&list));
+        let unwrapped = succinct.clone().verify_unwrap().unwrap();
+        assert_eq!(pob_list_hash(&unwrapped), succinct.hash);
+
+        let mut bad_list_hash = succinct.clone();
+        bad_list_hash.hash = B256::default();
+        assert!(matches!(
+            bad_list_hash.verify_unwrap(),
+            Err(PobListError::ListHashMismatch { .. })
+        ));
+
+        let mut bad_pob_hash = succinct.clone();
+        bad_pob_hash.pob[1].data.chain_id = 1;
+        assert!(matches!(
+            bad_pob_hash.verify_unwrap(),
+            Err(PobListError::PobHashMismatch { index: 1, .. })
+        ));
+
+        let mut bad_offset = succinct;
+        bad_offset.pob[0].data.codes[0] += 1;
+        assert!(matches!(
+            bad_offset.verify_unwrap(),
+            Err(PobListError::Interning(InterningError::InvalidOffset(_)))
+        ));
+    }
+
+    #[test]
+    fn test_verify_unwrap_legacy() {
+        // hashed by a client with the version 0 rule
+        let legacy = sample_list()
+            .into_iter()
+            .map(|pob| {
+                let mut data = pob.data;
+                data.version = 0;
+                data.coinbase = Some(Address::repeat_byte(7));
+                Pob::new(pob.block, data)
+            })
+            .collect::<Vec<_>>();
+        let succinct = SuccinctPobList::compress(&legacy);
+        let json = serde_json::to_vec(&succinct).unwrap();
+        let succinct: SuccinctPobList = serde_json::from_slice(&json).unwrap();
+        assert_eq!(succinct.pob[0].data.version, 0);
+
+        let unwrapped = succinct.clone().verify_unwrap().unwrap();
+        for (pob, legacy) in unwrapped.iter().zip(&legacy) {
+            assert_eq!(pob.data.version, POB_DATA_VERSION);
+            assert_eq!(pob.hash, pob.pob_hash());
+            assert_ne!(pob.hash, legacy.hash);
+        }
+        assert_ne!(pob_list_hash(&unwrapped), succinct.hash);
+
+        // the legacy hashes are still checked
+        let mut tampered = succinct;
+        tampered.pob[0].data.chain_id = 1;
+        assert!(matches!(
+            tampered.verify_unwrap(),
+            Err(PobListError::PobHashMismatch { index: 0, .. })
+        ));
+    }
+
+    // the genesis block of Ethereum mainnet
+    fn mainnet_genesis() -> PobBlock {
+        PobBlock {
+            miner: Address::ZERO,
+            state_root: b256!("d7f8974fb5ac78d9ac099b9ad5018bedc2ce0a72dad1827a1709da30580f0544"),
+            difficulty: U256::from(0x400000000_u64),
+            number: U64::ZERO,
+            gas_limit: U64::from(5000),
+            timestamp: U64::ZERO,
+            extra_data: hex!("11bbe8db4e347b4e8c937c1c8370e4b5ed33adb3db69cbdb7a38e1e50b1b82fa")
+                .into(),
+            parent_hash: B256::ZERO,
+            uncles_hash: b256!("1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347"),
+            transactions_root: b256!(
+                "56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421"
+            ),
+            receipts_root: b256!(
+                "56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421"
+            ),
+            logs_bloom: Bloom::ZERO,
+            nonce: B64::from(0x42_u64),
+            gas_used: U256::ZERO,
+            mix_hash: B256::ZERO,
+            base_fee_per_gas: None,
+            block_hash: Some(b256!(
+                "d4e56740f876aef8c010b86a40d5f56745a118d0906a34e69aec8c0db1cb8fa3"
+            )),
+            transactions: vec![],
+        }
+    }
+
+    #[test]
+    fn test_verify_header() {
+        let genesis = mainnet_genesis();
+        assert_eq!(Some(genesis.header_hash()), genesis.block_hash);
+        genesis.verify().unwrap();
+
+        let mut tampered = genesis.clone();
+        tampered.gas_limit = U64::from(5001);
+        assert!(matches!(
+            tampered.verify(),
+            Err(HeaderError::BlockHashMismatch { number: 0, .. })
+        ));
+        let mut with_tx = genesis;
+        with_tx.transactions.push(vec![0xc0].into());
+        assert!(matches!(
+            with_tx.verify(),
+            Err(HeaderError::TransactionsRootMismatch { number: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_bench_codecs() {
+        let benches = SuccinctPobList::bench_codecs(&sample_list()).unwrap();
+        let codecs = benches.iter().map(|bench| bench.codec).collect::<Vec<_>>();
+        assert_eq!(codecs, InterningCodec::ALL);
+    }
+
+    #[test]
+    fn test_delta_unwrap() {
+        let list = sample_list();
+        let hashes = DeltaPobList::node_hashes(&list);
+        // the prover already has the shared node
+        let known = keccak256([9_u8; 40]);
+        let missing = hashes.iter().filter(|h| **h != known).cloned().collect::<Vec<_>>();
+        let delta = DeltaPobList::build(&list, &missing);
+        assert_eq!(delta.blobs.len(), missing.len());
+
+        let lookup = |hash: &B256| (*hash == known).then(|| Bytes::from(vec![9_u8; 40]));
+        let unwrapped = delta.clone().unwrap(lookup).unwrap();
+        assert_eq!(pob_list_hash(&unwrapped), delta.hash);
+        assert!(matches!(
+            delta.unwrap(|_| None),
+            Err(PobListError::MissingNode(hash)) if hash == known
+        ));
+    }
+
+    #[test]
+    fn test_pob_data_json() {
+        let pob = &sample_list()[0];
+        let json = serde_json::to_value(&pob.data).unwrap();
+        assert_eq!(json["version"], POB_DATA_VERSION);
+        assert_eq!(json["chain"]["type"], "scroll");
+        let data: PobData<Bytes> = serde_json::from_value(json).unwrap();
+        assert_eq!(data.hash(), pob.data.hash());
+
+        // the hash covers the fields ignored by the version 0 rule
+        let mut with_coinbase = data.clone();
+        with_coinbase.coinbase = Some(Address::repeat_byte(1));
+        assert_ne!(with_coinbase.hash(), data.hash());
+    }
+
+    #[test]
+    fn test_pob_data_legacy_json() {
+        let legacy = serde_json::json!({
+            "chain_id": 59144,
+            "coinbase": null,
+            "prev_state_root": B256::repeat_byte(1),
+            "block_hashes": {},
+            "mpt_nodes": [],
+            "codes": ["0x6080"],
+            "start_l1_queue_index": 0,
+            "withdrawal_root": B256::default(),
+            "linea_traces": ["0x7b7d"],
+            "linea_proofs": [],
+            "linea_zkroot": B256::repeat_byte(2),
+        });
+        let data: PobData<Bytes> = serde_json::from_value(legacy.clone()).unwrap();
+        assert_eq!(data.version, 0);
+        assert_eq!(data.task_type(), TaskType::Linea);
+        let linea = data.linea().unwrap();
+        assert_eq!(linea.zkroot, B256::repeat_byte(2));
+        assert_eq!(linea.traces, vec![Bytes::from_static(b"{}")]);
+
+        // version 0 keeps its hashing rule, so the zkroot isn't covered
+        let mut other_root = data.clone();
+        if let ChainData::Linea(linea) = &mut other_root.chain {
+            linea.zkroot = B256::repeat_byte(3);
+        }
+        assert_eq!(other_root.hash(), data.hash());
+        other_root.version = POB_DATA_VERSION;
+        let mut upgraded = data.clone();
+        upgraded.version = POB_DATA_VERSION;
+        assert_ne!(other_root.hash(), upgraded.hash());
+
+        let mut unknown = legacy;
+        unknown["version"] = (POB_DATA_VERSION + 1).into();
+        assert!(serde_json::from_value::<PobData<Bytes>>(unknown).is_err());
+    }
+}
@@ -19,12 +19,7 @@ impl Poe {
             return None;
         }
 
-        let state_hash = keccak_encode(|hash| {
-            for report in reports {
-                hash(&report.state_hash.0);
-            }
-        })
-        .into();
+        let state_hash = Self::chain_state_hash(reports.iter().map(|report| report.state_hash));
         let prev_state_root = reports.first().unwrap().prev_state_root;
         let new_state_root = reports.last().unwrap().new_state_root;
         let withdrawal_root = reports.last().unwrap().withdrawal_root;
@@ -37,6 +32,60 @@ impl Poe {
             signature: vec![0_u8; 65].into(),
         })
     }
+
+    /// Chains the per-block commitments: h_i = keccak(h_{i-1} || state_hash_i),
+    /// starting from h_0 = 0.
+    pub fn chain_state_hash<I: IntoIterator<Item = B256>>(state_hashes: I) -> B256 {
+        let mut state_hash = B256::default();
+        for block_hash in state_hashes {
+            state_hash = keccak_encode(|hash| {
+                hash(&state_hash.0);
+                hash(&block_hash.0);
+            });
+        }
+        state_hash
+    }
+
+    /// Combine the reports of consecutive batches into a single report which
+    /// commits to every batch hash, the state hashes are chained like the
+    /// blocks of a batch in `merge`. Each report must start from the state
+    /// root the previous one ended with.
+    pub fn aggregate(reports: &[Self]) -> Result<Self, AggregateError> {
+        let (first, last) = match (reports.first(), reports.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Err(AggregateError::EmptyReports),
+        };
+        for (idx, pair) in reports.windows(2).enumerate() {
+            if pair[0].new_state_root != pair[1].prev_state_root {
+                return Err(AggregateError::StateRootDiscontinuity {
+                    index: idx + 1,
+                    expect: pair[0].new_state_root,
+                    got: pair[1].prev_state_root,
+                });
+            }
+        }
+
+        let batch_hash = keccak_encode(|hash| {
+            for report in reports {
+                hash(&report.batch_hash.0);
+            }
+        });
+        let state_hash = Self::chain_state_hash(reports.iter().map(|report| report.state_hash));
+        Ok(Self {
+            batch_hash,
+            state_hash,
+            prev_state_root: first.prev_state_root,
+            new_state_root: last.new_state_root,
+            withdrawal_root: last.withdrawal_root,
+            signature: vec![0_u8; 65].into(),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum AggregateError {
+    EmptyReports,
+    StateRootDiscontinuity { index: usize, expect: B256, got: B256 },
 }
 
 impl Default for Poe {
@@ -51,3 +100,90 @@ impl Default for Poe {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn report(n: u8) -> Poe {
+        Poe {
+            batch_hash: B256::repeat_byte(n),
+            state_hash: B256::repeat_byte(n + 100),
+            prev_state_root: B256::with_last_byte(n),
+            new_state_root: B256::with_last_byte(n + 1),
+            withdrawal_root: B256::repeat_byte(n + 200),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_merge_chains_state_hashes() {
+        let reports = (1..=3).map(report).collect::<Vec<_>>();
+        let poe = Poe::merge(B256::repeat_byte(9), &reports).unwrap();
+
+        let h1 = keccak_encode(|hash| {
+            hash(&[0; 32]);
+            hash(&[101; 32]);
+        });
+        let h2 = keccak_encode(|hash| {
+            hash(&h1.0);
+            hash(&[102; 32]);
+        });
+        let h3 = keccak_encode(|hash| {
+            hash(&h2.0);
+            hash(&[103; 32]);
+        });
+        assert_eq!(poe.state_hash, h3);
+        assert_eq!(poe.batch_hash, B256::repeat_byte(9));
+        assert_eq!(poe.prev_state_root, B256::with_last_byte(1));
+        assert_eq!(poe.new_state_root, B256::with_last_byte(4));
+
+        // the order of the blocks is committed
+        let swapped = vec![report(2), report(1), report(3)];
+        let other = Poe::merge(B256::repeat_byte(9), &swapped).unwrap();
+        assert_ne!(other.state_hash, poe.state_hash);
+        assert!(Poe::merge(B256::default(), &[]).is_none());
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let reports = (1..=3).map(report).collect::<Vec<_>>();
+        let poe = Poe::aggregate(&reports).unwrap();
+        assert_eq!(
+            poe.batch_hash,
+            keccak_encode(|hash| {
+                hash(&[1; 32]);
+                hash(&[2; 32]);
+                hash(&[3; 32]);
+            })
+        );
+        let merged = Poe::merge(B256::default(), &reports).unwrap();
+        assert_eq!(poe.state_hash, merged.state_hash);
+        assert_eq!(poe.prev_state_root, B256::with_last_byte(1));
+        assert_eq!(poe.new_state_root, B256::with_last_byte(4));
+        assert_eq!(poe.withdrawal_root, B256::repeat_byte(203));
+
+        // a single report still gets rehashed
+        let single = Poe::aggregate(&reports[..1]).unwrap();
+        assert_ne!(single.batch_hash, reports[0].batch_hash);
+        assert_eq!(single.new_state_root, reports[0].new_state_root);
+    }
+
+    #[test]
+    fn test_aggregate_errors() {
+        assert!(matches!(
+            Poe::aggregate(&[]),
+            Err(AggregateError::EmptyReports)
+        ));
+
+        let reports = vec![report(1), report(2), report(5)];
+        match Poe::aggregate(&reports) {
+            Err(AggregateError::StateRootDiscontinuity { index, expect, got }) => {
+                assert_eq!(index, 2);
+                assert_eq!(expect, B256::with_last_byte(3));
+                assert_eq!(got, B256::with_last_byte(5));
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+}
@@ -1,26 +1,133 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Read;
 
 use alloy::primitives::Bytes;
+use serde::{Deserialize, Serialize};
 
-pub struct StringInterningReader(Vec<u8>);
+/// Compression of the interning blob, stored as its first byte.
+///
+/// Blobs produced before the codec byte was introduced are plain gzip
+/// streams, they are recognized by the gzip magic (`0x1f 0x8b`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InterningCodec {
+    Uncompressed,
+    #[default]
+    Gzip,
+    Zstd,
+}
+
+impl InterningCodec {
+    pub const ALL: [InterningCodec; 3] = [Self::Uncompressed, Self::Gzip, Self::Zstd];
+
+    pub fn u8(&self) -> u8 {
+        match self {
+            Self::Uncompressed => 0,
+            Self::Gzip => 1,
+            Self::Zstd => 2,
+        }
+    }
+
+    pub fn from_u8(val: u8) -> Option<Self> {
+        Some(match val {
+            0 => Self::Uncompressed,
+            1 => Self::Gzip,
+            2 => Self::Zstd,
+            _ => return None,
+        })
+    }
+
+    pub fn compress(&self, data: &[u8]) -> Vec<u8> {
+        let mut out = vec![self.u8()];
+        match self {
+            Self::Uncompressed => out.extend_from_slice(data),
+            Self::Gzip => gzip_compress(data, &mut out),
+            Self::Zstd => zstd_compress(data, &mut out),
+        }
+        out
+    }
+
+    pub fn uncompress(data: &[u8]) -> Result<Vec<u8>, InterningError> {
+        Self::uncompress_with_limit(data, MAX_INTERNING_LEN)
+    }
+
+    /// Fails once the output exceeds `limit` bytes.
+    pub fn uncompress_with_limit(data: &[u8], limit: usize) -> Result<Vec<u8>, InterningError> {
+        if data.starts_with(&GZIP_MAGIC) {
+            return gzip_uncompress(data, limit);
+        }
+        let (codec, payload) = data.split_first().ok_or(InterningError::EmptyBlob)?;
+        match Self::from_u8(*codec).ok_or(InterningError::UnknownCodec(*codec))? {
+            Self::Uncompressed => {
+                if payload.len() > limit {
+                    return Err(InterningError::TooLarge { limit });
+                }
+                Ok(payload.to_vec())
+            }
+            Self::Gzip => gzip_uncompress(payload, limit),
+            Self::Zstd => zstd_uncompress(payload, limit),
+        }
+    }
+}
+
+/// Upper bound of an uncompressed interning blob, a small malicious blob
+/// must not expand without limit.
+pub const MAX_INTERNING_LEN: usize = 1 << 30;
+
+#[derive(Clone, Debug)]
+pub enum InterningError {
+    EmptyBlob,
+    UnknownCodec(u8),
+    Uncompress(String),
+    TooLarge { limit: usize },
+    Truncated { offset: usize },
+    InvalidOffset(usize),
+}
+
+pub struct StringInterningReader {
+    data: Vec<u8>,
+    entries: BTreeSet<usize>,
+}
 
 impl StringInterningReader {
-    pub fn new(data: &[u8]) -> Self {
-        Self(uncompress(data))
+    /// Uncompresses the blob and checks that it is a sequence of
+    /// length-prefixed entries.
+    pub fn try_new(data: &[u8]) -> Result<Self, InterningError> {
+        let data = InterningCodec::uncompress(data)?;
+        let mut entries = BTreeSet::new();
+        let mut offset = 0;
+        while offset < data.len() {
+            let len = entry_len(&data, offset).ok_or(InterningError::Truncated { offset })?;
+            entries.insert(offset);
+            offset += 4 + len;
+        }
+        Ok(Self { data, entries })
     }
 
-    pub fn read(&self, offs: &[usize]) -> Vec<Bytes> {
+    pub fn try_read(&self, offs: &[usize]) -> Result<Vec<Bytes>, InterningError> {
         let mut out = Vec::with_capacity(offs.len());
-        let mut len_bytes = [0_u8; 4];
         for off in offs {
             let off = *off;
-            len_bytes.copy_from_slice(&self.0[off..off + 4]);
-            let len = u32::from_be_bytes(len_bytes) as usize;
-            let data = (&self.0[off + 4..off + 4 + len]).to_owned();
-            out.push(data.into());
+            if !self.entries.contains(&off) {
+                return Err(InterningError::InvalidOffset(off));
+            }
+            // entries are checked in try_new
+            let len = entry_len(&self.data, off).unwrap();
+            out.push(self.data[off + 4..off + 4 + len].to_vec().into());
         }
-        out
+        Ok(out)
+    }
+}
+
+/// Length of the entry at `off` if it fits in `data`.
+fn entry_len(data: &[u8], off: usize) -> Option<usize> {
+    let len_bytes: [u8; 4] = data.get(off..off.checked_add(4)?)?.try_into().ok()?;
+    let len = u32::from_be_bytes(len_bytes) as usize;
+    let end = off.checked_add(4)?.checked_add(len)?;
+    if end > data.len() {
+        return None;
     }
+    Some(len)
 }
 
 pub struct StringInterning {
@@ -66,23 +173,137 @@ impl StringInterning {
     }
 
     pub fn to_compress_bytes(&self) -> Vec<u8> {
-        compress(&self.data)
+        self.to_bytes(InterningCodec::default())
+    }
+
+    pub fn to_bytes(&self, codec: InterningCodec) -> Vec<u8> {
+        codec.compress(&self.data)
     }
 }
 
-fn compress(data: &[u8]) -> Vec<u8> {
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+fn gzip_compress(data: &[u8], out: &mut Vec<u8>) {
     use std::io::Write;
 
-    let mut out = Vec::new();
-    let mut encoder = libflate::gzip::Encoder::new(&mut out).unwrap();
-    encoder.write(&data).unwrap();
+    let mut encoder = libflate::gzip::Encoder::new(out).unwrap();
+    encoder.write_all(data).unwrap();
     encoder.finish().unwrap();
-    out
 }
 
-fn uncompress(data: &[u8]) -> Vec<u8> {
+fn gzip_uncompress(data: &[u8], limit: usize) -> Result<Vec<u8>, InterningError> {
+    let decoder = libflate::gzip::Decoder::new(data).map_err(uncompress_err)?;
+    read_limited(decoder, limit)
+}
+
+fn zstd_compress(data: &[u8], out: &mut Vec<u8>) {
+    out.extend(ruzstd::encoding::compress_to_vec(
+        data,
+        ruzstd::encoding::CompressionLevel::Fastest,
+    ));
+}
+
+fn zstd_uncompress(mut data: &[u8], limit: usize) -> Result<Vec<u8>, InterningError> {
+    let decoder = ruzstd::decoding::StreamingDecoder::new(&mut data)
+        .map_err(|err| InterningError::Uncompress(err.to_string()))?;
+    read_limited(decoder, limit)
+}
+
+/// Reads one byte past `limit` to tell an output of exactly `limit` bytes
+/// from a larger one.
+fn read_limited<R: Read>(reader: R, limit: usize) -> Result<Vec<u8>, InterningError> {
     let mut out = Vec::new();
-    let mut decoder = libflate::gzip::Decoder::new(data).unwrap();
-    std::io::copy(&mut decoder, &mut out).unwrap();
-    out
+    reader
+        .take(limit as u64 + 1)
+        .read_to_end(&mut out)
+        .map_err(uncompress_err)?;
+    if out.len() > limit {
+        return Err(InterningError::TooLarge { limit });
+    }
+    Ok(out)
+}
+
+fn uncompress_err(err: std::io::Error) -> InterningError {
+    InterningError::Uncompress(err.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_codec_roundtrip() {
+        let mut si = StringInterning::new();
+        let items: Vec<Bytes> = vec![vec![1; 100].into(), vec![2, 3].into(), Bytes::new()];
+        for item in &items {
+            si.collect(item.to_vec());
+        }
+        si.build();
+        let offsets = si.offsets(&items);
+
+        for codec in InterningCodec::ALL {
+            let blob = si.to_bytes(codec);
+            assert_eq!(blob[0], codec.u8());
+            let reader = StringInterningReader::try_new(&blob).unwrap();
+            assert_eq!(reader.try_read(&offsets).unwrap(), items);
+        }
+
+        // blobs without the codec byte are gzip streams
+        let mut legacy = Vec::new();
+        gzip_compress(&si.data, &mut legacy);
+        let reader = StringInterningReader::try_new(&legacy).unwrap();
+        assert_eq!(reader.try_read(&offsets).unwrap(), items);
+    }
+
+    #[test]
+    fn test_reject_invalid_blob() {
+        let mut si = StringInterning::new();
+        si.collect(vec![1; 10]);
+        si.collect(vec![2; 20]);
+        si.build();
+        let reader = StringInterningReader::try_new(&si.to_bytes(InterningCodec::Zstd)).unwrap();
+        assert!(reader.try_read(&[0, 14]).is_ok());
+        assert!(matches!(
+            reader.try_read(&[1]),
+            Err(InterningError::InvalidOffset(1))
+        ));
+        assert!(matches!(
+            reader.try_read(&[1000]),
+            Err(InterningError::InvalidOffset(1000))
+        ));
+
+        let mut truncated = si.to_bytes(InterningCodec::Uncompressed);
+        truncated.pop();
+        assert!(matches!(
+            StringInterningReader::try_new(&truncated),
+            Err(InterningError::Truncated { offset: 14 })
+        ));
+        assert!(matches!(
+            StringInterningReader::try_new(&[9, 0, 0]),
+            Err(InterningError::UnknownCodec(9))
+        ));
+        assert!(StringInterningReader::try_new(&[InterningCodec::Zstd.u8(), 1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn test_uncompress_limit() {
+        let data = vec![7_u8; 4096];
+        for codec in InterningCodec::ALL {
+            let blob = codec.compress(&data);
+            assert_eq!(
+                InterningCodec::uncompress_with_limit(&blob, data.len()).unwrap(),
+                data
+            );
+            assert!(matches!(
+                InterningCodec::uncompress_with_limit(&blob, data.len() - 1),
+                Err(InterningError::TooLarge { limit: 4095 })
+            ));
+        }
+        let mut legacy = Vec::new();
+        gzip_compress(&data, &mut legacy);
+        assert!(matches!(
+            InterningCodec::uncompress_with_limit(&legacy, 100),
+            Err(InterningError::TooLarge { limit: 100 })
+        ));
+    }
 }
@@ -0,0 +1,161 @@
+use alloy::primitives::{keccak256, B256};
+use alloy::rlp::{Encodable, Header, EMPTY_STRING_CODE};
+
+/// Root of the Merkle Patricia Trie keyed by `rlp(index)`, as used for the
+/// `transactionsRoot` and `receiptsRoot` of a block header.
+pub fn ordered_trie_root<T: AsRef<[u8]>>(items: &[T]) -> B256 {
+    let mut leaves = items
+        .iter()
+        .enumerate()
+        .map(|(idx, item)| {
+            let mut key = Vec::new();
+            idx.encode(&mut key);
+            (to_nibbles(&key), item.as_ref())
+        })
+        .collect::<Vec<_>>();
+    leaves.sort_by(|a, b| a.0.cmp(&b.0));
+    trie_root(&leaves)
+}
+
+/// `leaves` must be sorted by key and contain no duplicated keys.
+fn trie_root(leaves: &[(Vec<u8>, &[u8])]) -> B256 {
+    if leaves.is_empty() {
+        return keccak256([EMPTY_STRING_CODE]);
+    }
+    keccak256(encode_node(leaves, 0))
+}
+
+fn encode_node(leaves: &[(Vec<u8>, &[u8])], depth: usize) -> Vec<u8> {
+    if leaves.len() == 1 {
+        let (key, value) = &leaves[0];
+        return encode_list(&[rlp_bytes(&hex_prefix(&key[depth..], true)), rlp_bytes(value)]);
+    }
+
+    // the leaves are sorted, so the prefix shared by the first and the last
+    // key is shared by all of them.
+    let first = &leaves[0].0;
+    let last = &leaves[leaves.len() - 1].0;
+    let common = first[depth..]
+        .iter()
+        .zip(&last[depth..])
+        .take_while(|(a, b)| a == b)
+        .count();
+    if common > 0 {
+        let child = encode_node(leaves, depth + common);
+        return encode_list(&[
+            rlp_bytes(&hex_prefix(&first[depth..depth + common], false)),
+            node_ref(child),
+        ]);
+    }
+
+    let mut rest = leaves;
+    let mut value = None;
+    if rest[0].0.len() == depth {
+        value = Some(rest[0].1);
+        rest = &rest[1..];
+    }
+    let mut items = Vec::with_capacity(17);
+    for nibble in 0..16u8 {
+        let n = rest.iter().take_while(|(key, _)| key[depth] == nibble).count();
+        if n == 0 {
+            items.push(vec![EMPTY_STRING_CODE]);
+            continue;
+        }
+        items.push(node_ref(encode_node(&rest[..n], depth + 1)));
+        rest = &rest[n..];
+    }
+    items.push(match value {
+        Some(value) => rlp_bytes(value),
+        None => vec![EMPTY_STRING_CODE],
+    });
+    encode_list(&items)
+}
+
+fn node_ref(node: Vec<u8>) -> Vec<u8> {
+    if node.len() < 32 {
+        return node;
+    }
+    rlp_bytes(keccak256(&node).as_slice())
+}
+
+fn hex_prefix(nibbles: &[u8], leaf: bool) -> Vec<u8> {
+    let flag = if leaf { 2 } else { 0 };
+    let mut out = Vec::with_capacity(nibbles.len() / 2 + 1);
+    let mut nibbles = nibbles;
+    if nibbles.len() % 2 == 1 {
+        out.push(((flag + 1) << 4) | nibbles[0]);
+        nibbles = &nibbles[1..];
+    } else {
+        out.push(flag << 4);
+    }
+    for pair in nibbles.chunks(2) {
+        out.push((pair[0] << 4) | pair[1]);
+    }
+    out
+}
+
+fn to_nibbles(key: &[u8]) -> Vec<u8> {
+    key.iter().flat_map(|b| [b >> 4, b & 0x0f]).collect()
+}
+
+fn rlp_bytes(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + 4);
+    data.encode(&mut out);
+    out
+}
+
+fn encode_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload_length = items.iter().map(|item| item.len()).sum();
+    let mut out = Vec::with_capacity(payload_length + 4);
+    Header {
+        list: true,
+        payload_length,
+    }
+    .encode(&mut out);
+    for item in items {
+        out.extend_from_slice(item);
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use alloy::primitives::{b256, hex};
+
+    use super::*;
+
+    // the signed transaction of the EIP-155 example
+    const EIP155_TX: [u8; 110] = hex!("f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83");
+
+    fn items(n: u32) -> Vec<Vec<u8>> {
+        (0..n)
+            .map(|idx| [&EIP155_TX[..], &idx.to_be_bytes()].concat())
+            .collect()
+    }
+
+    #[test]
+    fn test_empty_root() {
+        assert_eq!(
+            ordered_trie_root::<&[u8]>(&[]),
+            b256!("56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421")
+        );
+    }
+
+    // the roots are cross-checked with the `HashBuilder` of alloy-trie
+    #[test]
+    fn test_ordered_trie_root() {
+        assert_eq!(
+            ordered_trie_root(&[EIP155_TX]),
+            b256!("36cf58bec935fe50593ac7443cb728dd37dedac603d60fddfae59fd3bdbfcd7f")
+        );
+        assert_eq!(
+            ordered_trie_root(&items(3)),
+            b256!("d8dde5d13df5391646b6494ab750f43a2734cec6ef186c894d9f186f3d8c3c18")
+        );
+        // the keys of the indexes from 128 on are two bytes long
+        assert_eq!(
+            ordered_trie_root(&items(200)),
+            b256!("ae50d9907682a23dd836f61dc4808b64bfee36875c4038d4ce5fcbadaddc6c33")
+        );
+    }
+}
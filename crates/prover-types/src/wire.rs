@@ -0,0 +1,618 @@
+use std::collections::BTreeMap;
+
+use alloy::primitives::{Address, Bloom, Bytes, B256, B64, U256, U64};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    check_data_version, ChainData, LineaData, Pob, PobBlock, PobData, ScrollData, SuccinctPobList,
+};
+
+/// Version of the binary encoding of `Pob` and `SuccinctPobList`.
+///
+/// Layout: a 3-byte magic, the version byte, then the fields in declaration
+/// order. Integers and lengths are LEB128 varints, hashes and addresses are
+/// written as-is, `U256` is a length-prefixed big-endian value without
+/// leading zeros, `Option` is prefixed by a 0/1 flag and `ChainData` by its
+/// chain tag (0: Scroll, 1: Linea).
+pub const POB_WIRE_VERSION: u8 = 2;
+
+const POB_MAGIC: [u8; 3] = *b"POB";
+const POB_LIST_MAGIC: [u8; 3] = *b"SPL";
+
+/// Binary data carried as base64 in JSON-RPC, e.g. the wire format of a
+/// `SuccinctPobList`. `Bytes` would be hex encoded, twice the size.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Base64Bytes(pub Bytes);
+
+impl From<Vec<u8>> for Base64Bytes {
+    fn from(data: Vec<u8>) -> Self {
+        Self(data.into())
+    }
+}
+
+impl std::ops::Deref for Base64Bytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Serialize for Base64Bytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&BASE64_STANDARD.encode(&self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for Base64Bytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = String::deserialize(deserializer)?;
+        let data = BASE64_STANDARD
+            .decode(data)
+            .map_err(serde::de::Error::custom)?;
+        Ok(data.into())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum WireError {
+    UnexpectedEof,
+    InvalidMagic,
+    UnsupportedVersion(u8),
+    UnsupportedDataVersion(u8),
+    InvalidFlag(u8),
+    VarintOverflow,
+    IntegerOverflow,
+    TrailingBytes(usize),
+}
+
+impl Pob<Bytes> {
+    pub fn to_wire(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.wire_len());
+        encode_with_header(&mut out, POB_MAGIC, self);
+        out
+    }
+
+    pub fn wire_len(&self) -> usize {
+        let mut len = WireLen(0);
+        encode_with_header(&mut len, POB_MAGIC, self);
+        len.0
+    }
+
+    pub fn from_wire(data: &[u8]) -> Result<Self, WireError> {
+        decode_with_header(data, POB_MAGIC)
+    }
+}
+
+impl SuccinctPobList {
+    pub fn to_wire(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.wire_len());
+        encode_with_header(&mut out, POB_LIST_MAGIC, self);
+        out
+    }
+
+    pub fn wire_len(&self) -> usize {
+        let mut len = WireLen(0);
+        encode_with_header(&mut len, POB_LIST_MAGIC, self);
+        len.0
+    }
+
+    pub fn from_wire(data: &[u8]) -> Result<Self, WireError> {
+        decode_with_header(data, POB_LIST_MAGIC)
+    }
+}
+
+fn encode_with_header<W: WireSink, T: Wire>(w: &mut W, magic: [u8; 3], val: &T) {
+    w.put(&magic);
+    w.put(&[POB_WIRE_VERSION]);
+    val.encode(w);
+}
+
+fn decode_with_header<T: Wire>(data: &[u8], magic: [u8; 3]) -> Result<T, WireError> {
+    let mut r = WireReader { buf: data };
+    if r.take(magic.len())? != magic {
+        return Err(WireError::InvalidMagic);
+    }
+    let version = r.take(1)?[0];
+    if version != POB_WIRE_VERSION {
+        return Err(WireError::UnsupportedVersion(version));
+    }
+    let val = T::decode(&mut r)?;
+    if !r.buf.is_empty() {
+        return Err(WireError::TrailingBytes(r.buf.len()));
+    }
+    Ok(val)
+}
+
+trait WireSink {
+    fn put(&mut self, data: &[u8]);
+
+    fn put_varint(&mut self, mut val: u64) {
+        let mut buf = [0_u8; 10];
+        let mut n = 0;
+        loop {
+            let b = (val & 0x7f) as u8;
+            val >>= 7;
+            if val == 0 {
+                buf[n] = b;
+                n += 1;
+                break;
+            }
+            buf[n] = b | 0x80;
+            n += 1;
+        }
+        self.put(&buf[..n]);
+    }
+}
+
+impl WireSink for Vec<u8> {
+    fn put(&mut self, data: &[u8]) {
+        self.extend_from_slice(data);
+    }
+}
+
+/// Counts the encoded size without allocating the output.
+struct WireLen(usize);
+
+impl WireSink for WireLen {
+    fn put(&mut self, data: &[u8]) {
+        self.0 += data.len();
+    }
+}
+
+struct WireReader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> WireReader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], WireError> {
+        if self.buf.len() < n {
+            return Err(WireError::UnexpectedEof);
+        }
+        let (data, rest) = self.buf.split_at(n);
+        self.buf = rest;
+        Ok(data)
+    }
+
+    fn varint(&mut self) -> Result<u64, WireError> {
+        let mut val = 0_u64;
+        for i in 0..10 {
+            let b = self.take(1)?[0];
+            if i == 9 && b > 1 {
+                return Err(WireError::VarintOverflow);
+            }
+            val |= ((b & 0x7f) as u64) << (7 * i);
+            if b & 0x80 == 0 {
+                return Ok(val);
+            }
+        }
+        Err(WireError::VarintOverflow)
+    }
+
+    fn len(&mut self) -> Result<usize, WireError> {
+        usize::try_from(self.varint()?).map_err(|_| WireError::IntegerOverflow)
+    }
+}
+
+trait Wire: Sized {
+    /// Lower bound of the encoded size, a list never preallocates more items
+    /// than the remaining input could hold.
+    const MIN_LEN: usize = 1;
+
+    fn encode<W: WireSink>(&self, w: &mut W);
+    fn decode(r: &mut WireReader) -> Result<Self, WireError>;
+}
+
+macro_rules! impl_fixed_wire {
+    ($($ty:ty),*) => {$(
+        impl Wire for $ty {
+            const MIN_LEN: usize = std::mem::size_of::<$ty>();
+
+            fn encode<W: WireSink>(&self, w: &mut W) {
+                w.put(self.as_slice());
+            }
+
+            fn decode(r: &mut WireReader) -> Result<Self, WireError> {
+                Ok(<$ty>::from_slice(r.take(std::mem::size_of::<$ty>())?))
+            }
+        }
+    )*};
+}
+
+impl_fixed_wire!(B256, B64, Address, Bloom);
+
+impl Wire for u64 {
+    fn encode<W: WireSink>(&self, w: &mut W) {
+        w.put_varint(*self);
+    }
+
+    fn decode(r: &mut WireReader) -> Result<Self, WireError> {
+        r.varint()
+    }
+}
+
+impl Wire for usize {
+    fn encode<W: WireSink>(&self, w: &mut W) {
+        w.put_varint(*self as u64);
+    }
+
+    fn decode(r: &mut WireReader) -> Result<Self, WireError> {
+        r.len()
+    }
+}
+
+impl Wire for U64 {
+    fn encode<W: WireSink>(&self, w: &mut W) {
+        w.put_varint(self.to());
+    }
+
+    fn decode(r: &mut WireReader) -> Result<Self, WireError> {
+        Ok(U64::from(r.varint()?))
+    }
+}
+
+impl Wire for U256 {
+    fn encode<W: WireSink>(&self, w: &mut W) {
+        let data = self.to_be_bytes_trimmed_vec();
+        w.put_varint(data.len() as u64);
+        w.put(&data);
+    }
+
+    fn decode(r: &mut WireReader) -> Result<Self, WireError> {
+        let len = r.len()?;
+        U256::try_from_be_slice(r.take(len)?).ok_or(WireError::IntegerOverflow)
+    }
+}
+
+impl Wire for Bytes {
+    fn encode<W: WireSink>(&self, w: &mut W) {
+        w.put_varint(self.len() as u64);
+        w.put(self);
+    }
+
+    fn decode(r: &mut WireReader) -> Result<Self, WireError> {
+        let len = r.len()?;
+        Ok(Bytes::copy_from_slice(r.take(len)?))
+    }
+}
+
+impl<T: Wire> Wire for Option<T> {
+    fn encode<W: WireSink>(&self, w: &mut W) {
+        match self {
+            Some(val) => {
+                w.put(&[1]);
+                val.encode(w);
+            }
+            None => w.put(&[0]),
+        }
+    }
+
+    fn decode(r: &mut WireReader) -> Result<Self, WireError> {
+        match r.take(1)?[0] {
+            0 => Ok(None),
+            1 => Ok(Some(T::decode(r)?)),
+            flag => Err(WireError::InvalidFlag(flag)),
+        }
+    }
+}
+
+impl<T: Wire> Wire for Vec<T> {
+    fn encode<W: WireSink>(&self, w: &mut W) {
+        w.put_varint(self.len() as u64);
+        for item in self {
+            item.encode(w);
+        }
+    }
+
+    fn decode(r: &mut WireReader) -> Result<Self, WireError> {
+        let len = r.len()?;
+        // don't trust the length blindly
+        let mut out = Vec::with_capacity(len.min(r.buf.len() / T::MIN_LEN));
+        for _ in 0..len {
+            out.push(T::decode(r)?);
+        }
+        Ok(out)
+    }
+}
+
+impl Wire for BTreeMap<u64, B256> {
+    fn encode<W: WireSink>(&self, w: &mut W) {
+        w.put_varint(self.len() as u64);
+        for (number, hash) in self {
+            number.encode(w);
+            hash.encode(w);
+        }
+    }
+
+    fn decode(r: &mut WireReader) -> Result<Self, WireError> {
+        let len = r.len()?;
+        let mut out = BTreeMap::new();
+        for _ in 0..len {
+            let number = u64::decode(r)?;
+            out.insert(number, B256::decode(r)?);
+        }
+        Ok(out)
+    }
+}
+
+impl Wire for PobBlock {
+    fn encode<W: WireSink>(&self, w: &mut W) {
+        self.miner.encode(w);
+        self.state_root.encode(w);
+        self.difficulty.encode(w);
+        self.number.encode(w);
+        self.gas_limit.encode(w);
+        self.timestamp.encode(w);
+        self.extra_data.encode(w);
+        self.parent_hash.encode(w);
+        self.uncles_hash.encode(w);
+        self.transactions_root.encode(w);
+        self.receipts_root.encode(w);
+        self.logs_bloom.encode(w);
+        self.nonce.encode(w);
+        self.gas_used.encode(w);
+        self.mix_hash.encode(w);
+        self.base_fee_per_gas.encode(w);
+        self.block_hash.encode(w);
+        self.transactions.encode(w);
+    }
+
+    fn decode(r: &mut WireReader) -> Result<Self, WireError> {
+        Ok(PobBlock {
+            miner: Wire::decode(r)?,
+            state_root: Wire::decode(r)?,
+            difficulty: Wire::decode(r)?,
+            number: Wire::decode(r)?,
+            gas_limit: Wire::decode(r)?,
+            timestamp: Wire::decode(r)?,
+            extra_data: Wire::decode(r)?,
+            parent_hash: Wire::decode(r)?,
+            uncles_hash: Wire::decode(r)?,
+            transactions_root: Wire::decode(r)?,
+            receipts_root: Wire::decode(r)?,
+            logs_bloom: Wire::decode(r)?,
+            nonce: Wire::decode(r)?,
+            gas_used: Wire::decode(r)?,
+            mix_hash: Wire::decode(r)?,
+            base_fee_per_gas: Wire::decode(r)?,
+            block_hash: Wire::decode(r)?,
+            transactions: Wire::decode(r)?,
+        })
+    }
+}
+
+impl<T: Default + Wire> Wire for PobData<T> {
+    fn encode<W: WireSink>(&self, w: &mut W) {
+        w.put(&[self.version]);
+        self.chain_id.encode(w);
+        self.coinbase.encode(w);
+        self.prev_state_root.encode(w);
+        self.block_hashes.encode(w);
+        self.mpt_nodes.encode(w);
+        self.codes.encode(w);
+        self.chain.encode(w);
+    }
+
+    fn decode(r: &mut WireReader) -> Result<Self, WireError> {
+        Ok(PobData {
+            version: check_data_version(r.take(1)?[0])
+                .map_err(WireError::UnsupportedDataVersion)?,
+            chain_id: Wire::decode(r)?,
+            coinbase: Wire::decode(r)?,
+            prev_state_root: Wire::decode(r)?,
+            block_hashes: Wire::decode(r)?,
+            mpt_nodes: Wire::decode(r)?,
+            codes: Wire::decode(r)?,
+            chain: Wire::decode(r)?,
+        })
+    }
+}
+
+impl<T: Wire> Wire for ChainData<T> {
+    fn encode<W: WireSink>(&self, w: &mut W) {
+        match self {
+            ChainData::Scroll(data) => {
+                w.put(&[0]);
+                data.start_l1_queue_index.encode(w);
+                data.withdrawal_root.encode(w);
+            }
+            ChainData::Linea(data) => {
+                w.put(&[1]);
+                data.traces.encode(w);
+                data.proofs.encode(w);
+                data.zkroot.encode(w);
+            }
+        }
+    }
+
+    fn decode(r: &mut WireReader) -> Result<Self, WireError> {
+        match r.take(1)?[0] {
+            0 => Ok(ChainData::Scroll(ScrollData {
+                start_l1_queue_index: Wire::decode(r)?,
+                withdrawal_root: Wire::decode(r)?,
+            })),
+            1 => Ok(ChainData::Linea(LineaData {
+                traces: Wire::decode(r)?,
+                proofs: Wire::decode(r)?,
+                zkroot: Wire::decode(r)?,
+            })),
+            tag => Err(WireError::InvalidFlag(tag)),
+        }
+    }
+}
+
+impl<T: Default + Wire> Wire for Pob<T> {
+    fn encode<W: WireSink>(&self, w: &mut W) {
+        self.block.encode(w);
+        self.data.encode(w);
+        self.hash.encode(w);
+    }
+
+    fn decode(r: &mut WireReader) -> Result<Self, WireError> {
+        Ok(Pob {
+            block: Wire::decode(r)?,
+            data: Wire::decode(r)?,
+            hash: Wire::decode(r)?,
+        })
+    }
+}
+
+impl Wire for SuccinctPobList {
+    fn encode<W: WireSink>(&self, w: &mut W) {
+        self.pob.encode(w);
+        self.interning.encode(w);
+        self.hash.encode(w);
+    }
+
+    fn decode(r: &mut WireReader) -> Result<Self, WireError> {
+        Ok(SuccinctPobList {
+            pob: Wire::decode(r)?,
+            interning: Wire::decode(r)?,
+            hash: Wire::decode(r)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::POB_DATA_VERSION;
+
+    fn sample_pob(number: u64) -> Pob {
+        let block = PobBlock {
+            miner: Address::repeat_byte(1),
+            state_root: B256::repeat_byte(2),
+            difficulty: U256::from(2),
+            number: U64::from(number),
+            gas_limit: U64::from(30_000_000),
+            timestamp: U64::from(1_700_000_000),
+            extra_data: vec![0xab; 97].into(),
+            parent_hash: B256::repeat_byte(3),
+            logs_bloom: Bloom::repeat_byte(4),
+            gas_used: U256::from(21000),
+            base_fee_per_gas: Some(U256::from(7)),
+            block_hash: Some(B256::repeat_byte(5)),
+            transactions: vec![vec![0x02, 0xf8, 0x01].into(), Bytes::new()],
+            ..Default::default()
+        };
+        let data = PobData {
+            version: POB_DATA_VERSION,
+            chain_id: 59144,
+            coinbase: Some(Address::repeat_byte(6)),
+            prev_state_root: B256::repeat_byte(9),
+            block_hashes: BTreeMap::from([(number - 1, B256::repeat_byte(7))]),
+            mpt_nodes: vec![vec![1, 2, 3].into(), vec![4; 300].into()],
+            codes: vec![vec![0x60, 0x80].into()],
+            chain: ChainData::Linea(LineaData {
+                traces: vec![b"{}".to_vec().into()],
+                proofs: vec![],
+                zkroot: B256::repeat_byte(8),
+            }),
+        };
+        Pob::new(block, data)
+    }
+
+    #[test]
+    fn test_pob_roundtrip() {
+        let pob = sample_pob(100);
+        let wire = pob.to_wire();
+        assert_eq!(wire.len(), pob.wire_len());
+        let decoded = Pob::from_wire(&wire).unwrap();
+        assert_eq!(
+            serde_json::to_value(&pob).unwrap(),
+            serde_json::to_value(&decoded).unwrap()
+        );
+        assert_eq!(decoded.pob_hash(), pob.hash);
+    }
+
+    #[test]
+    fn test_succinct_pob_list_roundtrip() {
+        let list = SuccinctPobList::compress(&[sample_pob(100), sample_pob(101)]);
+        let json = serde_json::to_vec(&list).unwrap();
+        let wire = list.to_wire();
+        assert!(wire.len() < json.len());
+
+        let decoded = SuccinctPobList::from_wire(&wire).unwrap();
+        assert_eq!(json, serde_json::to_vec(&decoded).unwrap());
+        let from_json: SuccinctPobList = serde_json::from_slice(&json).unwrap();
+        assert_eq!(wire, from_json.to_wire());
+    }
+
+    #[test]
+    fn test_reject_malformed() {
+        let wire = sample_pob(100).to_wire();
+        assert!(matches!(
+            SuccinctPobList::from_wire(&wire),
+            Err(WireError::InvalidMagic)
+        ));
+        assert!(matches!(
+            Pob::from_wire(&wire[..wire.len() - 1]),
+            Err(WireError::UnexpectedEof)
+        ));
+
+        let mut trailing = wire.clone();
+        trailing.push(0);
+        assert!(matches!(
+            Pob::from_wire(&trailing),
+            Err(WireError::TrailingBytes(1))
+        ));
+
+        let mut version = wire;
+        version[3] = POB_WIRE_VERSION + 1;
+        assert!(matches!(
+            Pob::from_wire(&version),
+            Err(WireError::UnsupportedVersion(_))
+        ));
+    }
+
+    #[test]
+    fn test_pob_data_version() {
+        let mut data = sample_pob(100).data;
+        data.version = 0;
+        let mut buf = Vec::new();
+        data.encode(&mut buf);
+        let decoded = PobData::<Bytes>::decode(&mut WireReader { buf: &buf }).unwrap();
+        // the version is kept, the hash depends on it
+        assert_eq!(decoded.version, 0);
+        assert_eq!(decoded.hash(), data.hash());
+
+        data.version = POB_DATA_VERSION + 1;
+        let mut buf = Vec::new();
+        data.encode(&mut buf);
+        assert!(matches!(
+            PobData::<Bytes>::decode(&mut WireReader { buf: &buf }),
+            Err(WireError::UnsupportedDataVersion(2))
+        ));
+    }
+
+    #[test]
+    fn test_reject_hostile_length() {
+        // a list of 2^62 hashes followed by a single one
+        let mut data = Vec::new();
+        data.put_varint(1 << 62);
+        B256::repeat_byte(1).encode(&mut data);
+        let mut r = WireReader { buf: &data };
+        assert!(matches!(
+            Vec::<B256>::decode(&mut r),
+            Err(WireError::UnexpectedEof)
+        ));
+
+        let mut data = Vec::new();
+        data.put_varint(u64::MAX);
+        let mut r = WireReader { buf: &data };
+        assert!(matches!(
+            Vec::<Bytes>::decode(&mut r),
+            Err(WireError::UnexpectedEof)
+        ));
+    }
+
+    #[test]
+    fn test_base64_bytes_json() {
+        let data = Base64Bytes::from(vec![0xfb, 0xff, 0x00, 0x01]);
+        let json = serde_json::to_string(&data).unwrap();
+        assert_eq!(json, "\"+/8AAQ==\"");
+        assert_eq!(serde_json::from_str::<Base64Bytes>(&json).unwrap(), data);
+        assert!(serde_json::from_str::<Base64Bytes>("\"0x00\"").is_err());
+    }
+}
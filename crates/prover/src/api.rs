@@ -3,9 +3,13 @@ use std::sync::Arc;
 use std::time::Instant;
 
 use crate::types::{DaApiServer, ProverV1ApiServer, ProverV2ApiServer};
-use crate::{Collector, DaItemLockStatus, DaManager, Metadata, TaskManager, BUILD_TAG};
+use crate::{
+    AttestationMode, CacheKey, ChainMetadata, ChainRegistry, ChainVerifier, Collector,
+    DaItemLockStatus, DaManager, Metadata, NodePool, PoeVerification, ProverLimits, ProverLoad,
+    ServerConfig, TaskManager, UploadManager, BUILD_TAG, NODE_EXPIRED_SECS, UPLOAD_EXPIRED_SECS,
+};
 
-use alloy::primitives::Bytes;
+use alloy::primitives::{Address, Bytes};
 use async_trait::async_trait;
 use automata_sgx_sdk::dcap::dcap_quote;
 use base::format::debug;
@@ -14,14 +18,14 @@ use base::eth::{Eth, Keypair};
 use jsonrpsee::core::RpcResult;
 use jsonrpsee::types::{ErrorObject, ErrorObjectOwned};
 use jsonrpsee::RpcModule;
-use linea_verifier::LineaBatchVerifier;
 use prover_types::{
-    keccak_encode, poe_digest, Pob, Poe, PoeResponse, ProveTaskParams, SuccinctPobList, TaskType, B256
+    aggregated_poe_digest, pob_list_hash, poe_digest, AggregatedPoeResponse, Base64Bytes,
+    DeltaPobList, InterningCodec, Pob, Poe, PoeResponse, ProveTaskParams, SuccinctPobList,
+    TaskType, B256, POB_WIRE_VERSION,
 };
 use scroll_da_codec::BatchTask;
-use scroll_verifier::ScrollBatchVerifier;
 
-const POB_EXPIRED_SECS: u64 = 120;
+pub const POB_EXPIRED_SECS: u64 = 120;
 
 #[derive(Clone)]
 pub struct ProverApi {
@@ -30,13 +34,17 @@ pub struct ProverApi {
     pub force_with_context: bool,
     pub l1_el: Option<Eth>,
     pub task_mgr: Arc<TaskManager<BatchTask, Poe, String>>,
-    pub pobda_task_mgr: Arc<TaskManager<(u64, u64, u64, B256), Poe, String>>,
+    pub pobda_task_mgr: Arc<TaskManager<CacheKey, Poe, String>>,
     pub pob_da: Arc<DaManager<Vec<Pob>>>,
+    pub node_pool: Arc<NodePool>,
+    pub uploads: Arc<UploadManager>,
     pub metrics: Arc<Collector>,
     pub keypair: Keypair,
-
-    pub scroll: ScrollBatchVerifier,
-    pub linea: LineaBatchVerifier,
+    pub pob_codec: InterningCodec,
+    pub chains: Arc<ChainRegistry>,
+    /// The chain served by the v1 API, which has no chain id parameter.
+    pub scroll_chain_id: Option<u64>,
+    pub server: ServerConfig,
 }
 
 fn jsonrpc_err<E>(code: i32) -> impl Fn(E) -> ErrorObjectOwned
@@ -58,6 +66,32 @@ impl ProverApi {
     pub fn err<M: Into<String>>(&self, code: i32, msg: M) -> ErrorObjectOwned {
         ErrorObject::owned(code, msg, None::<()>)
     }
+
+    fn chain(&self, ty: TaskType, chain_id: Option<u64>) -> RpcResult<&Arc<dyn ChainVerifier>> {
+        self.chains.get(ty, chain_id).ok_or_else(|| match chain_id {
+            Some(chain_id) => self.err(
+                14005,
+                format!("unknown chain: {:?}, chain_id={}", ty, chain_id),
+            ),
+            None if self.chains.contains(ty) => {
+                self.err(14005, format!("chain_id is required for {:?}", ty))
+            }
+            None => self.err(14005, format!("unknown task: {:?}", ty)),
+        })
+    }
+
+    fn check_chain_id(&self, pob_list: &[Pob], chain_id: u64) -> RpcResult<()> {
+        match pob_list.iter().find(|pob| pob.data.chain_id != chain_id) {
+            Some(pob) => Err(self.err(
+                14019,
+                format!(
+                    "unexpected chain_id in block {}: expect {}, got {}",
+                    pob.block.number, chain_id, pob.data.chain_id
+                ),
+            )),
+            None => Ok(()),
+        }
+    }
 }
 
 #[async_trait]
@@ -101,50 +135,8 @@ impl ProverV1ApiServer for ProverApi {
 #[async_trait]
 impl ProverV2ApiServer for ProverApi {
     async fn prove_task(&self, params: ProveTaskParams) -> RpcResult<PoeResponse> {
-        let ty = TaskType::from_opu64(params.task_type);
-
-        let pob_list = self
-            .pob_da
-            .get(&params.pob_hash)
-            .ok_or(self.err(14006, format!("pob_hash not found: {:?}", params.pob_hash)))?;
-
-        let cache_key = match ty {
-            TaskType::Scroll => self
-                .scroll
-                .cache_key(params.batch().map_err(jsonrpc_err(14001))?, params.pob_hash)
-                .map_err(jsonrpc_err(14001))?,
-            TaskType::Linea => self.linea.cache_key(&params).map_err(jsonrpc_err(14001))?,
-            TaskType::Other(_) => unreachable!(),
-        };
-
-        let poe = match self.pobda_task_mgr.process_task(cache_key.clone()).await {
-            Some(poe) => poe,
-            None => {
-                let start = Instant::now();
-                let result = match ty {
-                    TaskType::Scroll => self
-                        .scroll
-                        .prove(pob_list.as_slice(), params.batch().map_err(jsonrpc_err(14001))?)
-                        .await
-                        .map_err(debug),
-                    TaskType::Linea => self.linea.prove(&pob_list, params).await.map_err(debug),
-                    TaskType::Other(_) => unreachable!(),
-                };
-                self.pobda_task_mgr
-                    .update_task(cache_key.clone(), result.clone())
-                    .await;
-                self.metrics
-                    .gauge_prove_ms
-                    .lock()
-                    .unwrap()
-                    .set([ty.name()], start.elapsed().as_millis() as _);
-                result
-            }
-        }
-        .map_err(jsonrpc_err(15001))?;
-        self.metrics.counter_prove.lock().unwrap().inc([ty.name()]);
-
-        let sig = Keypair::sign_digest_ecdsa(&self.keypair.secret_key(), poe_digest(&poe).into());
+        let (cache_key, poe) = self.prove_poe(params).await?;
+        let sig = self.sign_digest(poe_digest(&poe));
 
         Ok(PoeResponse {
             not_ready: false,
@@ -152,7 +144,85 @@ impl ProverV2ApiServer for ProverApi {
             start_block: cache_key.1,
             end_block: cache_key.2,
             poe: Some(poe),
-            poe_signature: Some(sig.to_vec().into()),
+            poe_signature: Some(sig),
+        })
+    }
+
+    async fn prove_aggregated_task(
+        &self,
+        args: Vec<ProveTaskParams>,
+    ) -> RpcResult<AggregatedPoeResponse> {
+        let ty = match args.first() {
+            Some(params) => TaskType::from_opu64(params.task_type),
+            None => return Err(self.err(14013, "empty task list")),
+        };
+
+        let chain_id = self.task_pob_list(&args[0])?.1;
+
+        let mut batch_ids = Vec::with_capacity(args.len());
+        let mut reports: Vec<Poe> = Vec::with_capacity(args.len());
+        let mut block_range: Option<(u64, u64)> = None;
+        for params in args {
+            let task_ty = TaskType::from_opu64(params.task_type);
+            if task_ty != ty {
+                return Err(self.err(
+                    14013,
+                    format!("mixed task types: {:?} and {:?}", ty, task_ty),
+                ));
+            }
+            let task_chain_id = self.task_pob_list(&params)?.1;
+            if task_chain_id != chain_id {
+                return Err(self.err(
+                    14013,
+                    format!("mixed chain ids: {:?} and {:?}", chain_id, task_chain_id),
+                ));
+            }
+            if let (TaskType::Scroll, Some(prev)) = (ty, reports.last()) {
+                let batch = BatchTask::from_calldata(params.batch().map_err(jsonrpc_err(14001))?)
+                    .map_err(jsonrpc_err(14001))?;
+                let parent_hash = batch.parent_batch_header.hash();
+                if parent_hash != prev.batch_hash {
+                    return Err(self.err(
+                        14013,
+                        format!(
+                            "batch {} is not the child of the previous batch: parent={:?}, previous={:?}",
+                            batch.id(),
+                            parent_hash,
+                            prev.batch_hash
+                        ),
+                    ));
+                }
+            }
+
+            let (cache_key, poe) = self.prove_poe(params).await?;
+            block_range = match block_range {
+                Some((start, end)) if end + 1 != cache_key.1 => {
+                    return Err(self.err(
+                        14013,
+                        format!(
+                            "block range is not contiguous: {}..={} and {}..={}",
+                            start, end, cache_key.1, cache_key.2
+                        ),
+                    ));
+                }
+                Some((start, _)) => Some((start, cache_key.2)),
+                None => Some((cache_key.1, cache_key.2)),
+            };
+            batch_ids.push(cache_key.0);
+            reports.push(poe);
+        }
+
+        let poe = Poe::aggregate(&reports).map_err(jsonrpc_err(14013))?;
+        let (start_block, end_block) = block_range.unwrap_or_default();
+        let sig = self.sign_digest(aggregated_poe_digest(&poe, start_block, end_block));
+
+        Ok(AggregatedPoeResponse {
+            batch_ids,
+            batch_hashes: reports.iter().map(|poe| poe.batch_hash).collect(),
+            start_block,
+            end_block,
+            poe,
+            poe_signature: sig,
         })
     }
 
@@ -170,36 +240,32 @@ impl ProverV2ApiServer for ProverApi {
         start_block: u64,
         end_block: u64,
         ty: u64,
+        chain_id: Option<u64>,
     ) -> RpcResult<SuccinctPobList> {
         let ty = TaskType::from_u64(ty);
 
         let start = Instant::now();
-        let result = match ty {
-            TaskType::Scroll => self
-                .scroll
-                .generate_context(start_block, end_block)
-                .await
-                .map_err(jsonrpc_err(14004))?,
-            TaskType::Linea => self
-                .linea
-                .generate_context(start_block, end_block)
-                .await
-                .map_err(jsonrpc_err(14004))?,
-            TaskType::Other(_) => return Err(self.err(14005, format!("unknown task: {:?}", ty))),
-        };
+        let result = self
+            .chain(ty, chain_id)?
+            .generate_context(start_block, end_block)
+            .await
+            .map_err(jsonrpc_err(14004))?;
+        if let Some(chain_id) = chain_id {
+            self.check_chain_id(&result, chain_id)?;
+        }
 
-        let pob_list = SuccinctPobList::compress(&result);
+        let pob_list = SuccinctPobList::compress_with(&result, self.pob_codec);
         let gen_ctx_time = start.elapsed().as_millis() as f64;
 
         self.pob_da
             .put(pob_list.hash, Arc::new(result), POB_EXPIRED_SECS);
 
-        let data = serde_json::to_vec(&pob_list).unwrap();
+        // measured on the wire encoding, without serializing the list
         self.metrics
             .pob_size
             .lock()
             .unwrap()
-            .set([ty.name()], data.len() as _);
+            .set([ty.name()], pob_list.wire_len() as _);
         self.metrics
             .counter_gen_ctx
             .lock()
@@ -213,13 +279,34 @@ impl ProverV2ApiServer for ProverApi {
         Ok(pob_list)
     }
 
+    async fn generate_context_wire(
+        &self,
+        start_block: u64,
+        end_block: u64,
+        ty: u64,
+        chain_id: Option<u64>,
+    ) -> RpcResult<Base64Bytes> {
+        let pob_list = self
+            .generate_context(start_block, end_block, ty, chain_id)
+            .await?;
+        Ok(pob_list.to_wire().into())
+    }
+
     async fn metadata(&self) -> RpcResult<Metadata> {
         let mut task_with_context = BTreeMap::new();
-        task_with_context.insert(
-            TaskType::Scroll.u64(),
-            self.force_with_context || self.scroll.with_context(),
-        );
-        task_with_context.insert(TaskType::Linea.u64(), self.force_with_context || true);
+        let mut chains = Vec::new();
+        for (ty, chain_id, chain) in self.chains.iter() {
+            let with_context = self.force_with_context || chain.with_context();
+            // a task type requires the context if any of its chains does
+            *task_with_context.entry(ty.u64()).or_insert(false) |= with_context;
+            chains.push(ChainMetadata {
+                task_type: ty.u64(),
+                chain_id,
+                with_context,
+                hardforks: chain_id.map(|id| chain.hardforks(id)).unwrap_or_default(),
+                batch_versions: chain.batch_versions(),
+            });
+        }
         Ok(Metadata {
             with_context: task_with_context
                 .get(&TaskType::Scroll.u64())
@@ -227,22 +314,100 @@ impl ProverV2ApiServer for ProverApi {
                 .unwrap_or(true),
             task_with_context,
             version: BUILD_TAG.unwrap_or("v0.1.0"),
+            pob_wire_version: POB_WIRE_VERSION,
+            chains,
+            signer: self.keypair.address(),
+            attestation: AttestationMode::current(),
+            limits: ProverLimits {
+                body_limit: self.server.body_limit,
+                upload_limit: self.uploads.limit() as _,
+                upload_budget: self.uploads.budget() as _,
+                max_uploads: self.uploads.max_uploads(),
+                task_queue_size: self.pobda_task_mgr.cap(),
+                pob_ttl_secs: POB_EXPIRED_SECS,
+                node_ttl_secs: NODE_EXPIRED_SECS,
+                upload_ttl_secs: UPLOAD_EXPIRED_SECS,
+                max_pool_nodes: self.node_pool.capacity(),
+            },
+            load: ProverLoad {
+                pending_tasks: self.pobda_task_mgr.pending().await,
+                pob_lists: self.pob_da.len(),
+                pool_nodes: self.node_pool.len(),
+                uploads: self.uploads.len(),
+            },
+        })
+    }
+
+    async fn verify_poe(
+        &self,
+        poe: PoeResponse,
+        signer: Option<Address>,
+    ) -> RpcResult<PoeVerification> {
+        let expected_signer = signer.unwrap_or_else(|| self.keypair.address());
+        let recovered = poe.recover_signer().map_err(jsonrpc_err(14012))?;
+        Ok(PoeVerification {
+            valid: recovered == expected_signer,
+            signer: recovered,
+            expected_signer,
         })
     }
 }
 
 #[async_trait]
 impl DaApiServer for ProverApi {
-    async fn da_put_pob(&self, arg: SuccinctPobList) -> RpcResult<()> {
-        let pob_list = arg.unwrap();
-        let pob_hash = keccak_encode(|hash| {
-            for pob in &pob_list {
-                hash(pob.pob_hash().as_slice());
-            }
-        });
-        self.pob_da
-            .put(pob_hash.into(), Arc::new(pob_list), POB_EXPIRED_SECS);
-        Ok(())
+    async fn da_put_pob(&self, arg: SuccinctPobList) -> RpcResult<B256> {
+        let pob_list = arg.verify_unwrap().map_err(jsonrpc_err(14018))?;
+        Ok(self.put_pob_list(pob_list))
+    }
+
+    async fn da_put_pob_wire(&self, arg: Base64Bytes) -> RpcResult<B256> {
+        let pob_list = SuccinctPobList::from_wire(&arg).map_err(jsonrpc_err(14014))?;
+        self.da_put_pob(pob_list).await
+    }
+
+    async fn da_put_pob_delta(&self, arg: DeltaPobList) -> RpcResult<B256> {
+        let pob_list = arg
+            .unwrap(|hash| self.node_pool.get(hash))
+            .map_err(jsonrpc_err(14015))?;
+        Ok(self.put_pob_list(pob_list))
+    }
+
+    async fn da_missing_nodes(&self, arg: Vec<B256>) -> RpcResult<Vec<B256>> {
+        Ok(self.node_pool.missing(&arg))
+    }
+
+    async fn da_begin_pob_upload(
+        &self,
+        pob_hash: B256,
+        parts: Vec<B256>,
+        size: u64,
+    ) -> RpcResult<()> {
+        self.uploads
+            .begin(pob_hash, parts, size)
+            .map_err(jsonrpc_err(14016))
+    }
+
+    async fn da_append_pob_upload(
+        &self,
+        pob_hash: B256,
+        index: u64,
+        data: Base64Bytes,
+    ) -> RpcResult<()> {
+        self.uploads
+            .append(pob_hash, index, data.0)
+            .map_err(jsonrpc_err(14016))
+    }
+
+    async fn da_commit_pob_upload(&self, pob_hash: B256) -> RpcResult<B256> {
+        let data = self.uploads.commit(&pob_hash).map_err(jsonrpc_err(14016))?;
+        let pob_list = SuccinctPobList::from_wire(&data).map_err(jsonrpc_err(14014))?;
+        if pob_list.hash != pob_hash {
+            return Err(self.err(
+                14017,
+                format!("pob_hash mismatch: expect {:?}, got {:?}", pob_hash, pob_list.hash),
+            ));
+        }
+        self.da_put_pob(pob_list).await
     }
 
     async fn da_try_lock(&self, arg: B256) -> RpcResult<DaItemLockStatus> {
@@ -251,6 +416,70 @@ impl DaApiServer for ProverApi {
 }
 
 impl ProverApi {
+    fn put_pob_list(&self, pob_list: Vec<Pob>) -> B256 {
+        let pob_list = self.node_pool.intern(pob_list);
+        let pob_hash = pob_list_hash(&pob_list);
+        self.pob_da.put(pob_hash, Arc::new(pob_list), POB_EXPIRED_SECS);
+        pob_hash
+    }
+
+    fn sign_digest(&self, digest: B256) -> Bytes {
+        let sig = Keypair::sign_digest_ecdsa(&self.keypair.secret_key(), digest.into());
+        sig.to_vec().into()
+    }
+
+    /// The PoB list of a task and the chain it is proven on: the chain id of
+    /// the params, or else the one of the PoBs.
+    fn task_pob_list(
+        &self,
+        params: &ProveTaskParams,
+    ) -> RpcResult<(Arc<Vec<Pob>>, Option<u64>)> {
+        let pob_list = self
+            .pob_da
+            .get(&params.pob_hash)
+            .ok_or(self.err(14006, format!("pob_hash not found: {:?}", params.pob_hash)))?;
+        let chain_id = params
+            .chain_id
+            .or_else(|| pob_list.first().map(|pob| pob.data.chain_id));
+        Ok((pob_list, chain_id))
+    }
+
+    async fn prove_poe(
+        &self,
+        params: ProveTaskParams,
+    ) -> RpcResult<(CacheKey, Poe)> {
+        let ty = TaskType::from_opu64(params.task_type);
+
+        let (pob_list, chain_id) = self.task_pob_list(&params)?;
+        if let Some(chain_id) = chain_id {
+            self.check_chain_id(&pob_list, chain_id)?;
+        }
+        let chain = self.chain(ty, chain_id)?;
+
+        let cache_key = chain.cache_key(&params).map_err(jsonrpc_err(14001))?;
+
+        let poe = match self.pobda_task_mgr.process_task(cache_key.clone()).await {
+            Some(poe) => poe,
+            None => {
+                let start = Instant::now();
+                let result = chain.prove(&pob_list, params).await.map_err(debug);
+                self.pobda_task_mgr
+                    .update_task(cache_key.clone(), result.clone())
+                    .await;
+                self.metrics
+                    .gauge_prove_ms
+                    .lock()
+                    .unwrap()
+                    .set([ty.name()], start.elapsed().as_millis() as _);
+                result
+            }
+        }
+        .map_err(jsonrpc_err(15001))?;
+        self.metrics.counter_prove.lock().unwrap().inc([ty.name()]);
+
+        Ok((cache_key, poe))
+    }
+
     async fn prove_task_with_sample(
         &self,
         tx_hash: B256,
@@ -288,13 +517,11 @@ impl ProverApi {
                 batch_task.start().unwrap(),
                 batch_task.end().unwrap(),
                 ty.u64(),
+                self.scroll_chain_id,
             )
             .await?;
 
-        println!(
-            "context size: {}",
-            serde_json::to_vec(&pob_list).unwrap().len()
-        );
+        println!("context size: {}", pob_list.wire_len());
 
         let poe = self
             .prove_task(ProveTaskParams {
@@ -305,6 +532,7 @@ impl ProverApi {
                 starting_state_root: None,
                 final_state_root: None,
                 task_type: Some(ty.u64()),
+                chain_id: self.scroll_chain_id,
                 from: None,
             })
             .await?;
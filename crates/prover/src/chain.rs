@@ -0,0 +1,297 @@
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use linea_verifier::LineaBatchVerifier;
+use prover_types::{
+    verify_block_headers, BasePob, HeaderError, ParamsError, Pob, Poe, ProveTaskParams, TaskType,
+    B256,
+};
+use scroll_da_codec::BatchTask;
+use scroll_verifier::{HardforkConfig, ScrollBatchVerifier};
+
+use crate::HardforkMetadata;
+
+/// DA batch versions supported by the Scroll verifier.
+pub const SCROLL_BATCH_VERSIONS: [u8; 5] = [0, 1, 2, 3, 4];
+
+/// `(batch_id, start_block, end_block, pob_hash)`
+pub type CacheKey = (u64, u64, u64, B256);
+
+/// The verifiers keep their own error types, the API layer only reports
+/// them.
+pub type ChainError = Box<dyn Debug + Send + Sync>;
+
+fn chain_err<E: Debug + Send + Sync + 'static>(err: E) -> ChainError {
+    Box::new(err)
+}
+
+/// Why the prover rejects a Scroll PoB list before it reaches the verifier.
+#[derive(Debug)]
+pub enum ScrollPobError {
+    EmptyBatch,
+    UnexpectedChain { number: u64 },
+    Params(ParamsError),
+    Header(HeaderError),
+}
+
+/// The PoB list has to cover the blocks of the batch in order, each block
+/// starting from the state root its predecessor ended with, and the block
+/// fields have to match the header hashes.
+fn check_scroll_pob_list(
+    params: &ProveTaskParams,
+    batch: &BatchTask,
+    pob_list: &[Pob],
+) -> Result<(), ScrollPobError> {
+    let range = match (batch.start(), batch.end()) {
+        (Some(start), Some(end)) => (start, end),
+        _ => return Err(ScrollPobError::EmptyBatch),
+    };
+    if let Some(pob) = pob_list
+        .iter()
+        .find(|pob| pob.data.task_type() != TaskType::Scroll)
+    {
+        return Err(ScrollPobError::UnexpectedChain {
+            number: pob.block.number.to(),
+        });
+    }
+    params
+        .check_pob_list(pob_list, range, |pob| pob.block.state_root)
+        .map_err(ScrollPobError::Params)?;
+    verify_block_headers(pob_list).map_err(ScrollPobError::Header)
+}
+
+/// A rollup the prover can generate contexts for and prove batches of.
+#[async_trait]
+pub trait ChainVerifier: Send + Sync {
+    fn cache_key(&self, params: &ProveTaskParams) -> Result<CacheKey, ChainError>;
+
+    async fn generate_context(
+        &self,
+        start_block: u64,
+        end_block: u64,
+    ) -> Result<Vec<Pob>, ChainError>;
+
+    async fn prove(&self, pob_list: &[Pob], params: ProveTaskParams) -> Result<Poe, ChainError>;
+
+    /// Whether the tasks have to come with a PoB list, i.e. the prover
+    /// can't generate the context itself.
+    fn with_context(&self) -> bool;
+
+    /// The hardforks of the chain in activation order, empty if unknown.
+    fn hardforks(&self, _chain_id: u64) -> Vec<HardforkMetadata> {
+        Vec::new()
+    }
+
+    /// The DA batch versions `prove` accepts, empty if the chain has none.
+    fn batch_versions(&self) -> Vec<u8> {
+        Vec::new()
+    }
+}
+
+#[async_trait]
+impl ChainVerifier for ScrollBatchVerifier {
+    fn cache_key(&self, params: &ProveTaskParams) -> Result<CacheKey, ChainError> {
+        let batch = params.batch().map_err(chain_err)?;
+        ScrollBatchVerifier::cache_key(self, batch, params.pob_hash).map_err(chain_err)
+    }
+
+    async fn generate_context(
+        &self,
+        start_block: u64,
+        end_block: u64,
+    ) -> Result<Vec<Pob>, ChainError> {
+        let pob_list = ScrollBatchVerifier::generate_context(self, start_block, end_block)
+            .await
+            .map_err(chain_err)?;
+        Ok(pob_list.into_iter().map(Pob::from).collect())
+    }
+
+    async fn prove(&self, pob_list: &[Pob], params: ProveTaskParams) -> Result<Poe, ChainError> {
+        let batch = params.batch().map_err(chain_err)?;
+        let task = BatchTask::from_calldata(batch).map_err(chain_err)?;
+        check_scroll_pob_list(&params, &task, pob_list).map_err(chain_err)?;
+        // the verifier is built on the PoB types of `base`
+        let base_list = pob_list.iter().map(BasePob::from).collect::<Vec<_>>();
+        let poe = ScrollBatchVerifier::prove(self, &base_list, batch)
+            .await
+            .map_err(chain_err)?;
+        // it doesn't commit to the witness of the blocks, chain their
+        // commitments the way `Poe::merge` does for Linea
+        let mut poe: Poe = poe.into();
+        poe.state_hash = Poe::chain_state_hash(pob_list.iter().map(Pob::state_commitment));
+        Ok(poe)
+    }
+
+    fn with_context(&self) -> bool {
+        ScrollBatchVerifier::with_context(self)
+    }
+
+    /// The config only exposes the batch version of a block, the
+    /// activations are searched from it: bernoulli and curie bump the
+    /// version by block number, darwin and darwin_v2 by timestamp.
+    fn hardforks(&self, chain_id: u64) -> Vec<HardforkMetadata> {
+        let config = HardforkConfig::default_from_chain_id(chain_id);
+        let mut out = vec![HardforkMetadata {
+            name: "pre_bernoulli".into(),
+            block: Some(0),
+            timestamp: None,
+        }];
+        for (name, version) in [("bernoulli", 1), ("curie", 2)] {
+            if let Some(block) = first_activated(version, |n| config.batch_version(n, 0)) {
+                out.push(HardforkMetadata {
+                    name: name.into(),
+                    block: Some(block),
+                    timestamp: None,
+                });
+            }
+        }
+        for (name, version) in [("darwin", 3), ("darwin_v2", 4)] {
+            if let Some(timestamp) = first_activated(version, |t| config.batch_version(u64::MAX, t))
+            {
+                out.push(HardforkMetadata {
+                    name: name.into(),
+                    block: None,
+                    timestamp: Some(timestamp),
+                });
+            }
+        }
+        out
+    }
+
+    fn batch_versions(&self) -> Vec<u8> {
+        SCROLL_BATCH_VERSIONS.to_vec()
+    }
+}
+
+/// The smallest `n` with `version(n) >= target`, `version` must be
+/// non-decreasing.
+fn first_activated<F: Fn(u64) -> u8>(target: u8, version: F) -> Option<u64> {
+    if version(u64::MAX) < target {
+        return None;
+    }
+    let (mut lo, mut hi) = (0, u64::MAX);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if version(mid) >= target {
+            hi = mid;
+        } else {
+            lo = mid + 1;
+        }
+    }
+    Some(lo)
+}
+
+#[async_trait]
+impl ChainVerifier for LineaBatchVerifier {
+    fn cache_key(&self, params: &ProveTaskParams) -> Result<CacheKey, ChainError> {
+        LineaBatchVerifier::cache_key(self, params).map_err(chain_err)
+    }
+
+    async fn generate_context(
+        &self,
+        start_block: u64,
+        end_block: u64,
+    ) -> Result<Vec<Pob>, ChainError> {
+        LineaBatchVerifier::generate_context(self, start_block, end_block)
+            .await
+            .map_err(chain_err)
+    }
+
+    async fn prove(&self, pob_list: &[Pob], params: ProveTaskParams) -> Result<Poe, ChainError> {
+        LineaBatchVerifier::prove(self, pob_list, params)
+            .await
+            .map_err(chain_err)
+    }
+
+    fn with_context(&self) -> bool {
+        true
+    }
+
+    fn hardforks(&self, _chain_id: u64) -> Vec<HardforkMetadata> {
+        // every Linea block is executed with the London rules
+        vec![HardforkMetadata {
+            name: "london".into(),
+            block: Some(0),
+            timestamp: None,
+        }]
+    }
+}
+
+/// The chain verifiers keyed by task type and chain id, registered at
+/// startup. A verifier registered without a chain id (from the legacy
+/// single-chain config) serves any chain of its task type.
+#[derive(Clone, Default)]
+pub struct ChainRegistry {
+    chains: BTreeMap<(u64, Option<u64>), Arc<dyn ChainVerifier>>,
+}
+
+impl ChainRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns false if a verifier is already registered for the chain.
+    pub fn register<V>(&mut self, ty: TaskType, chain_id: Option<u64>, verifier: V) -> bool
+    where
+        V: ChainVerifier + 'static,
+    {
+        let key = (ty.u64(), chain_id);
+        if self.chains.contains_key(&key) {
+            return false;
+        }
+        self.chains.insert(key, Arc::new(verifier));
+        true
+    }
+
+    pub fn contains(&self, ty: TaskType) -> bool {
+        self.iter().any(|(chain_ty, _, _)| chain_ty == ty)
+    }
+
+    /// Without a chain id, the task type must only be served by one
+    /// verifier.
+    pub fn get(&self, ty: TaskType, chain_id: Option<u64>) -> Option<&Arc<dyn ChainVerifier>> {
+        match chain_id {
+            Some(chain_id) => self
+                .chains
+                .get(&(ty.u64(), Some(chain_id)))
+                .or_else(|| self.chains.get(&(ty.u64(), None))),
+            None => {
+                let mut chains = self
+                    .iter()
+                    .filter(|(chain_ty, _, _)| *chain_ty == ty)
+                    .map(|(_, _, verifier)| verifier);
+                match (chains.next(), chains.next()) {
+                    (Some(verifier), None) => Some(verifier),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (TaskType, Option<u64>, &Arc<dyn ChainVerifier>)> {
+        self.chains
+            .iter()
+            .map(|((ty, chain_id), verifier)| (TaskType::from_u64(*ty), *chain_id, verifier))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_first_activated() {
+        let version = |n: u64| match n {
+            n if n < 100 => 0,
+            n if n < 1_700_000_000 => 1,
+            _ => 2,
+        };
+        assert_eq!(first_activated(0, version), Some(0));
+        assert_eq!(first_activated(1, version), Some(100));
+        assert_eq!(first_activated(2, version), Some(1_700_000_000));
+        assert_eq!(first_activated(3, version), None);
+        assert_eq!(first_activated(1, |_| 1), Some(0));
+    }
+}
@@ -3,13 +3,21 @@ use base::time::Time;
 use prover_types::B256;
 use serde::Deserialize;
 use serde::Serialize;
+use std::cmp::Ordering;
 use std::collections::btree_map::Entry;
 use std::collections::BTreeMap;
 use std::sync::Arc;
 use std::sync::Mutex;
 
+/// Sweeping the expired items is linear in the size of the map, it runs at
+/// most once per interval. Expired items are never returned in between.
+const CLEAN_INTERVAL: Duration = Duration::from_secs(10);
+
 pub struct DaManager<T> {
     data: Mutex<BTreeMap<B256, DaItem<T>>>,
+    // `None`: bounded by the expiry only
+    capacity: Option<usize>,
+    next_clean: Mutex<Time>,
 }
 
 #[derive(Debug)]
@@ -74,21 +82,61 @@ impl<T> DaManager<T> {
     pub fn new() -> Self {
         DaManager {
             data: Mutex::new(BTreeMap::new()),
+            capacity: None,
+            next_clean: Mutex::new(Time::now()),
+        }
+    }
+
+    /// Once more than `capacity` items are stored, the ones closest to
+    /// their expiry are dropped first. With a common `alive_secs` that is
+    /// the least recently used.
+    pub fn with_capacity(capacity: usize) -> Self {
+        DaManager {
+            capacity: Some(capacity),
+            ..Self::new()
         }
     }
 
     fn clean(&self, raw: &mut BTreeMap<B256, DaItem<T>>) {
-        let mut keys = Vec::new();
-        for (k, item) in raw.iter() {
-            if item.is_dead() {
-                keys.push(*k);
-            }
+        let mut next_clean = self.next_clean.lock().unwrap();
+        if Time::now() < *next_clean {
+            return;
         }
-        for key in keys {
-            raw.remove(&key);
+        *next_clean = Time::now() + CLEAN_INTERVAL;
+        raw.retain(|_, item| !item.is_dead());
+    }
+
+    fn evict(&self, raw: &mut BTreeMap<B256, DaItem<T>>) {
+        let capacity = match self.capacity {
+            Some(capacity) if raw.len() > capacity => capacity,
+            _ => return,
+        };
+        // leave some room so the sort is amortised over the next inserts
+        let target = capacity - capacity / 10;
+        let mut by_age = raw
+            .iter()
+            .map(|(hash, item)| (&item.dead_time, *hash))
+            .collect::<Vec<_>>();
+        by_age.sort_by(|a, b| a.0.partial_cmp(b.0).unwrap_or(Ordering::Equal));
+        let evicted = by_age
+            .into_iter()
+            .take(raw.len() - target)
+            .map(|(_, hash)| hash)
+            .collect::<Vec<_>>();
+        for hash in evicted {
+            raw.remove(&hash);
         }
     }
 
+    /// Number of the stored items, the locked entries are not counted.
+    pub fn len(&self) -> usize {
+        let mut data = self.data.lock().unwrap();
+        self.clean(&mut data);
+        data.values()
+            .filter(|item| item.raw.is_some() && !item.is_dead())
+            .count()
+    }
+
     pub fn get(&self, hash: &B256) -> Option<Arc<T>> {
         let data = self.data.lock().unwrap();
         data.get(hash).map(|n| n.get()).flatten()
@@ -104,6 +152,40 @@ impl<T> DaManager<T> {
         da_item.touch();
 
         self.clean(&mut data);
+        self.evict(&mut data);
+    }
+
+    /// Puts all the items with a single cleanup, returns the stored values
+    /// so callers can share the copies that already exist.
+    pub fn put_many(&self, items: Vec<(B256, Arc<T>)>, alive_secs: u64) -> Vec<Arc<T>> {
+        let mut out = Vec::with_capacity(items.len());
+        let mut data = self.data.lock().unwrap();
+        self.clean(&mut data);
+        for (hash, raw) in items {
+            let da_item = data
+                .entry(hash)
+                .or_insert_with(|| DaItem::new(raw.clone(), alive_secs));
+            let raw = da_item.raw.get_or_insert(raw).clone();
+            da_item.touch();
+            out.push(raw);
+        }
+        self.evict(&mut data);
+        out
+    }
+
+    /// Returns the hashes that are not available, the available ones are
+    /// touched so they stay alive until the caller uses them.
+    pub fn missing(&self, hashes: &[B256]) -> Vec<B256> {
+        let mut out = Vec::new();
+        let mut data = self.data.lock().unwrap();
+        self.clean(&mut data);
+        for hash in hashes {
+            match data.get_mut(hash) {
+                Some(item) if item.raw.is_some() && !item.is_dead() => item.touch(),
+                _ => out.push(*hash),
+            }
+        }
+        out
     }
 
     pub fn try_lock(&self, hashes: &[B256], alive_secs: u64) -> Vec<DaItemLockStatus> {
@@ -111,6 +193,11 @@ impl<T> DaManager<T> {
         let mut data = self.data.lock().unwrap();
         for hash in hashes {
             match data.entry(*hash) {
+                // not swept yet, same as a vacant entry
+                Entry::Occupied(mut entry) if entry.get().is_dead() => {
+                    entry.insert(DaItem::lock(alive_secs));
+                    status.push(DaItemLockStatus::Locked)
+                }
                 Entry::Occupied(mut entry) => {
                     status.push(entry.get_mut().try_lock());
                 }
@@ -125,3 +212,44 @@ impl<T> DaManager<T> {
         status
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_capacity_evicts_the_oldest() {
+        let da = DaManager::with_capacity(2);
+        let hashes = (1..=3).map(B256::with_last_byte).collect::<Vec<_>>();
+        da.put(hashes[0], Arc::new(1), 10);
+        da.put(hashes[1], Arc::new(2), 30);
+        da.put(hashes[2], Arc::new(3), 20);
+        assert_eq!(da.len(), 2);
+        assert!(da.get(&hashes[0]).is_none());
+        assert_eq!(da.missing(&hashes), vec![hashes[0]]);
+
+        let stored = da.put_many(vec![(hashes[0], Arc::new(1))], 40);
+        assert_eq!(*stored[0], 1);
+        assert_eq!(da.len(), 2);
+        assert!(da.get(&hashes[2]).is_none());
+    }
+
+    #[test]
+    fn test_expired_lock_is_taken_over() {
+        let da = DaManager::<u64>::new();
+        let hash = B256::with_last_byte(1);
+        assert!(matches!(
+            da.try_lock(&[hash], 0)[0],
+            DaItemLockStatus::Locked
+        ));
+        std::thread::sleep(Duration::from_millis(10));
+        assert!(matches!(
+            da.try_lock(&[hash], 60)[0],
+            DaItemLockStatus::Locked
+        ));
+        assert!(matches!(
+            da.try_lock(&[hash], 60)[0],
+            DaItemLockStatus::Failed
+        ));
+    }
+}
@@ -2,10 +2,17 @@ mod api;
 pub use api::*;
 mod types;
 use linea_verifier::LineaBatchVerifier;
+use prover_types::TaskType;
 use scroll_verifier::ScrollBatchVerifier;
 pub use types::*;
+mod chain;
+pub use chain::*;
 mod da;
 pub use da::*;
+mod node_pool;
+pub use node_pool::*;
+mod upload;
+pub use upload::*;
 mod task_manager;
 pub use task_manager::*;
 mod metrics;
@@ -50,18 +57,51 @@ pub async fn entrypoint() {
 
     let keypair = Keypair::new();
 
-    let scroll = ScrollBatchVerifier::new(
-        cfg.scroll_endpoint.as_ref().map(|n| n.as_str()),
-        Some(Duration::from_secs(cfg.l2_timeout_secs)),
-    )
-    .unwrap();
+    let timeout = Some(Duration::from_secs(cfg.l2_timeout_secs));
+    let mut chains = ChainRegistry::new();
+    for chain in &cfg.chains {
+        let endpoint = chain.endpoint.as_ref().map(|n| n.as_str());
+        let ty = TaskType::from_u64(chain.task_type);
+        let registered = match ty {
+            TaskType::Scroll => chains.register(
+                ty,
+                Some(chain.chain_id),
+                ScrollBatchVerifier::new(endpoint, timeout).unwrap(),
+            ),
+            TaskType::Linea => chains.register(
+                ty,
+                Some(chain.chain_id),
+                LineaBatchVerifier::new(endpoint, timeout, chain.shomei.clone())
+                    .unwrap()
+                    .with_commit_threads(cfg.linea_commit_threads),
+            ),
+            TaskType::Other(_) => panic!("unsupported chain: {:?}", chain),
+        };
+        assert!(registered, "duplicated chain: {:?}", chain);
+    }
 
-    let linea = LineaBatchVerifier::new(
-        cfg.linea_endpoint.as_ref().map(|n| n.as_str()),
-        Some(Duration::from_secs(cfg.l2_timeout_secs)),
-        cfg.linea_shomei,
-    )
-    .unwrap();
+    // the legacy entries are still served unless the chain list replaces
+    // them
+    if cfg.scroll_endpoint.is_some() || !chains.contains(TaskType::Scroll) {
+        let scroll = ScrollBatchVerifier::new(
+            cfg.scroll_endpoint.as_ref().map(|n| n.as_str()),
+            timeout,
+        )
+        .unwrap();
+        let registered = chains.register(TaskType::Scroll, cfg.scroll_chain_id, scroll);
+        assert!(registered, "duplicated scroll chain: {:?}", cfg.scroll_chain_id);
+    }
+    if cfg.linea_endpoint.is_some() || !chains.contains(TaskType::Linea) {
+        let linea = LineaBatchVerifier::new(
+            cfg.linea_endpoint.as_ref().map(|n| n.as_str()),
+            timeout,
+            cfg.linea_shomei,
+        )
+        .unwrap()
+        .with_commit_threads(cfg.linea_commit_threads);
+        let registered = chains.register(TaskType::Linea, cfg.linea_chain_id, linea);
+        assert!(registered, "duplicated linea chain: {:?}", cfg.linea_chain_id);
+    }
 
     let l1_el = cfg
         .scroll_chain
@@ -76,13 +116,21 @@ pub async fn entrypoint() {
         force_with_context: opt.force_with_context,
         sampling: opt.sampling,
         l1_el,
-        scroll,
-        linea,
         task_mgr: Arc::new(TaskManager::new(100)),
         pobda_task_mgr: Arc::new(TaskManager::new(100)),
         pob_da: Arc::new(DaManager::new()),
+        node_pool: Arc::new(NodePool::new(cfg.server.node_pool_size)),
+        uploads: Arc::new(UploadManager::new(
+            cfg.server.upload_limit as _,
+            cfg.server.upload_budget as _,
+            cfg.server.max_uploads,
+        )),
         metrics: collector.clone(),
         keypair,
+        pob_codec: cfg.pob_codec,
+        chains: Arc::new(chains),
+        scroll_chain_id: cfg.scroll_chain_id,
+        server: cfg.server.clone(),
     };
 
     run_jsonrpc(&cfg.server, opt.port, api.rpc(), collector).await
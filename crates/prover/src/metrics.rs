@@ -57,7 +57,13 @@ impl Collector {
         );
         let gauge_prove_ms =
             registry.create_gauge(ns, "prover", "prove_ms", "gauge for generate poe", ["type"]);
-        let pob_size = registry.create_gauge(ns, "prover", "pob_size", "the size of pob", ["type"]);
+        let pob_size = registry.create_gauge(
+            ns,
+            "prover",
+            "pob_size",
+            "the wire size of the pob list",
+            ["type"],
+        );
         let rpc_call_ms = registry.create_gauge(
             ns,
             "prover",
@@ -0,0 +1,65 @@
+use std::sync::Arc;
+
+use alloy::primitives::{keccak256, Bytes};
+use prover_types::{Pob, B256};
+
+use crate::DaManager;
+
+pub const NODE_EXPIRED_SECS: u64 = 600;
+
+/// Content-addressed pool of the mpt nodes, codes, traces and proofs seen in
+/// uploaded PoBs, keyed by keccak. Consecutive batches share most of them, so
+/// clients only need to upload the ones reported by `da_missingNodes`.
+pub struct NodePool {
+    nodes: DaManager<Bytes>,
+    capacity: usize,
+}
+
+impl NodePool {
+    /// Keeps at most `capacity` items, the least recently used are evicted
+    /// first.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            nodes: DaManager::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn get(&self, hash: &B256) -> Option<Bytes> {
+        self.nodes.get(hash).map(|n| n.as_ref().clone())
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn missing(&self, hashes: &[B256]) -> Vec<B256> {
+        self.nodes.missing(hashes)
+    }
+
+    /// Adds the items of the PoBs to the pool and replaces them with the
+    /// pooled copies, so identical items across requests share memory.
+    pub fn intern(&self, pob_list: Vec<Pob>) -> Vec<Pob> {
+        let mut out = Vec::with_capacity(pob_list.len());
+        for mut pob in pob_list {
+            for items in pob.data.item_lists_mut() {
+                let hashed = items
+                    .drain(..)
+                    .map(|item| (keccak256(&item), Arc::new(item)))
+                    .collect();
+                *items = self
+                    .nodes
+                    .put_many(hashed, NODE_EXPIRED_SECS)
+                    .into_iter()
+                    .map(|item| item.as_ref().clone())
+                    .collect();
+            }
+            out.push(pob);
+        }
+        out
+    }
+}
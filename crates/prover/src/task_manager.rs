@@ -26,6 +26,16 @@ where
         }
     }
 
+    pub fn cap(&self) -> usize {
+        self.cap
+    }
+
+    /// Number of the tasks still being processed.
+    pub async fn pending(&self) -> usize {
+        let tasks = self.tasks.lock().await;
+        tasks.0.values().filter(|tc| tc.result.is_none()).count()
+    }
+
     async fn add_task(&self, task: K) -> Option<TaskContext<V, E>> {
         let mut tasks = self.tasks.lock().await;
         let result = match tasks.0.entry(task.clone()) {
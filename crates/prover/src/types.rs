@@ -1,10 +1,13 @@
 use std::{collections::BTreeMap, time::Duration};
 
-use alloy::primitives::Bytes;
+use alloy::primitives::{Address, Bytes};
 use base::format::debug;
 use jsonrpsee::{core::RpcResult, proc_macros::rpc};
 use linea_shomei::ShomeiConfig;
-use prover_types::{PoeResponse, ProveTaskParams, SuccinctPobList, B256};
+use prover_types::{
+    AggregatedPoeResponse, Base64Bytes, DeltaPobList, InterningCodec, PoeResponse, ProveTaskParams,
+    SuccinctPobList, B256,
+};
 use serde::{Deserialize, Serialize};
 
 use crate::DaItemLockStatus;
@@ -18,10 +21,24 @@ pub struct Config {
     pub scroll_chain_id: Option<u64>,
 
     pub linea_endpoint: Option<String>,
+    pub linea_chain_id: Option<u64>,
     pub linea_shomei: Option<ShomeiConfig>,
 
+    /// Chains served next to the legacy `scroll_*` and `linea_*` entries,
+    /// e.g. mainnet and Sepolia side by side.
+    #[serde(default)]
+    pub chains: Vec<ChainConfig>,
+
     #[serde(default = "default_l2_timeout_secs")]
     pub l2_timeout_secs: u64,
+
+    /// Threads committing the storage tries of a Linea block.
+    #[serde(default = "default_linea_commit_threads")]
+    pub linea_commit_threads: usize,
+
+    /// Compression of the interning blob in the generated context.
+    #[serde(default)]
+    pub pob_codec: InterningCodec,
 }
 
 impl Config {
@@ -31,6 +48,15 @@ impl Config {
     }
 }
 
+#[derive(Deserialize, Debug, Clone)]
+pub struct ChainConfig {
+    pub chain_id: u64,
+    pub task_type: u64,
+    pub endpoint: Option<String>,
+    /// Linea only.
+    pub shomei: Option<ShomeiConfig>,
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug, Default)]
 pub struct ScrollChain {
     pub endpoint: String,
@@ -46,6 +72,16 @@ pub struct ServerConfig {
     pub workers: usize,
     #[serde(default = "default_queue_size")]
     pub queue_size: usize,
+    #[serde(default = "default_upload_limit")]
+    pub upload_limit: usize,
+    /// Total size of the chunked uploads in progress.
+    #[serde(default = "default_upload_budget")]
+    pub upload_budget: usize,
+    #[serde(default = "default_max_uploads")]
+    pub max_uploads: usize,
+    /// Max number of the items kept in the node pool.
+    #[serde(default = "default_node_pool_size")]
+    pub node_pool_size: usize,
 }
 
 impl Default for ServerConfig {
@@ -55,6 +91,10 @@ impl Default for ServerConfig {
             body_limit: default_body_limit(),
             workers: default_worker(),
             queue_size: default_queue_size(),
+            upload_limit: default_upload_limit(),
+            upload_budget: default_upload_budget(),
+            max_uploads: default_max_uploads(),
+            node_pool_size: default_node_pool_size(),
         }
     }
 }
@@ -67,6 +107,22 @@ fn default_body_limit() -> usize {
     52428800
 }
 
+fn default_upload_limit() -> usize {
+    1073741824
+}
+
+fn default_upload_budget() -> usize {
+    4294967296
+}
+
+fn default_max_uploads() -> usize {
+    16
+}
+
+fn default_node_pool_size() -> usize {
+    1000000
+}
+
 fn default_worker() -> usize {
     10
 }
@@ -83,30 +139,91 @@ fn default_l2_timeout_secs() -> u64 {
     60
 }
 
+fn default_linea_commit_threads() -> usize {
+    1
+}
+
 #[rpc(server, namespace = "prover")]
 pub trait ProverV2Api {
     #[method(name = "proveTask")]
     async fn prove_task(&self, arg: ProveTaskParams) -> RpcResult<PoeResponse>;
 
+    #[method(name = "proveAggregatedTask")]
+    async fn prove_aggregated_task(
+        &self,
+        args: Vec<ProveTaskParams>,
+    ) -> RpcResult<AggregatedPoeResponse>;
+
     #[method(name = "proveTaskWithoutContext")]
     async fn prove_task_without_context(&self, task_data: Bytes, ty: u64) -> RpcResult<PoeResponse>;
 
+    /// `chain_id` is only required if several chains of the task type are
+    /// served, see `metadata`.
     #[method(name = "genContext")]
     async fn generate_context(
         &self,
         start_block: u64,
         end_block: u64,
         ty: u64,
+        chain_id: Option<u64>,
     ) -> RpcResult<SuccinctPobList>;
 
+    /// Same as `genContext` but returns the list in the binary wire format,
+    /// base64 encoded, see `prover_types::POB_WIRE_VERSION`.
+    #[method(name = "genContextWire")]
+    async fn generate_context_wire(
+        &self,
+        start_block: u64,
+        end_block: u64,
+        ty: u64,
+        chain_id: Option<u64>,
+    ) -> RpcResult<Base64Bytes>;
+
     #[method(name = "metadata")]
     async fn metadata(&self) -> RpcResult<Metadata>;
+
+    #[method(name = "verifyPoe")]
+    async fn verify_poe(
+        &self,
+        poe: PoeResponse,
+        signer: Option<Address>,
+    ) -> RpcResult<PoeVerification>;
 }
 
 #[rpc(server, namespace = "da")]
 pub trait DaApi {
+    /// Returns the `pob_hash` the list is stored under, uploads whose hashes
+    /// don't match their content are rejected.
     #[method(name = "putPob")]
-    async fn da_put_pob(&self, arg: SuccinctPobList) -> RpcResult<()>;
+    async fn da_put_pob(&self, arg: SuccinctPobList) -> RpcResult<B256>;
+    /// Takes the list in the binary wire format, base64 encoded.
+    #[method(name = "putPobWire")]
+    async fn da_put_pob_wire(&self, arg: Base64Bytes) -> RpcResult<B256>;
+    /// Upload a PoB list referencing the nodes already known by the prover,
+    /// see `missingNodes`.
+    #[method(name = "putPobDelta")]
+    async fn da_put_pob_delta(&self, arg: DeltaPobList) -> RpcResult<B256>;
+    /// Returns the node hashes the prover doesn't have.
+    #[method(name = "missingNodes")]
+    async fn da_missing_nodes(&self, arg: Vec<B256>) -> RpcResult<Vec<B256>>;
+    /// Starts a chunked upload of a PoB list in the binary wire format,
+    /// `parts` are the keccak hashes of the chunks in order.
+    #[method(name = "beginPobUpload")]
+    async fn da_begin_pob_upload(
+        &self,
+        pob_hash: B256,
+        parts: Vec<B256>,
+        size: u64,
+    ) -> RpcResult<()>;
+    #[method(name = "appendPobUpload")]
+    async fn da_append_pob_upload(
+        &self,
+        pob_hash: B256,
+        index: u64,
+        data: Base64Bytes,
+    ) -> RpcResult<()>;
+    #[method(name = "commitPobUpload")]
+    async fn da_commit_pob_upload(&self, pob_hash: B256) -> RpcResult<B256>;
     #[method(name = "tryLock")]
     async fn da_try_lock(&self, arg: B256) -> RpcResult<DaItemLockStatus>;
 }
@@ -124,4 +241,79 @@ pub struct Metadata {
     pub with_context: bool,
     pub version: &'static str,
     pub task_with_context: BTreeMap<u64, bool>,
+    pub pob_wire_version: u8,
+    pub chains: Vec<ChainMetadata>,
+    /// Address of the key signing the PoEs.
+    pub signer: Address,
+    pub attestation: AttestationMode,
+    pub limits: ProverLimits,
+    pub load: ProverLoad,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ChainMetadata {
+    pub task_type: u64,
+    /// None if the chain is served for any chain id of its task type.
+    pub chain_id: Option<u64>,
+    pub with_context: bool,
+    /// In activation order, each hardfork is active until the next one.
+    pub hardforks: Vec<HardforkMetadata>,
+    /// Versions of the DA batches accepted, empty if the chain has none.
+    pub batch_versions: Vec<u8>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HardforkMetadata {
+    pub name: String,
+    /// Only one of `block` and `timestamp` is set, depending on how the
+    /// hardfork is activated.
+    pub block: Option<u64>,
+    pub timestamp: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttestationMode {
+    /// The prover runs inside an SGX enclave and returns DCAP quotes.
+    Sgx,
+    Std,
+}
+
+impl AttestationMode {
+    pub fn current() -> Self {
+        if cfg!(feature = "tstd_enclave") {
+            Self::Sgx
+        } else {
+            Self::Std
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ProverLimits {
+    pub body_limit: usize,
+    pub upload_limit: usize,
+    pub upload_budget: usize,
+    pub max_uploads: usize,
+    /// Number of the proving tasks kept, including the finished ones.
+    pub task_queue_size: usize,
+    pub pob_ttl_secs: u64,
+    pub node_ttl_secs: u64,
+    pub upload_ttl_secs: u64,
+    pub max_pool_nodes: usize,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ProverLoad {
+    pub pending_tasks: usize,
+    pub pob_lists: usize,
+    pub pool_nodes: usize,
+    pub uploads: usize,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PoeVerification {
+    pub valid: bool,
+    pub signer: Address,
+    pub expected_signer: Address,
 }
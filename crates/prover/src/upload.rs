@@ -0,0 +1,333 @@
+use core::time::Duration;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use alloy::primitives::{keccak256, Bytes};
+use base::time::Time;
+use prover_types::B256;
+
+pub const UPLOAD_EXPIRED_SECS: u64 = 600;
+
+#[derive(Debug)]
+pub enum UploadError {
+    TooLarge { size: u64, limit: u64 },
+    TooManyUploads { limit: usize },
+    BudgetExceeded { size: u64, reserved: u64, budget: u64 },
+    EmptyUpload,
+    Conflict(B256),
+    NotFound(B256),
+    PartOutOfRange { index: u64, parts: usize },
+    PartHashMismatch { index: u64, expect: B256, got: B256 },
+    SizeExceeded { size: u64 },
+    Incomplete { missing: Vec<u64> },
+    SizeMismatch { expect: u64, got: u64 },
+}
+
+/// Chunked uploads of PoB lists keyed by `pob_hash`, for lists that don't
+/// fit in a single request under the server's body limit.
+///
+/// The client announces the keccak of every part in `begin`, each `append`
+/// is checked against it, and `commit` returns the reassembled payload once
+/// all the parts are received.
+///
+/// The announced size is reserved in `begin`: the sum over the uploads in
+/// progress is bounded by `budget`, and at most `max_uploads` of them are
+/// kept at a time.
+pub struct UploadManager {
+    limit: u64,
+    budget: u64,
+    max_uploads: usize,
+    uploads: Mutex<BTreeMap<B256, Upload>>,
+}
+
+struct Upload {
+    parts: Vec<B256>,
+    data: Vec<Option<Bytes>>,
+    size: u64,
+    received: u64,
+    dead_time: Time,
+}
+
+impl Upload {
+    fn touch(&mut self) {
+        self.dead_time = Time::now() + Duration::from_secs(UPLOAD_EXPIRED_SECS);
+    }
+}
+
+impl UploadManager {
+    pub fn new(limit: u64, budget: u64, max_uploads: usize) -> Self {
+        Self {
+            limit,
+            budget,
+            max_uploads,
+            uploads: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    pub fn budget(&self) -> u64 {
+        self.budget
+    }
+
+    pub fn max_uploads(&self) -> usize {
+        self.max_uploads
+    }
+
+    /// Number of the uploads in progress.
+    pub fn len(&self) -> usize {
+        let mut uploads = self.uploads.lock().unwrap();
+        Self::clean(&mut uploads);
+        uploads.len()
+    }
+
+    fn clean(uploads: &mut BTreeMap<B256, Upload>) {
+        let now = Time::now();
+        uploads.retain(|_, upload| upload.dead_time > now);
+    }
+
+    pub fn begin(&self, pob_hash: B256, parts: Vec<B256>, size: u64) -> Result<(), UploadError> {
+        if size > self.limit {
+            return Err(UploadError::TooLarge {
+                size,
+                limit: self.limit,
+            });
+        }
+        if parts.is_empty() {
+            return Err(UploadError::EmptyUpload);
+        }
+        let mut uploads = self.uploads.lock().unwrap();
+        Self::clean(&mut uploads);
+        if let Some(upload) = uploads.get_mut(&pob_hash) {
+            // restarting the same upload is fine, the received parts are kept
+            if upload.parts != parts || upload.size != size {
+                return Err(UploadError::Conflict(pob_hash));
+            }
+            upload.touch();
+            return Ok(());
+        }
+        if uploads.len() >= self.max_uploads {
+            return Err(UploadError::TooManyUploads {
+                limit: self.max_uploads,
+            });
+        }
+        let reserved = uploads.values().map(|upload| upload.size).sum::<u64>();
+        if reserved + size > self.budget {
+            return Err(UploadError::BudgetExceeded {
+                size,
+                reserved,
+                budget: self.budget,
+            });
+        }
+        let mut upload = Upload {
+            data: vec![None; parts.len()],
+            parts,
+            size,
+            received: 0,
+            dead_time: Time::now(),
+        };
+        upload.touch();
+        uploads.insert(pob_hash, upload);
+        Ok(())
+    }
+
+    pub fn append(&self, pob_hash: B256, index: u64, data: Bytes) -> Result<(), UploadError> {
+        let mut uploads = self.uploads.lock().unwrap();
+        Self::clean(&mut uploads);
+        let upload = uploads
+            .get_mut(&pob_hash)
+            .ok_or(UploadError::NotFound(pob_hash))?;
+        let idx = index as usize;
+        let expect = *upload.parts.get(idx).ok_or(UploadError::PartOutOfRange {
+            index,
+            parts: upload.parts.len(),
+        })?;
+        let got = keccak256(&data);
+        if got != expect {
+            return Err(UploadError::PartHashMismatch {
+                index,
+                expect,
+                got,
+            });
+        }
+        upload.touch();
+        if upload.data[idx].is_some() {
+            return Ok(());
+        }
+        let received = upload.received + data.len() as u64;
+        if received > upload.size {
+            return Err(UploadError::SizeExceeded { size: upload.size });
+        }
+        upload.received = received;
+        upload.data[idx] = Some(data);
+        Ok(())
+    }
+
+    /// Removes the upload and returns the reassembled payload, an incomplete
+    /// upload is kept so the client can send the missing parts.
+    pub fn commit(&self, pob_hash: &B256) -> Result<Vec<u8>, UploadError> {
+        let mut uploads = self.uploads.lock().unwrap();
+        Self::clean(&mut uploads);
+        let upload = uploads
+            .get(pob_hash)
+            .ok_or(UploadError::NotFound(*pob_hash))?;
+        let missing = upload
+            .data
+            .iter()
+            .enumerate()
+            .filter(|(_, part)| part.is_none())
+            .map(|(idx, _)| idx as u64)
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(UploadError::Incomplete { missing });
+        }
+        if upload.received != upload.size {
+            return Err(UploadError::SizeMismatch {
+                expect: upload.size,
+                got: upload.received,
+            });
+        }
+        let upload = uploads.remove(pob_hash).unwrap();
+        let mut out = Vec::with_capacity(upload.size as usize);
+        for part in upload.data.into_iter().flatten() {
+            out.extend_from_slice(&part);
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn split(data: &[u8], chunk: usize) -> (Vec<Bytes>, Vec<B256>) {
+        let parts = data
+            .chunks(chunk)
+            .map(Bytes::copy_from_slice)
+            .collect::<Vec<_>>();
+        let hashes = parts.iter().map(keccak256).collect();
+        (parts, hashes)
+    }
+
+    #[test]
+    fn test_upload() {
+        let mgr = UploadManager::new(1024, 4096, 4);
+        let data = (0..100u8).collect::<Vec<_>>();
+        let (parts, hashes) = split(&data, 30);
+        let hash = B256::with_last_byte(1);
+        mgr.begin(hash, hashes.clone(), data.len() as _).unwrap();
+        // restarting with the same parts is allowed
+        mgr.begin(hash, hashes.clone(), data.len() as _).unwrap();
+        assert!(matches!(
+            mgr.begin(hash, hashes[1..].to_vec(), data.len() as _),
+            Err(UploadError::Conflict(_))
+        ));
+
+        for index in [3, 1, 0] {
+            mgr.append(hash, index, parts[index as usize].clone())
+                .unwrap();
+        }
+        // a part sent twice is only counted once
+        mgr.append(hash, 1, parts[1].clone()).unwrap();
+        match mgr.commit(&hash) {
+            Err(UploadError::Incomplete { missing }) => assert_eq!(missing, vec![2]),
+            other => panic!("unexpected: {:?}", other),
+        }
+        assert!(matches!(
+            mgr.append(hash, 2, parts[1].clone()),
+            Err(UploadError::PartHashMismatch { index: 2, .. })
+        ));
+        assert!(matches!(
+            mgr.append(hash, 4, parts[2].clone()),
+            Err(UploadError::PartOutOfRange { index: 4, parts: 4 })
+        ));
+        mgr.append(hash, 2, parts[2].clone()).unwrap();
+
+        assert_eq!(mgr.len(), 1);
+        assert_eq!(mgr.commit(&hash).unwrap(), data);
+        assert_eq!(mgr.len(), 0);
+        assert!(matches!(mgr.commit(&hash), Err(UploadError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_upload_size() {
+        let mgr = UploadManager::new(1024, 4096, 4);
+        let data = vec![1u8; 100];
+        let (parts, hashes) = split(&data, 50);
+        let hash = B256::with_last_byte(1);
+        assert!(matches!(
+            mgr.begin(hash, hashes.clone(), 1025),
+            Err(UploadError::TooLarge { size: 1025, .. })
+        ));
+        assert!(matches!(
+            mgr.begin(hash, vec![], 10),
+            Err(UploadError::EmptyUpload)
+        ));
+
+        mgr.begin(hash, hashes.clone(), 60).unwrap();
+        mgr.append(hash, 0, parts[0].clone()).unwrap();
+        assert!(matches!(
+            mgr.append(hash, 1, parts[1].clone()),
+            Err(UploadError::SizeExceeded { size: 60 })
+        ));
+
+        let hash = B256::with_last_byte(2);
+        mgr.begin(hash, hashes, 120).unwrap();
+        for (index, part) in parts.into_iter().enumerate() {
+            mgr.append(hash, index as _, part).unwrap();
+        }
+        assert!(matches!(
+            mgr.commit(&hash),
+            Err(UploadError::SizeMismatch {
+                expect: 120,
+                got: 100
+            })
+        ));
+    }
+
+    #[test]
+    fn test_upload_limits() {
+        let mgr = UploadManager::new(1024, 2048, 3);
+        let parts = vec![B256::ZERO];
+        for n in 1..=2 {
+            mgr.begin(B256::with_last_byte(n), parts.clone(), 1000)
+                .unwrap();
+        }
+        assert!(matches!(
+            mgr.begin(B256::with_last_byte(3), parts.clone(), 100),
+            Err(UploadError::BudgetExceeded { reserved: 2000, .. })
+        ));
+        mgr.begin(B256::with_last_byte(3), parts.clone(), 10)
+            .unwrap();
+        assert!(matches!(
+            mgr.begin(B256::with_last_byte(4), parts.clone(), 10),
+            Err(UploadError::TooManyUploads { limit: 3 })
+        ));
+    }
+
+    #[test]
+    fn test_upload_expired() {
+        let mgr = UploadManager::new(1024, 1024, 1);
+        let data = vec![1u8; 100];
+        let (parts, hashes) = split(&data, 100);
+        let hash = B256::with_last_byte(1);
+        mgr.begin(hash, hashes.clone(), 100).unwrap();
+        mgr.uploads
+            .lock()
+            .unwrap()
+            .get_mut(&hash)
+            .unwrap()
+            .dead_time = Time::now();
+        std::thread::sleep(Duration::from_millis(10));
+
+        assert_eq!(mgr.len(), 0);
+        assert!(matches!(
+            mgr.append(hash, 0, parts[0].clone()),
+            Err(UploadError::NotFound(_))
+        ));
+        // the expired upload doesn't hold its slot and budget
+        mgr.begin(B256::with_last_byte(2), hashes, 100).unwrap();
+    }
+}
@@ -1,7 +1,7 @@
 use std::collections::{BTreeMap, BTreeSet};
 
 use base::PrimitivesConvert;
-use prover_types::{Pob, PobBlock, PobData};
+use prover_types::{ChainData, Pob, PobBlock, PobData, ScrollData, POB_DATA_VERSION};
 use scroll_executor::{
     eth_types::l2_types::StorageTrace, BlockTrace, Bytes, EthPrimitivesConvert, B256,
 };
@@ -51,17 +51,17 @@ pub fn block_trace_to_pob(trace: BlockTrace) -> Option<Pob<Bytes>> {
     let mpt_nodes = collect_mpt_nodes(trace.storage_trace);
 
     let data = PobData {
+        version: POB_DATA_VERSION,
         chain_id: trace.chain_id,
         coinbase: Some(trace.coinbase.address.to()),
         prev_state_root,
         block_hashes: BTreeMap::new(),
         mpt_nodes,
         codes,
-        start_l1_queue_index: trace.start_l1_queue_index,
-        withdrawal_root: trace.withdraw_trie_root.0.into(),
-        linea_proofs: vec![],
-        linea_traces: vec![],
-        linea_zkroot: B256::default(),
+        chain: ChainData::Scroll(ScrollData {
+            start_l1_queue_index: trace.start_l1_queue_index,
+            withdrawal_root: trace.withdraw_trie_root.0.into(),
+        }),
     };
     Some(Pob {
         block,
@@ -121,7 +121,11 @@ impl Context for PobContext {
 
     #[inline]
     fn withdrawal_root(&self) -> B256 {
-        self.pob.data.withdrawal_root
+        self.pob
+            .data
+            .scroll()
+            .expect("should be a scroll pob")
+            .withdrawal_root
     }
 
     #[inline]